@@ -1,14 +1,141 @@
-use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
+use crate::discover;
+use crate::manifest::TemplateManifest;
+use crate::registry;
+use crate::utils;
+
+use anyhow::{Context, Result, bail};
+use convert_case::{Case, Casing};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ScafferConfig {
+    #[serde(default, deserialize_with = "string_or_vec")]
     pub scaffer: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scaffer_template_urls: Option<HashMap<String, String>>,
+    /// URLs of registry indexes whose templates are made available
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registries: Vec<String>,
+    /// Whether local or global templates win when both provide the same name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_precedence: Option<TemplatePrecedence>,
+    /// Globs of destination paths that are overwritten without prompting
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auto_overwrite: Vec<String>,
+    /// Globs of variable names that are never asked for nor substituted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_vars: Vec<String>,
+    /// Tokens that are never variables, in addition to the built-in ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub substitution_denylist: Vec<String>,
+    /// Syntax check commands used by `--validate`, keyed by file extension
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub validators: HashMap<String, String>,
+    /// Prefix of template variable tokens (`scf` unless set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// Case styles template variables are recognized in (all unless set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cases: Option<Vec<String>>,
+    /// Command that `scaffer g --open` opens the written files with (global only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    /// Proxy for template and registry downloads when `HTTPS_PROXY`/`HTTP_PROXY`
+    /// are not set (global only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Directory downloaded templates are extracted in (the system temp directory
+    /// unless set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temp_dir: Option<String>,
+    /// Directory of partials shared by all templates, searched by includes after the
+    /// template's own `_partials` directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partials_dir: Option<String>,
+    /// Register every directory with a `scaffer.toml` below this configuration's
+    /// directory as a template (local only)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub discover: bool,
+    /// Serve names that no template has from the wildcard entries of
+    /// `scaffer_template_urls` (off unless set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wildcard_urls: Option<bool>,
+    /// Case variable names are reported in (kebab-case unless set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internal_case: Option<InternalCase>,
+    /// Values of variables that templates don't give a `default` for, used instead
+    /// of prompting (e.g. `author`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub global_var_defaults: HashMap<String, String>,
+    /// The global configuration this one is merged with, read once by `load`; a
+    /// configuration built otherwise has an empty one and never reads the home directory
+    #[serde(skip)]
+    pub global: Option<Box<ScafferConfig>>,
+}
+
+/// Stands in for the global configuration of a configuration that has none
+static NO_GLOBAL: LazyLock<ScafferConfig> = LazyLock::new(ScafferConfig::default);
+
+/// Which configuration is searched first for templates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplatePrecedence {
+    #[default]
+    Local,
+    Global,
+}
+
+/// The case variable names are keyed and reported in: `my-var` or `my_var`
+///
+/// Variables given in any case are the same variable, and tokens are substituted in
+/// all their case styles either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InternalCase {
+    #[default]
+    Kebab,
+    Snake,
+}
+
+impl InternalCase {
+    /// Write a (kebab-case) variable name in this case
+    pub fn name(self, var_name: &str) -> String {
+        match self {
+            InternalCase::Kebab => var_name.to_string(),
+            InternalCase::Snake => var_name.replace('-', "_"),
+        }
+    }
+}
+
+/// Where an available template comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateSource {
+    Directory(PathBuf),
+    Url(String),
+    Registry { registry: String, url: String },
+}
+
+impl fmt::Display for TemplateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateSource::Directory(path) => write!(f, "{}", path.display()),
+            TemplateSource::Url(url) => write!(f, "{url}"),
+            TemplateSource::Registry { registry, url } => write!(f, "{url} (registry {registry})"),
+        }
+    }
+}
+
+/// An available template and where it was found
+#[derive(Debug, Clone)]
+pub struct TemplateInfo {
+    pub name: String,
+    pub source: TemplateSource,
+    pub description: Option<String>,
 }
 
 impl ScafferConfig {
@@ -30,18 +157,88 @@ impl ScafferConfig {
             .insert(name, url);
     }
 
-    /// Load scaffer configuration from current directory or parent directories
+    /// Parse configuration from JSON text, naming `source` in any error
+    pub fn from_json_str(content: &str, source: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(content)
+            .with_context(|| format!("Failed to parse {source}: invalid JSON"))?;
+        Self::from_json_value(value, source)
+    }
+
+    /// Build configuration from a parsed JSON value, naming `source` in any error
+    ///
+    /// The shape is validated first so that a mistyped field produces a message
+    /// naming the field and the expected type rather than a raw serde error.
+    pub fn from_json_value(value: serde_json::Value, source: &str) -> Result<Self> {
+        validate_shape(&value).with_context(|| format!("Invalid configuration in {source}"))?;
+        serde_json::from_value(value).with_context(|| format!("Failed to parse {source}"))
+    }
+
+    /// Load scaffer configuration from current directory or parent directories,
+    /// along with the global configuration it is merged with
+    ///
+    /// Environment variable references in template directories and URLs are expanded.
     pub fn load() -> Result<Self> {
+        let global_config = Self::load_global()?;
+        let Some(path) = Self::find_local_file()? else {
+            // No configuration found, return default
+            return Ok(Self::default().with_global(global_config));
+        };
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let mut config = if path.file_name() == Some(std::ffi::OsStr::new("package.json")) {
+            let package_data: serde_json::Value = serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            let source = format!("\"scaffer\" key of {}", path.display());
+            Self::from_json_value(package_data["scaffer"].clone(), &source)?
+        } else {
+            Self::from_json_str(&content, &path.display().to_string())?
+        };
+
+        config
+            .expand_env()
+            .with_context(|| format!("Invalid configuration in {}", path.display()))?;
+        Ok(config.with_global(global_config))
+    }
+
+    /// Merge this (local) configuration with `global_config` instead of an empty one
+    pub fn with_global(mut self, global_config: Self) -> Self {
+        self.global = Some(Box::new(global_config));
+        self
+    }
+
+    /// The global configuration this one is merged with
+    fn global(&self) -> &Self {
+        self.global.as_deref().unwrap_or(&NO_GLOBAL)
+    }
+
+    /// Expand `${VAR}` and `${VAR:-default}` references in template directories,
+    /// template URLs and registry URLs
+    fn expand_env(&mut self) -> Result<()> {
+        for value in self.scaffer.iter_mut().chain(&mut self.registries) {
+            *value = utils::expand_env(value)?;
+        }
+        for url in self
+            .scaffer_template_urls
+            .iter_mut()
+            .flat_map(|urls| urls.values_mut())
+        {
+            *url = utils::expand_env(url)?;
+        }
+        Ok(())
+    }
+
+    /// Find the local configuration file: the nearest `scaffer.json`, or `package.json`
+    /// with a "scaffer" key, in the current directory or its parents
+    pub fn find_local_file() -> Result<Option<PathBuf>> {
         let mut current_dir = std::env::current_dir().context("Failed to get current directory")?;
 
         loop {
             // Try scaffer.json first
             let scaffer_json = current_dir.join("scaffer.json");
             if scaffer_json.exists() {
-                let content = fs::read_to_string(&scaffer_json)
-                    .with_context(|| format!("Failed to read {}", scaffer_json.display()))?;
-                return serde_json::from_str(&content)
-                    .with_context(|| format!("Failed to parse {}", scaffer_json.display()));
+                return Ok(Some(scaffer_json));
             }
 
             // Try package.json with scaffer key
@@ -51,10 +248,8 @@ impl ScafferConfig {
                     .with_context(|| format!("Failed to read {}", package_json.display()))?;
 
                 if let Ok(package_data) = serde_json::from_str::<serde_json::Value>(&content) {
-                    if let Some(scaffer_config) = package_data.get("scaffer") {
-                        if let Ok(config) = serde_json::from_value::<Self>(scaffer_config.clone()) {
-                            return Ok(config);
-                        }
+                    if package_data.get("scaffer").is_some() {
+                        return Ok(Some(package_json));
                     }
                 }
             }
@@ -63,24 +258,25 @@ impl ScafferConfig {
             if let Some(parent) = current_dir.parent() {
                 current_dir = parent.to_path_buf();
             } else {
-                break;
+                return Ok(None);
             }
         }
+    }
 
-        // No configuration found, return default
-        Ok(Self::default())
+    /// Path of the global configuration file in the user's home directory
+    pub fn global_file() -> Result<PathBuf> {
+        let home_dir = dirs::home_dir().context("Failed to get home directory")?;
+        Ok(home_dir.join(".scaffer.json"))
     }
 
     /// Load global scaffer configuration from user's home directory
     pub fn load_global() -> Result<Self> {
-        let home_dir = dirs::home_dir().context("Failed to get home directory")?;
-        let global_config_path = home_dir.join(".scaffer.json");
+        let global_config_path = Self::global_file()?;
 
         if global_config_path.exists() {
             let content = fs::read_to_string(&global_config_path)
                 .with_context(|| format!("Failed to read {}", global_config_path.display()))?;
-            serde_json::from_str(&content)
-                .with_context(|| format!("Failed to parse {}", global_config_path.display()))
+            Self::from_json_str(&content, &global_config_path.display().to_string())
         } else {
             Ok(Self::default())
         }
@@ -88,8 +284,7 @@ impl ScafferConfig {
 
     /// Save global scaffer configuration to user's home directory
     pub fn save_global(&self) -> Result<()> {
-        let home_dir = dirs::home_dir().context("Failed to get home directory")?;
-        let global_config_path = home_dir.join(".scaffer.json");
+        let global_config_path = Self::global_file()?;
 
         let content =
             serde_json::to_string_pretty(self).context("Failed to serialize configuration")?;
@@ -101,68 +296,837 @@ impl ScafferConfig {
     }
 
     /// Get all template directories, merging local and global configurations
-    pub fn get_template_directories(&self) -> Result<Vec<PathBuf>> {
-        let mut directories = Vec::new();
+    ///
+    /// Directories are returned in search order as set by `template_precedence`;
+    /// a template name present in several directories resolves to the first match.
+    pub fn get_template_directories(&self) -> Vec<PathBuf> {
+        self.by_precedence()
+            .iter()
+            .flat_map(|config| config.scaffer.iter().map(PathBuf::from))
+            .collect()
+    }
+
+    /// Order this (local) configuration and the global one by template precedence, highest first
+    fn by_precedence(&self) -> [&Self; 2] {
+        let global_config = self.global();
+        let precedence = self
+            .template_precedence
+            .or(global_config.template_precedence)
+            .unwrap_or_default();
 
-        // Add local template directories
-        for path in &self.scaffer {
-            directories.push(PathBuf::from(path));
+        match precedence {
+            TemplatePrecedence::Local => [self, global_config],
+            TemplatePrecedence::Global => [global_config, self],
         }
+    }
 
-        // Add global template directories
-        let global_config = Self::load_global()?;
-        for path in &global_config.scaffer {
-            directories.push(PathBuf::from(path));
+    /// Get the template directories discovered below the local configuration's
+    /// directory, if it sets `discover`
+    pub fn get_discovered_templates(&self) -> Result<Vec<PathBuf>> {
+        if !self.discover {
+            return Ok(Vec::new());
         }
+        let Some(root) = Self::find_local_file()?
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+        else {
+            return Ok(Vec::new());
+        };
+
+        Ok(discover::discover_templates(&root))
+    }
+
+    /// Get validator commands, merging local and global configurations
+    pub fn get_validators(&self) -> HashMap<String, String> {
+        let mut validators = self.global().validators.clone();
+
+        // Local validators override global ones for the same extension
+        validators.extend(self.validators.clone());
+
+        validators
+    }
 
-        Ok(directories)
+    /// Get the fallback variable values, keyed in kebab-case, merging local and global
+    /// configurations
+    pub fn get_global_var_defaults(&self) -> HashMap<String, String> {
+        // Local defaults override global ones for the same variable
+        self.global()
+            .global_var_defaults
+            .iter()
+            .chain(&self.global_var_defaults)
+            .map(|(name, value)| (name.to_case(Case::Kebab), value.clone()))
+            .collect()
     }
 
-    /// Get all template URLs, merging local and global configurations
-    pub fn get_template_urls(&self) -> Result<HashMap<String, String>> {
+    /// Get the variable token prefix, from local or else global configuration
+    pub fn get_prefix(&self) -> Option<String> {
+        self.prefix.clone().or_else(|| self.global().prefix.clone())
+    }
+
+    /// Get the variable case styles, from local or else global configuration
+    pub fn get_cases(&self) -> Option<Vec<String>> {
+        self.cases.clone().or_else(|| self.global().cases.clone())
+    }
+
+    /// Get the case variable names are reported in, from local or else global
+    /// configuration
+    pub fn get_internal_case(&self) -> InternalCase {
+        self.internal_case
+            .or(self.global().internal_case)
+            .unwrap_or_default()
+    }
+
+    /// Whether wildcard template URL entries are used, from local or else global
+    /// configuration
+    pub fn get_wildcard_urls(&self) -> bool {
+        self.wildcard_urls
+            .or(self.global().wildcard_urls)
+            .unwrap_or_default()
+    }
+
+    /// Get the shared partials directory, from local or else global configuration
+    pub fn get_partials_dir(&self) -> Option<String> {
+        self.partials_dir
+            .clone()
+            .or_else(|| self.global().partials_dir.clone())
+    }
+
+    /// Get the directory for temporary template files, from local or else global
+    /// configuration
+    pub fn get_temp_dir(&self) -> Option<String> {
+        self.temp_dir
+            .clone()
+            .or_else(|| self.global().temp_dir.clone())
+    }
+
+    /// Get auto-overwrite globs, merging local and global configurations
+    pub fn get_auto_overwrite(&self) -> Vec<String> {
+        let mut patterns = self.auto_overwrite.clone();
+        patterns.extend(self.global().auto_overwrite.iter().cloned());
+        patterns
+    }
+
+    /// Get ignored variable globs, merging local and global configurations
+    pub fn get_ignore_vars(&self) -> Vec<String> {
+        let mut patterns = self.ignore_vars.clone();
+        patterns.extend(self.global().ignore_vars.iter().cloned());
+        patterns
+    }
+
+    /// Get the tokens that are never variables, merging local and global configurations
+    pub fn get_substitution_denylist(&self) -> Vec<String> {
+        let mut tokens = self.substitution_denylist.clone();
+        tokens.extend(self.global().substitution_denylist.iter().cloned());
+        tokens
+    }
+
+    /// Get all registry URLs, merging local and global configurations
+    pub fn get_registries(&self) -> Vec<String> {
+        let mut registries = self.registries.clone();
+
+        for url in &self.global().registries {
+            if !registries.contains(url) {
+                registries.push(url.clone());
+            }
+        }
+
+        registries
+    }
+
+    /// Get all templates offered by configured registries, keyed by name
+    ///
+    /// Registries that cannot be fetched are reported and skipped.
+    pub fn get_registry_templates(&self) -> HashMap<String, TemplateInfo> {
+        let mut templates = HashMap::new();
+
+        for registry_url in self.get_registries() {
+            let index = match registry::load_index(&registry_url) {
+                Ok(index) => index,
+                Err(err) => {
+                    eprintln!("Warning: {err:#}");
+                    continue;
+                }
+            };
+
+            for (name, entry) in index.templates {
+                // Earlier registries take precedence
+                templates.entry(name.clone()).or_insert(TemplateInfo {
+                    name,
+                    source: TemplateSource::Registry {
+                        registry: registry_url.clone(),
+                        url: entry.url,
+                    },
+                    description: entry.description,
+                });
+            }
+        }
+
+        templates
+    }
+
+    /// Get all template URLs, merging registry, local and global configurations
+    pub fn get_template_urls(&self) -> HashMap<String, String> {
         let mut urls = HashMap::new();
 
-        // Add global template URLs
-        let global_config = Self::load_global()?;
-        if let Some(global_urls) = &global_config.scaffer_template_urls {
-            urls.extend(global_urls.clone());
+        // Add registry template URLs (lowest precedence)
+        for (name, info) in self.get_registry_templates() {
+            if let TemplateSource::Registry { url, .. } = info.source {
+                urls.insert(name, url);
+            }
         }
 
-        // Add local template URLs (these override global ones with same name)
-        if let Some(local_urls) = &self.scaffer_template_urls {
-            urls.extend(local_urls.clone());
+        // Add configured template URLs, lowest precedence first so higher ones override
+        for config in self.by_precedence().iter().rev() {
+            if let Some(config_urls) = &config.scaffer_template_urls {
+                urls.extend(config_urls.clone());
+            }
         }
 
-        Ok(urls)
+        urls
     }
 
-    /// Find all available templates
-    pub fn find_templates(&self) -> Result<Vec<String>> {
+    /// List all available templates along with their sources, sorted by name
+    ///
+    /// Templates with the same name are in the order `scaffer g` searches them:
+    /// configured URLs, then registries, then template directories (local and global
+    /// ones ordered by `template_precedence`), then discovered templates. The first
+    /// of them is the one that is used; the others are shadowed by it.
+    pub fn list_templates(&self) -> Result<Vec<TemplateInfo>> {
         let mut templates = Vec::new();
 
-        // Find directory-based templates
-        for dir in self.get_template_directories()? {
-            if dir.exists() && dir.is_dir() {
-                for entry in fs::read_dir(&dir)
-                    .with_context(|| format!("Failed to read directory {}", dir.display()))?
-                {
-                    let entry = entry?;
-                    if entry.file_type()?.is_dir() {
-                        if let Some(name) = entry.file_name().to_str() {
-                            templates.push(name.to_string());
-                        }
-                    }
-                }
+        // URL-based templates from local and global configuration
+        for urls in self
+            .by_precedence()
+            .into_iter()
+            .filter_map(|config| config.scaffer_template_urls.as_ref())
+        {
+            // Wildcard entries only name the templates they serve when asked for
+            for (name, url) in urls.iter().filter(|(name, _)| !is_wildcard(name)) {
+                templates.push(TemplateInfo {
+                    name: name.clone(),
+                    source: TemplateSource::Url(url.clone()),
+                    description: None,
+                });
             }
         }
 
-        // Add URL-based templates
-        for name in self.get_template_urls()?.keys() {
-            templates.push(name.clone());
+        // Registry templates
+        let mut registry_templates: Vec<TemplateInfo> =
+            self.get_registry_templates().into_values().collect();
+        registry_templates.sort_by(|a, b| a.name.cmp(&b.name));
+        templates.extend(registry_templates);
+
+        templates.extend(self.local_templates()?);
+
+        // A stable sort keeps the search order among templates of the same name
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(templates)
+    }
+
+    /// Directory templates, from the template directories and then those discovered
+    /// in the repository, in search order
+    fn local_templates(&self) -> Result<Vec<TemplateInfo>> {
+        let mut templates = directory_templates(&self.get_template_directories())?;
+
+        // Templates discovered in the repository, unless a template directory
+        // already provides them
+        for path in self.get_discovered_templates()? {
+            let already_listed = templates.iter().any(|info| match &info.source {
+                TemplateSource::Directory(listed) => same_path(listed, &path),
+                _ => false,
+            });
+            if already_listed {
+                continue;
+            }
+            if let Some(name) = template_name(&path) {
+                templates.push(TemplateInfo {
+                    name,
+                    description: manifest_description(&path),
+                    source: TemplateSource::Directory(path.clone()),
+                });
+            }
         }
 
-        templates.sort();
+        Ok(templates)
+    }
+
+    /// Find the directory template named `name`, by manifest `name` or else directory
+    /// name; the first match in search order wins
+    pub fn find_local_template(&self, name: &str) -> Result<Option<PathBuf>> {
+        Ok(self
+            .local_templates()?
+            .into_iter()
+            .find(|info| info.name == name)
+            .and_then(|info| match info.source {
+                TemplateSource::Directory(path) => Some(path),
+                _ => None,
+            }))
+    }
+
+    /// The templates named `name` in the local and global template directories, in
+    /// search order; unlike `template_candidates`, no registry is consulted
+    pub fn directory_candidates(&self, name: &str) -> Result<Vec<TemplateInfo>> {
+        Ok(directory_templates(&self.get_template_directories())?
+            .into_iter()
+            .filter(|info| info.name == name)
+            .collect())
+    }
+
+    /// All templates named `name` in search order; the first is the one that is used
+    pub fn template_candidates(&self, name: &str) -> Result<Vec<TemplateInfo>> {
+        Ok(self
+            .list_templates()?
+            .into_iter()
+            .filter(|info| info.name == name)
+            .collect())
+    }
+
+    /// Find all available template names
+    pub fn find_templates(&self) -> Result<Vec<String>> {
+        let mut templates: Vec<String> = self
+            .list_templates()?
+            .into_iter()
+            .map(|info| info.name)
+            .collect();
+
         templates.dedup();
         Ok(templates)
     }
 }
+
+/// Whether a template URL entry's name is a pattern serving many templates
+pub fn is_wildcard(name: &str) -> bool {
+    name.contains('*')
+}
+
+/// The URL of the most specific wildcard entry matching a template name, where a
+/// longer pattern is more specific (`acme-*` before `*`)
+pub fn wildcard_url<'a>(urls: &'a HashMap<String, String>, name: &str) -> Option<&'a String> {
+    urls.iter()
+        .filter(|(pattern, _)| is_wildcard(pattern))
+        .filter(|(pattern, _)| {
+            globset::Glob::new(pattern).is_ok_and(|glob| glob.compile_matcher().is_match(name))
+        })
+        .max_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| b.cmp(a)))
+        .map(|(_, url)| url)
+}
+
+/// The templates in the given template directories, in search order
+///
+/// Within one directory, templates are ordered by directory name.
+fn directory_templates(dirs: &[PathBuf]) -> Result<Vec<TemplateInfo>> {
+    let mut templates = Vec::new();
+    for dir in dirs.iter().filter(|dir| dir.is_dir()) {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory {}", dir.display()))?
+        {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+
+        for path in paths {
+            if let Some(name) = template_name(&path) {
+                templates.push(TemplateInfo {
+                    name,
+                    description: manifest_description(&path),
+                    source: TemplateSource::Directory(path),
+                });
+            }
+        }
+    }
+    Ok(templates)
+}
+
+/// The name of the template in `path`: the `name` declared in its manifest, else its
+/// directory name
+fn template_name(path: &Path) -> Option<String> {
+    manifest_name(path).or_else(|| path.file_name()?.to_str().map(str::to_string))
+}
+
+/// The `name` declared in the manifest of the template in `path`, if any
+pub fn manifest_name(path: &Path) -> Option<String> {
+    TemplateManifest::load(path)
+        .ok()?
+        .name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// The first line of the `description` declared in the manifest of the template in
+/// `path`, if any
+fn manifest_description(path: &Path) -> Option<String> {
+    let description = TemplateManifest::load(path).ok()?.description?;
+    description
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+/// Whether two paths name the same file, comparing canonical paths when possible
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Rename a URL template in a `scaffer.json`-style configuration file, leaving the
+/// rest of the file as it is
+///
+/// Returns false if the file has no URL template named `old`.
+pub fn rename_template_url(path: &Path, old: &str, new: &str) -> Result<bool> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut value: serde_json::Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let Some(urls) = value
+        .get_mut("scaffer_template_urls")
+        .and_then(|urls| urls.as_object_mut())
+    else {
+        return Ok(false);
+    };
+    if urls.contains_key(new) {
+        bail!("Template '{new}' already exists in {}", path.display());
+    }
+    let Some(url) = urls.remove(old) else {
+        return Ok(false);
+    };
+    urls.insert(new.to_string(), url);
+
+    let content =
+        serde_json::to_string_pretty(&value).context("Failed to serialize configuration")?;
+    fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(true)
+}
+
+/// Accept either a single string or an array of strings
+fn string_or_vec<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrVec {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match StringOrVec::deserialize(deserializer)? {
+        StringOrVec::One(value) => vec![value],
+        StringOrVec::Many(values) => values,
+    })
+}
+
+/// Describe the type of a JSON value for error messages
+fn json_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+/// Check that configuration fields have the expected JSON types
+fn validate_shape(value: &serde_json::Value) -> Result<()> {
+    let Some(object) = value.as_object() else {
+        bail!(
+            "expected a JSON object with a \"scaffer\" field, found {}",
+            json_type_name(value)
+        );
+    };
+
+    let is_string_array = |v: &serde_json::Value| {
+        v.as_array()
+            .is_some_and(|items| items.iter().all(|i| i.is_string()))
+    };
+
+    if let Some(field) = object.get("scaffer") {
+        if !field.is_string() && !is_string_array(field) {
+            bail!(
+                "field \"scaffer\" must be an array of directory paths (or a single path string), found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("scaffer_template_urls") {
+        let valid = field.is_null()
+            || field
+                .as_object()
+                .is_some_and(|urls| urls.values().all(|url| url.is_string()));
+        if !valid {
+            bail!(
+                "field \"scaffer_template_urls\" must be an object mapping template names to URL strings, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("template_precedence") {
+        if !matches!(field.as_str(), Some("local") | Some("global")) {
+            bail!(
+                "field \"template_precedence\" must be \"local\" or \"global\", found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("internal_case") {
+        if !matches!(field.as_str(), Some("kebab") | Some("snake")) {
+            bail!(
+                "field \"internal_case\" must be \"kebab\" or \"snake\", found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("validators") {
+        let valid = field
+            .as_object()
+            .is_some_and(|cmds| cmds.values().all(|cmd| cmd.is_string()));
+        if !valid {
+            bail!(
+                "field \"validators\" must be an object mapping file extensions to commands, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("global_var_defaults") {
+        let valid = field
+            .as_object()
+            .is_some_and(|values| values.values().all(|value| value.is_string()));
+        if !valid {
+            bail!(
+                "field \"global_var_defaults\" must be an object mapping variable names to values, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("auto_overwrite") {
+        if !is_string_array(field) {
+            bail!(
+                "field \"auto_overwrite\" must be an array of glob patterns, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("ignore_vars") {
+        if !is_string_array(field) {
+            bail!(
+                "field \"ignore_vars\" must be an array of glob patterns, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("substitution_denylist") {
+        if !is_string_array(field) {
+            bail!(
+                "field \"substitution_denylist\" must be an array of tokens, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("registries") {
+        if !is_string_array(field) {
+            bail!(
+                "field \"registries\" must be an array of URL strings, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("prefix") {
+        if !field.is_string() {
+            bail!(
+                "field \"prefix\" must be a string, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("cases") {
+        if !is_string_array(field) {
+            bail!(
+                "field \"cases\" must be an array of case style names, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("discover") {
+        if !field.is_boolean() {
+            bail!(
+                "field \"discover\" must be true or false, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("wildcard_urls") {
+        if !field.is_boolean() {
+            bail!(
+                "field \"wildcard_urls\" must be true or false, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("editor") {
+        if !field.is_string() {
+            bail!(
+                "field \"editor\" must be a command string, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("proxy") {
+        if !field.is_string() {
+            bail!(
+                "field \"proxy\" must be a proxy URL string, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("temp_dir") {
+        if !field.is_string() {
+            bail!(
+                "field \"temp_dir\" must be a directory path string, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    if let Some(field) = object.get("partials_dir") {
+        if !field.is_string() {
+            bail!(
+                "field \"partials_dir\" must be a directory path string, found {}",
+                json_type_name(field)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_string_is_coerced_to_array() {
+        let config = ScafferConfig::from_json_str(r#"{"scaffer": "templates"}"#, "test").unwrap();
+        assert_eq!(config.scaffer, vec!["templates"]);
+    }
+
+    #[test]
+    fn test_wrong_shape_names_field() {
+        let err = ScafferConfig::from_json_str(r#"{"scaffer": 42}"#, "scaffer.json").unwrap_err();
+        let message = format!("{err:#}");
+        assert!(message.contains("scaffer.json"));
+        assert!(message.contains("\"scaffer\" must be an array"));
+        assert!(message.contains("found a number"));
+
+        let err = ScafferConfig::from_json_str(
+            r#"{"scaffer": [], "scaffer_template_urls": ["https://example.com/a.zip"]}"#,
+            "scaffer.json",
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("\"scaffer_template_urls\" must be an object"));
+
+        let err = ScafferConfig::from_json_str(r#"{"internal_case": "camel"}"#, "scaffer.json")
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("\"internal_case\" must be \"kebab\" or \"snake\""));
+    }
+
+    #[test]
+    fn test_internal_case() {
+        let config =
+            ScafferConfig::from_json_str(r#"{"internal_case": "snake"}"#, "scaffer.json").unwrap();
+        let internal_case = config.get_internal_case();
+        assert_eq!(internal_case, InternalCase::Snake);
+        assert_eq!(internal_case.name("api-base-url"), "api_base_url");
+        assert_eq!(InternalCase::Kebab.name("api-base-url"), "api-base-url");
+    }
+
+    #[test]
+    fn test_wildcard_url() {
+        let urls = HashMap::from([
+            ("*".to_string(), "https://x/{name}.zip".to_string()),
+            ("acme-*".to_string(), "https://acme/{name}.zip".to_string()),
+            ("fixed".to_string(), "https://x/fixed.zip".to_string()),
+        ]);
+        assert_eq!(
+            wildcard_url(&urls, "widget").unwrap(),
+            "https://x/{name}.zip"
+        );
+        assert_eq!(
+            wildcard_url(&urls, "acme-api").unwrap(),
+            "https://acme/{name}.zip"
+        );
+        assert!(wildcard_url(&HashMap::new(), "widget").is_none());
+    }
+
+    #[test]
+    fn test_rename_template_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scaffer.json");
+        fs::write(
+            &path,
+            r#"{"scaffer": "templates", "scaffer_template_urls": {"old": "https://x/a.zip", "taken": "https://x/b.zip"}}"#,
+        )
+        .unwrap();
+
+        assert!(rename_template_url(&path, "old", "taken").is_err());
+        assert!(!rename_template_url(&path, "missing", "new").unwrap());
+        assert!(rename_template_url(&path, "old", "new").unwrap());
+
+        let config =
+            ScafferConfig::from_json_str(&fs::read_to_string(&path).unwrap(), "test").unwrap();
+        let urls = config.scaffer_template_urls.unwrap();
+        assert_eq!(urls["new"], "https://x/a.zip");
+        assert!(!urls.contains_key("old"));
+        assert_eq!(config.scaffer, vec!["templates"]);
+    }
+
+    #[test]
+    fn test_template_precedence() {
+        let local_root = tempfile::tempdir().unwrap();
+        let global_root = tempfile::tempdir().unwrap();
+        fs::create_dir(local_root.path().join("shared")).unwrap();
+        fs::create_dir(global_root.path().join("shared")).unwrap();
+
+        let mut local = ScafferConfig::new();
+        local.add_template_path(local_root.path().to_string_lossy().to_string());
+        let mut global = ScafferConfig::new();
+        global.add_template_path(global_root.path().to_string_lossy().to_string());
+
+        let resolve = |local: &ScafferConfig| {
+            local
+                .clone()
+                .with_global(global.clone())
+                .get_template_directories()
+                .into_iter()
+                .map(|dir| dir.join("shared"))
+                .find(|path| path.exists())
+                .unwrap()
+        };
+
+        assert_eq!(resolve(&local), local_root.path().join("shared"));
+
+        local.template_precedence = Some(TemplatePrecedence::Global);
+        assert_eq!(resolve(&local), global_root.path().join("shared"));
+
+        // Both directories provide the template; a URL entry isn't a directory candidate
+        local.scaffer_template_urls = Some(HashMap::from([(
+            "shared".to_string(),
+            "https://x/shared.zip".to_string(),
+        )]));
+        let candidates = local
+            .with_global(global.clone())
+            .directory_candidates("shared")
+            .unwrap();
+        let sources: Vec<String> = candidates.iter().map(|c| c.source.to_string()).collect();
+        assert_eq!(
+            sources,
+            [global_root.path(), local_root.path()]
+                .map(|root| root.join("shared").display().to_string())
+        );
+    }
+
+    #[test]
+    fn test_templates_named_by_manifest() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        for (root, dir, name) in [
+            (&first, "2024-03-react-comp", Some("react-component")),
+            (&first, "plain", None),
+            (&second, "react-component", None),
+            (&second, "older-react", Some("react-component")),
+        ] {
+            let path = root.path().join(dir);
+            fs::create_dir(&path).unwrap();
+            if let Some(name) = name {
+                fs::write(
+                    path.join("scaffer.toml"),
+                    format!("name = \"{name}\"\ndescription = \"\"\"\n  A {name}\nMore\"\"\""),
+                )
+                .unwrap();
+            }
+        }
+
+        let templates =
+            directory_templates(&[first.path().to_path_buf(), second.path().to_path_buf()])
+                .unwrap();
+        let listed: Vec<(&str, PathBuf)> = templates
+            .iter()
+            .map(|info| match &info.source {
+                TemplateSource::Directory(path) => (info.name.as_str(), path.clone()),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(
+            listed,
+            vec![
+                ("react-component", first.path().join("2024-03-react-comp")),
+                ("plain", first.path().join("plain")),
+                ("react-component", second.path().join("older-react")),
+                ("react-component", second.path().join("react-component")),
+            ]
+        );
+        // The directory name no longer names a template that declares its own name
+        assert!(!listed.iter().any(|(name, _)| *name == "2024-03-react-comp"));
+
+        // Listed with the first line of their description
+        assert_eq!(
+            templates[0].description.as_deref(),
+            Some("A react-component")
+        );
+        assert_eq!(templates[1].description, None);
+    }
+
+    #[test]
+    fn test_global_config_is_merged() {
+        let global = ScafferConfig::from_json_str(
+            r#"{"prefix": "tpl", "auto_overwrite": ["*.lock"],
+                "global_var_defaults": {"author": "Ada", "license": "MIT"}}"#,
+            "global",
+        )
+        .unwrap();
+        let local = ScafferConfig::from_json_str(
+            r#"{"auto_overwrite": ["*.gen.ts"], "global_var_defaults": {"Author": "Bob"}}"#,
+            "local",
+        )
+        .unwrap();
+
+        // Without a global configuration, only the local one counts
+        assert_eq!(local.get_prefix(), None);
+        assert_eq!(local.get_auto_overwrite(), ["*.gen.ts"]);
+
+        let config = local.with_global(global);
+        assert_eq!(config.get_prefix().as_deref(), Some("tpl"));
+        assert_eq!(config.get_auto_overwrite(), ["*.gen.ts", "*.lock"]);
+        // Local values win whatever case their names are given in
+        let defaults = config.get_global_var_defaults();
+        assert_eq!(defaults["author"], "Bob");
+        assert_eq!(defaults["license"], "MIT");
+        assert_eq!(defaults.len(), 2);
+    }
+}
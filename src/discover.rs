@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+use crate::manifest::MANIFEST_FILE;
+use crate::utils;
+
+/// How deep below the repository root template directories are searched for
+const MAX_DEPTH: usize = 8;
+
+/// Directories never searched for templates
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "dist", "build"];
+
+/// Discovered template directories, valid while the repository state is unchanged
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedDiscovery {
+    key: String,
+    templates: Vec<PathBuf>,
+}
+
+/// Find the template directories (those with a `scaffer.toml`) below `root`
+///
+/// The result is cached while the repository's git HEAD and index are unchanged.
+/// Outside a git repository the search is done every time.
+pub fn discover_templates(root: &Path) -> Vec<PathBuf> {
+    let key = repo_state_key(root);
+    if let Some(key) = &key {
+        if let Some(templates) = load(root, key) {
+            return templates;
+        }
+    }
+
+    let templates = search(root);
+    if let Some(key) = &key {
+        store(root, key, &templates);
+    }
+    templates
+}
+
+/// Walk the tree for template directories, not descending into templates themselves,
+/// hidden directories or build output
+fn search(root: &Path) -> Vec<PathBuf> {
+    let mut templates = Vec::new();
+    let mut walker = WalkDir::new(root)
+        .max_depth(MAX_DEPTH)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            let name = e.file_name().to_string_lossy();
+            e.depth() == 0
+                || (e.file_type().is_dir()
+                    && !name.starts_with('.')
+                    && !SKIPPED_DIRS.contains(&name.as_ref()))
+        });
+
+    while let Some(entry) = walker.next() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if entry.depth() > 0 && entry.path().join(MANIFEST_FILE).is_file() {
+            templates.push(entry.into_path());
+            walker.skip_current_dir();
+        }
+    }
+
+    templates
+}
+
+/// Fingerprint of the repository state: the version, git HEAD and index modification
+/// time. `None` if `root` is not the root of a git repository.
+fn repo_state_key(root: &Path) -> Option<String> {
+    let git_dir = root.join(".git");
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let index_modified = fs::metadata(git_dir.join("index"))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |age| age.as_nanos());
+
+    Some(utils::stable_key([
+        env!("CARGO_PKG_VERSION").as_bytes(),
+        head.as_bytes(),
+        &index_modified.to_le_bytes(),
+    ]))
+}
+
+fn load(root: &Path, key: &str) -> Option<Vec<PathBuf>> {
+    let content = fs::read_to_string(cache_path(root)?).ok()?;
+    let cached: CachedDiscovery = serde_json::from_str(&content).ok()?;
+    (cached.key == key).then_some(cached.templates)
+}
+
+/// Remember the discovered templates (best-effort)
+fn store(root: &Path, key: &str, templates: &[PathBuf]) {
+    let Some(path) = cache_path(root) else {
+        return;
+    };
+
+    let cached = CachedDiscovery {
+        key: key.to_string(),
+        templates: templates.to_vec(),
+    };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(content) = serde_json::to_string(&cached) {
+        let _ = fs::write(path, content);
+    }
+}
+
+/// Location of the cached discovery for a repository root
+fn cache_path(root: &Path) -> Option<PathBuf> {
+    let root = fs::canonicalize(root).ok()?;
+    let file_name = format!(
+        "{}.json",
+        utils::stable_key([root.as_os_str().as_encoded_bytes()])
+    );
+
+    dirs::cache_dir().map(|dir| dir.join("scaffer").join("discover").join(file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_finds_manifest_directories() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in [
+            "packages/api/templates/endpoint",
+            "packages/api/templates/endpoint/nested",
+            "packages/web/component",
+            "node_modules/lib/template",
+            ".hidden/template",
+        ] {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+            fs::write(root.path().join(dir).join(MANIFEST_FILE), "").unwrap();
+        }
+        fs::create_dir_all(root.path().join("packages/plain")).unwrap();
+
+        assert_eq!(
+            search(root.path()),
+            vec![
+                root.path().join("packages/api/templates/endpoint"),
+                root.path().join("packages/web/component"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+/// Keywords that can't be used as plain Rust identifiers
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+    "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// Rust keywords that can't be written as raw identifiers either
+const RUST_NON_RAW_KEYWORDS: &[&str] = &["crate", "self", "Self", "super"];
+
+/// Reserved words that can't be used as TypeScript identifiers
+const TS_KEYWORDS: &[&str] = &[
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "new",
+    "null",
+    "return",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "implements",
+    "interface",
+    "let",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "static",
+    "yield",
+];
+
+/// Apply the filter `name` (with its optional `:arg`) to an already case-converted
+/// value, or `None` if there is no such filter
+///
+/// - `ident` - a valid identifier: characters other than letters, digits and `_`
+///   become `_`, and a leading digit gets a `_` prefix
+/// - `ident:rust` - as `ident`, with keywords made raw (`r#type`) or, where that is
+///   not possible, suffixed (`self_`)
+/// - `ident:ts` - as `ident`, also allowing `$`, with reserved words suffixed (`class_`)
+pub fn apply(name: &str, arg: Option<&str>, value: &str) -> Option<String> {
+    match (name, arg) {
+        ("ident", None) => Some(ident(value, |c| c.is_ascii_alphanumeric() || c == '_')),
+        ("ident", Some("rust")) => {
+            let ident = ident(value, |c| c.is_alphanumeric() || c == '_');
+            Some(if RUST_NON_RAW_KEYWORDS.contains(&ident.as_str()) {
+                format!("{ident}_")
+            } else if RUST_KEYWORDS.contains(&ident.as_str()) {
+                format!("r#{ident}")
+            } else {
+                ident
+            })
+        }
+        ("ident", Some("ts")) => {
+            let ident = ident(value, |c| c.is_alphanumeric() || c == '_' || c == '$');
+            Some(if TS_KEYWORDS.contains(&ident.as_str()) {
+                format!("{ident}_")
+            } else {
+                ident
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Turn a value into an identifier made of the characters accepted by `valid`
+///
+/// Runs of other characters become a single `_`, leading and trailing ones are
+/// dropped, and an identifier that would start with a digit (or be empty) gets a
+/// `_` prefix.
+fn ident(value: &str, valid: impl Fn(char) -> bool) -> String {
+    let mut ident = String::with_capacity(value.len());
+    let mut pending_separator = false;
+    for c in value.chars() {
+        if valid(c) {
+            if pending_separator && !ident.is_empty() {
+                ident.push('_');
+            }
+            pending_separator = false;
+            ident.push(c);
+        } else {
+            pending_separator = true;
+        }
+    }
+
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    ident
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ident() {
+        let value = "123 my-thing!";
+        assert_eq!(apply("ident", None, value).unwrap(), "_123_my_thing");
+        assert_eq!(
+            apply("ident", Some("rust"), value).unwrap(),
+            "_123_my_thing"
+        );
+        assert_eq!(apply("ident", Some("ts"), "$el-ref").unwrap(), "$el_ref");
+        assert_eq!(apply("ident", None, "$el-ref").unwrap(), "el_ref");
+        assert_eq!(apply("ident", None, "!!").unwrap(), "_");
+        assert_eq!(apply("ident", None, "Käse").unwrap(), "K_se");
+        assert_eq!(apply("ident", Some("rust"), "Käse").unwrap(), "Käse");
+
+        assert_eq!(apply("ident", Some("rust"), "type").unwrap(), "r#type");
+        assert_eq!(apply("ident", Some("rust"), "self").unwrap(), "self_");
+        assert_eq!(apply("ident", Some("ts"), "class").unwrap(), "class_");
+
+        assert!(apply("ident", Some("cobol"), value).is_none());
+        assert!(apply("upper", None, value).is_none());
+    }
+}
@@ -1,46 +1,248 @@
-use crate::config::ScafferConfig;
-use crate::template::TemplateProcessor;
+use crate::config::{self, ScafferConfig};
+use crate::gitignore;
+use crate::header;
+use crate::history::{self, History};
+use crate::http;
+use crate::interrupt;
+use crate::lockfile::{LOCK_FILE, LockedTemplate, Lockfile};
+use crate::manifest::{self, TemplateManifest};
+use crate::merge::{self, ArrayStrategy};
+use crate::oci;
+use crate::patch;
+use crate::progress::Progress;
+use crate::pseudo::{self, PseudoVariables};
+use crate::registry;
+use crate::runlog;
+use crate::scancache;
+use crate::stats;
+use crate::template::{
+    self, CaseStyle, FilenameCase, OutputEncoding, SubstitutionScope, TemplateProcessor,
+};
 use crate::utils;
+use crate::validate::{ValidationOutcome, Validator};
 
 use anyhow::{Context, Result, bail};
-use dialoguer::{Confirm, Input, Select};
-use std::collections::{HashMap, HashSet};
+use convert_case::{Case, Casing};
+use dialoguer::{Input, Password, Select};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 use walkdir::WalkDir;
 
+/// How much output a generation run prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// Per-file lines and the full summary
+    #[default]
+    Normal,
+    /// Only a single `scaffer: N created, M skipped` line
+    SummaryOnly,
+    /// Nothing on success
+    Quiet,
+}
+
+/// Options controlling a single generation run
+#[derive(Debug, Clone, Default)]
+pub struct GenerateOptions {
+    pub force: bool,
+    pub dry_run: bool,
+    pub verbosity: Verbosity,
+    /// Overrides the manifest's `filename_case` when set
+    pub filename_case: Option<FilenameCase>,
+    /// Run configured syntax checks on processed content (dry runs only)
+    pub validate: bool,
+    /// Allow running external commands such as validators
+    pub allow_scripts: bool,
+    /// Directory to generate into (defaults to the current directory)
+    pub output_dir: Option<PathBuf>,
+    /// Fail instead of prompting for missing variables or overwrites
+    pub no_input: bool,
+    /// Seed for random pseudo-variables, for reproducible output
+    pub seed: Option<u64>,
+    /// Neither use nor record the recently-used template history
+    pub no_history: bool,
+    /// Append a JSON-lines record of the run to this file
+    pub log_file: Option<PathBuf>,
+    /// Write a JSON summary of the run's counts to this file
+    pub stats: Option<PathBuf>,
+    /// Leave variable values out of the log file
+    pub log_redact: bool,
+    /// Scan local templates for variables even when a cached scan is current
+    pub no_scan_cache: bool,
+    /// Skip every file that already exists instead of overwriting or prompting
+    pub into_existing: bool,
+    /// Stage all output and move it into place only after the whole run succeeds
+    pub atomic: bool,
+    /// Write a patch of all changes to this file instead of writing the files
+    pub patch: Option<PathBuf>,
+    /// Review and edit all variables in one form instead of prompting one by one
+    pub interactive_vars: bool,
+    /// Prompt also for the variables that already have a value, offering it
+    pub prompt_all: bool,
+    /// Don't go on to the templates named by manifests' `after` field
+    pub no_chain: bool,
+    /// Print how the template name was resolved before generating
+    pub explain: bool,
+    /// Add a `.gitignore` for the detected stacks when the manifest names none
+    pub with_gitignore: bool,
+    /// Fetch the registry indexes again, updating their cache, before resolving
+    pub refresh: bool,
+    /// Fail instead of warning about destinations that differ only in letter case
+    pub strict: bool,
+    /// Fail when the run creates no files (except on a dry run)
+    pub require_output: bool,
+    /// Substitute variables in file and directory names only
+    pub names_only: bool,
+    /// Substitute variables in file contents only
+    pub content_only: bool,
+    /// Print the processed files to stdout instead of writing them
+    pub stdout: bool,
+    /// Print the resolved variables of a dry run as JSON instead of a table
+    pub json: bool,
+    /// Neither suggest nor remember values entered for variables
+    pub no_value_history: bool,
+    /// Write the generated files into this zip archive instead of the output directory
+    pub to_zip: Option<PathBuf>,
+    /// Without a terminal, leave variables without a value and put this in place of
+    /// their tokens (`{name}` becomes the variable name) instead of failing
+    pub missing_placeholder: Option<String>,
+    /// Directory for downloaded and extracted templates, overriding `SCAFFER_TMPDIR`
+    /// and the configuration
+    pub temp_dir: Option<PathBuf>,
+    /// Report a downloaded archive in one line (URL, size, SHA-256) instead of
+    /// showing download progress
+    pub quiet_download: bool,
+    /// Record the resolved template in the output directory's `scaffer.lock`
+    pub lock: bool,
+    /// Fail unless the template matches its entry in `scaffer.lock`
+    pub locked: bool,
+    /// Drop this many leading components from every generated file's path
+    pub strip_components: usize,
+    /// Overrides the manifest's `output_encoding` when set
+    pub output_encoding: Option<OutputEncoding>,
+}
+
+impl GenerateOptions {
+    /// The directory to generate into, with `~` and environment variables expanded
+    pub fn output_dir(&self) -> Result<PathBuf> {
+        match &self.output_dir {
+            Some(dir) => utils::expand_path(dir),
+            None => std::env::current_dir().context("Failed to get current directory"),
+        }
+    }
+
+    /// Which parts of the template variables are substituted in
+    fn scope(&self) -> SubstitutionScope {
+        if self.names_only {
+            SubstitutionScope::NamesOnly
+        } else if self.content_only {
+            SubstitutionScope::ContentOnly
+        } else {
+            SubstitutionScope::All
+        }
+    }
+
+    /// Whether per-file progress lines should be printed
+    fn verbose(&self) -> bool {
+        self.verbosity == Verbosity::Normal
+    }
+}
+
+/// What happened to a single template file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOutcome {
+    Created,
+    Overwritten,
+    /// Overwritten without prompting because it matched `auto_overwrite`
+    AutoOverwritten,
+    Skipped,
+    /// Left alone because it already has the generated content
+    Unchanged,
+}
+
+/// A template file and where it was (or would be) written
+#[derive(Debug, Clone, Serialize)]
+pub struct FileAction {
+    pub source: PathBuf,
+    /// Destination relative to the output directory
+    pub destination: String,
+    pub outcome: FileOutcome,
+}
+
+/// Shown instead of the values of secret variables
+const SECRET_MASK: &str = "********";
+
+/// What a generation run did
+#[derive(Debug, Clone, Default)]
+pub struct GenerationReport {
+    pub template: Option<String>,
+    /// Resolved template directory or download URL
+    pub template_source: Option<String>,
+    pub variables: HashMap<String, String>,
+    pub actions: Vec<FileAction>,
+    /// Total size of the files written (nothing on a dry run)
+    pub bytes_written: u64,
+    /// Variables found in the template that were left without a value
+    pub missing_vars: Vec<String>,
+    /// Content hashes of the files written or found unchanged, by destination
+    pub hashes: BTreeMap<String, String>,
+    /// Templates generated one after the other through their manifests' `after`
+    /// field, starting with the requested one (empty without a chain)
+    pub chain: Vec<String>,
+    /// Template the manifest asks to generate next
+    pub after: Option<String>,
+    /// Variables holding secrets, whose values are never shown or stored
+    pub secrets: HashSet<String>,
+    /// The variables given or asked for, which a chained template is generated with;
+    /// unlike `variables`, without defaults and pseudo-variables
+    pub chain_variables: HashMap<String, String>,
+}
+
+impl GenerationReport {
+    fn record(&mut self, source: &Path, destination: &str, outcome: FileOutcome) {
+        self.actions.push(FileAction {
+            source: source.to_path_buf(),
+            destination: destination.to_string(),
+            outcome,
+        });
+    }
+
+    /// Number of files with the given outcome
+    pub fn count(&self, outcome: FileOutcome) -> usize {
+        self.actions.iter().filter(|a| a.outcome == outcome).count()
+    }
+
+    /// Number of files written, whether new or overwritten
+    pub fn written(&self) -> usize {
+        self.count(FileOutcome::Created)
+            + self.count(FileOutcome::Overwritten)
+            + self.count(FileOutcome::AutoOverwritten)
+    }
+}
+
 pub struct TemplateGenerator {
     config: ScafferConfig,
 }
 
 impl TemplateGenerator {
-    pub fn new() -> Self {
-        let config = ScafferConfig::load().unwrap_or_default();
+    pub fn new() -> Result<Self> {
+        let config = ScafferConfig::load()?;
 
-        Self { config }
+        Ok(Self { config })
     }
 
     pub fn generate(
         &self,
         template: Option<String>,
         variables: Vec<String>,
-        force: bool,
-        dry_run: bool,
-    ) -> Result<()> {
-        let template_name = match template {
-            Some(name) => name,
-            None => self.prompt_for_template()?,
-        };
-
-        // Check if it's a URL
-        let template_path =
-            if template_name.starts_with("http://") || template_name.starts_with("https://") {
-                self.download_template(&template_name)?
-            } else {
-                self.find_template(&template_name)?
-            };
-
+        options: &GenerateOptions,
+    ) -> Result<GenerationReport> {
         // Parse command-line variables
         let mut var_map = HashMap::new();
         for var_str in variables {
@@ -49,245 +251,4797 @@ impl TemplateGenerator {
             }
         }
 
-        // Scan template for variables
-        let required_vars = self.scan_template_variables(&template_path)?;
+        self.generate_with_variables(template, var_map, options)
+    }
+
+    /// Generate from a template using already-parsed variable values
+    pub fn generate_with_variables(
+        &self,
+        template: Option<String>,
+        var_map: HashMap<String, String>,
+        options: &GenerateOptions,
+    ) -> Result<GenerationReport> {
+        let mut report = GenerationReport::default();
+        let mut result = self.run_generation(template, var_map, options, &mut report);
+        if result.is_ok() && !options.no_chain {
+            result = self.generate_chain(options, &mut report);
+        }
+        if result.is_ok()
+            && options.require_output
+            && !options.dry_run
+            && report.count(FileOutcome::Created) == 0
+        {
+            result = Err(anyhow::anyhow!(
+                "No files were created (--require-output); check the template and the files already in the output directory"
+            ));
+        }
 
-        // Prompt for missing variables
-        for var_name in &required_vars {
-            if !var_map.contains_key(var_name) {
-                let value: String = Input::new()
-                    .with_prompt(format!("Enter value for '{var_name}'"))
-                    .interact_text()?;
-                var_map.insert(var_name.clone(), value);
+        // The log records failed runs too, up to the point of failure
+        if let Some(log_file) = &options.log_file {
+            let logged = runlog::append(log_file, &report, options, result.as_ref().err());
+            if result.is_ok() {
+                logged?;
+            }
+        }
+        if let Some(stats_file) = &options.stats {
+            let written = stats::write(stats_file, &report, options, result.as_ref().err());
+            if result.is_ok() {
+                written?;
             }
         }
 
-        // Process the template
-        self.process_template(&template_path, var_map, force, dry_run)?;
+        result.map(|()| report)
+    }
+
+    /// Generate the templates that manifests ask to generate next, one after the other,
+    /// with the variables of the run so far, adding what they do to the report
+    fn generate_chain(
+        &self,
+        options: &GenerateOptions,
+        report: &mut GenerationReport,
+    ) -> Result<()> {
+        let Some(mut next) = report.after.take() else {
+            return Ok(());
+        };
+        // These outputs hold a single template's files
+        if options.to_zip.is_some() || options.patch.is_some() || options.stdout {
+            eprintln!(
+                "Warning: not generating the chained template {next} into a zip, patch or stdout"
+            );
+            return Ok(());
+        }
+        let first = report.template.clone().unwrap_or_default();
+        let mut generated = vec![chain_key(
+            report.template_source.as_deref().unwrap_or_default(),
+        )];
+        report.chain = vec![first];
+
+        loop {
+            let key = match self.resolve_template_url(&next)? {
+                Some(url) => url,
+                None => chain_key(&self.find_template(&next)?.display().to_string()),
+            };
+            if generated.contains(&key) {
+                bail!(
+                    "Template chain {} -> {next} has a cycle",
+                    report.chain.join(" -> ")
+                );
+            }
+            generated.push(key);
+            report.chain.push(next.clone());
+
+            if options.verbosity != Verbosity::Quiet {
+                println!("\nGenerating chained template {next}");
+            }
+            // Files written so far, so that the same ones aren't asked about again
+            let mut chained = GenerationReport {
+                hashes: report.hashes.clone(),
+                ..Default::default()
+            };
+            self.run_generation(
+                Some(next),
+                report.chain_variables.clone(),
+                options,
+                &mut chained,
+            )
+            .with_context(|| format!("Chained template failed: {}", report.chain.join(" -> ")))?;
+
+            report.actions.append(&mut chained.actions);
+            report.bytes_written += chained.bytes_written;
+            report.hashes.append(&mut chained.hashes);
+            for var in chained.missing_vars {
+                if !report.missing_vars.contains(&var) {
+                    report.missing_vars.push(var);
+                }
+            }
+            for (name, value) in chained.variables {
+                report.variables.entry(name).or_insert(value);
+            }
+            for (name, value) in chained.chain_variables {
+                report.chain_variables.entry(name).or_insert(value);
+            }
+            report.secrets.extend(chained.secrets);
+
+            match chained.after {
+                Some(after) => next = after,
+                None => break,
+            }
+        }
 
+        if options.verbosity != Verbosity::Quiet {
+            println!("\nGenerated template chain: {}", report.chain.join(" -> "));
+        }
         Ok(())
     }
 
-    fn prompt_for_template(&self) -> Result<String> {
-        let templates = self.config.find_templates()?;
+    fn run_generation(
+        &self,
+        template: Option<String>,
+        var_map: HashMap<String, String>,
+        options: &GenerateOptions,
+        report: &mut GenerationReport,
+    ) -> Result<()> {
+        // Variables are keyed in kebab-case, whatever case they were given in
+        let mut var_map: HashMap<String, String> = var_map
+            .into_iter()
+            .map(|(name, value)| (name.to_case(Case::Kebab), value))
+            .collect();
+        let given_vars: HashSet<String> = var_map.keys().cloned().collect();
 
-        if templates.is_empty() {
-            bail!("No templates found. Run 'scaffer setup' to configure template directories.");
+        // Fetched and cached before anything reads them, so the whole run uses them
+        if options.refresh {
+            let registries = self.config.get_registries();
+            let results = registry::update_all(&registries, registries.len().max(1));
+            for (url, result) in registries.iter().zip(results) {
+                if let Err(err) = result {
+                    eprintln!("Warning: could not refresh registry {url}: {err:#}");
+                }
+            }
         }
 
-        let selection = Select::new()
-            .with_prompt("Select a template")
-            .items(&templates)
-            .interact()?;
-
-        Ok(templates[selection].clone())
-    }
+        let template_name = match template {
+            Some(name) => name,
+            None if options.no_input => bail!("No template given"),
+            None => self.prompt_for_template(options)?,
+        };
 
-    fn download_template(&self, url: &str) -> Result<PathBuf> {
-        println!("Downloading template from {url}...");
+        report.template = Some(template_name.clone());
+        if options.explain {
+            for step in self.resolution_steps(&template_name)? {
+                eprintln!("explain: {step}");
+            }
+        }
+        if options.verbosity != Verbosity::Quiet
+            && !template_name.contains("://")
+            && !Path::new(&template_name).exists()
+        {
+            self.warn_if_shadowing(&template_name)?;
+        }
 
-        let response = minreq::get(url)
-            .send()
-            .with_context(|| format!("Failed to download template from {url}"))?;
+        // Check if it's a URL, either given directly or configured by name. A downloaded
+        // or single-file template is kept in a temporary directory until generation is done.
+        let mut _template_dir = None;
+        let mut archive_sha256 = None;
+        let (template_path, is_local) = match self.resolve_template_url(&template_name)? {
+            Some(url) => {
+                let url = self.expand_url_variables(&url, &var_map, options)?;
+                // A wildcard entry fetches from wherever the name leads, so say where
+                if options.verbosity != Verbosity::Quiet
+                    && !template_name.contains("://")
+                    && !self.config.get_template_urls().contains_key(&template_name)
+                {
+                    eprintln!("Fetching '{template_name}' from wildcard template URL {url}");
+                }
+                report.template_source = Some(url.clone());
+                let temp_dir = self.temp_dir(options)?;
+                let (download_dir, template_path, sha256) =
+                    self.download_template(&url, temp_dir, options)?;
+                _template_dir = Some(download_dir);
+                archive_sha256 = Some(sha256);
+                (template_path, false)
+            }
+            None => {
+                let path = self.find_template(&template_name)?;
+                report.template_source = Some(path.display().to_string());
+                if path.is_file() {
+                    let (template_dir, template_path) =
+                        single_file_template(&path, self.temp_dir(options)?)?;
+                    _template_dir = Some(template_dir);
+                    (template_path, false)
+                } else {
+                    (path, true)
+                }
+            }
+        };
 
-        if response.status_code != 200 {
-            bail!("Failed to download template: HTTP {}", response.status_code);
+        if options.locked {
+            let output_dir = options.output_dir()?;
+            let Some(lockfile) = Lockfile::load(&output_dir)? else {
+                bail!("No {} in {} (--locked)", LOCK_FILE, output_dir.display());
+            };
+            lockfile.verify(&template_name, archive_sha256.as_deref())?;
         }
 
-        let bytes = response.into_bytes();
+        let manifest = TemplateManifest::load(&template_path)?;
 
-        // Create temporary directory
-        let temp_dir = TempDir::new().context("Failed to create temporary directory")?;
+        // A chained template may be given relative to a local template
+        report.after = manifest.after.as_ref().map(|after| {
+            let sibling = template_path.join(after);
+            if is_local && !after.contains("://") && sibling.is_dir() {
+                sibling.display().to_string()
+            } else {
+                after.clone()
+            }
+        });
 
-        let zip_path = temp_dir.path().join("template.zip");
-        fs::write(&zip_path, bytes).context("Failed to write template zip file")?;
+        // Scan template for variables; local templates are only rescanned when they change.
+        // The cache holds the variables of both names and contents.
+        let cacheable = options.scope() == SubstitutionScope::All;
+        let required_vars = if is_local && cacheable && !options.no_scan_cache {
+            // Shared partials can be included, so their changes count too
+            let processor = self.processor(&manifest)?;
+            let shared_partials = processor
+                .shared_partials()
+                .iter()
+                .flat_map(|dir| WalkDir::new(dir).into_iter().filter_map(|e| e.ok()));
+            let fingerprint = scancache::fingerprint(
+                &template_path,
+                template_entries(&template_path, &manifest, &[]).chain(shared_partials),
+                &processor.token_forms("name"),
+            );
+            match scancache::load(&template_path, &fingerprint) {
+                Some(variables) => variables,
+                None => {
+                    let variables =
+                        self.scan_template_variables(&template_path, &manifest, options)?;
+                    scancache::store(&template_path, &fingerprint, &variables);
+                    variables
+                }
+            }
+        } else {
+            self.scan_template_variables(&template_path, &manifest, options)?
+        };
 
-        // Extract zip file
-        let extract_dir = temp_dir.path().join("extracted");
-        fs::create_dir_all(&extract_dir).context("Failed to create extraction directory")?;
+        // The variables deciding conditional directories are needed even when no file
+        // uses them
+        let mut processor = self.processor(&manifest)?;
+        processor.set_scope(options.scope());
+        let mut required_vars = required_vars;
+        for condition in manifest.conditional_dirs.values() {
+            let (variable, _) = split_comparison(condition);
+            let variable = condition_variable(&processor, variable);
+            // Environment variables are read when the condition is evaluated
+            if pseudo::env_value(&variable).is_none() {
+                required_vars.insert(variable);
+            }
+        }
 
-        utils::extract_zip(&zip_path, &extract_dir).context("Failed to extract template zip")?;
+        // Likewise the lists that each-directories repeat over, while the item variables
+        // they bind are never asked for
+        let each_lists = each_dir_lists(&template_path, &manifest, &processor);
+        required_vars.extend(each_lists.iter().cloned());
+        required_vars.retain(|var| !is_item_variable(var, &each_lists));
 
-        // Find the actual template directory (might be nested)
-        let template_dir = utils::find_template_root(&extract_dir)?;
+        // Tokens that only look like variables are neither asked for nor substituted
+        let ignored_vars = self.ignored_vars(&manifest)?;
+        let ignored: Vec<&String> = required_vars
+            .iter()
+            .filter(|var| ignored_vars.is_match(var.as_str()))
+            .collect();
+        let internal_case = self.config.get_internal_case();
+        if options.verbose() && !ignored.is_empty() {
+            let mut ignored: Vec<String> =
+                ignored.iter().map(|var| internal_case.name(var)).collect();
+            ignored.sort();
+            println!("Ignoring variables: {}", ignored.join(", "));
+        }
+        required_vars.retain(|var| !ignored_vars.is_match(var));
+        var_map.retain(|var, _| !ignored_vars.is_match(var));
 
-        Ok(template_dir)
-    }
+        // The manifest can force variables to be asked for whatever the scan found, and
+        // keep others from being asked for
+        let optional_vars: HashSet<String> = manifest
+            .optional
+            .iter()
+            .map(|var| var.to_case(Case::Kebab))
+            .collect();
+        required_vars.extend(manifest.required.iter().map(|var| var.to_case(Case::Kebab)));
+        required_vars.retain(|var| !optional_vars.contains(var));
 
-    fn find_template(&self, template_name: &str) -> Result<PathBuf> {
-        // First check if it's a direct path
-        let direct_path = PathBuf::from(template_name);
-        if direct_path.exists() {
-            return Ok(direct_path);
+        let mut pseudo = PseudoVariables::new(options.seed);
+        pseudo.set_template(
+            &template_name,
+            report.template_source.as_deref().unwrap_or_default(),
+        );
+        let mut wanted_vars = required_vars.clone();
+        self.fill_variables(required_vars, &mut var_map, &mut pseudo, &manifest, options)?;
+
+        // Variables used only inside conditional directories are needed once those
+        // directories turn out to be generated
+        let skipped_dirs = inactive_dirs(&manifest, &processor, &var_map, pseudo.values());
+        let active_dirs: Vec<PathBuf> = conditional_dir_paths(&manifest)
+            .filter(|dir| !skipped_dirs.contains(dir))
+            .collect();
+        if !active_dirs.is_empty() {
+            let entries = template_entries(&template_path, &manifest, &skipped_dirs).filter(|e| {
+                e.path()
+                    .strip_prefix(&template_path)
+                    .is_ok_and(|rel| active_dirs.iter().any(|dir| rel.starts_with(dir)))
+            });
+            let mut extra_vars = extract_entry_variables(
+                &processor,
+                &template_path,
+                &FileRules::new(&manifest)?,
+                entries,
+            );
+            extra_vars.retain(|var| {
+                !is_item_variable(var, &each_lists)
+                    && !ignored_vars.is_match(var)
+                    && !optional_vars.contains(var)
+            });
+            wanted_vars.extend(extra_vars.iter().cloned());
+            self.fill_variables(extra_vars, &mut var_map, &mut pseudo, &manifest, options)?;
         }
+        self.check_variable_types(&mut var_map, &manifest, options)?;
 
-        // Check template URLs
-        let template_urls = self.config.get_template_urls()?;
-        if let Some(url) = template_urls.get(template_name) {
-            return Ok(PathBuf::from(url));
+        if options.verbosity != Verbosity::Quiet
+            && let Some(notice) = no_variables_notice(&wanted_vars, &manifest, &processor)
+        {
+            eprintln!("{notice}");
         }
 
-        // Search in template directories
-        for template_dir in self.config.get_template_directories()? {
-            let template_path = template_dir.join(template_name);
-            if template_path.exists() {
-                return Ok(template_path);
-            }
+        let mut missing_vars: Vec<String> = wanted_vars
+            .into_iter()
+            .filter(|var| !var_map.contains_key(var) && !pseudo.values().contains_key(var))
+            .map(|var| internal_case.name(&var))
+            .collect();
+        missing_vars.sort();
+        if options.missing_placeholder.is_some() && !missing_vars.is_empty() {
+            eprintln!(
+                "Warning: no value for {}; writing the placeholder instead",
+                missing_vars.join(", ")
+            );
         }
+        report.missing_vars = missing_vars;
 
-        bail!("Template '{}' not found", template_name);
+        // Process the template
+        report.variables = var_map
+            .iter()
+            .chain(pseudo.values())
+            .map(|(name, value)| (internal_case.name(name), value.clone()))
+            .collect();
+        report.secrets = var_map
+            .keys()
+            .filter(|name| manifest.is_secret(name))
+            .map(|name| internal_case.name(name))
+            .collect();
+        // A chained template has defaults of its own
+        let defaults = self.variable_defaults(&manifest);
+        report.chain_variables = var_map
+            .iter()
+            .filter(|(name, value)| {
+                given_vars.contains(*name) || defaults.get(*name) != Some(*value)
+            })
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        self.process_template(
+            &template_path,
+            var_map,
+            pseudo.values(),
+            &manifest,
+            options,
+            report,
+        )?;
+        self.update_lockfile(
+            &template_name,
+            report.template_source.as_deref().unwrap_or_default(),
+            archive_sha256.as_deref(),
+            options,
+        )?;
+
+        if !options.no_history && !options.dry_run {
+            let mut history = History::load();
+            history.record_template(&template_name);
+            // History is a convenience; failing to save it should not fail generation
+            let _ = history.save();
+        }
+
+        Ok(())
     }
 
-    fn scan_template_variables(&self, template_path: &Path) -> Result<HashSet<String>> {
-        let mut variables = HashSet::new();
-        let processor = TemplateProcessor::new();
+    /// Record the template in the output directory's lockfile, when asked to with
+    /// `--lock` or when the directory already has one (except on runs that write no
+    /// files there and under `--locked`, which leaves the lockfile as it is)
+    fn update_lockfile(
+        &self,
+        template_name: &str,
+        source: &str,
+        sha256: Option<&str>,
+        options: &GenerateOptions,
+    ) -> Result<()> {
+        if options.locked
+            || options.dry_run
+            || options.patch.is_some()
+            || options.stdout
+            || options.to_zip.is_some()
+        {
+            return Ok(());
+        }
+        let output_dir = options.output_dir()?;
+        let mut lockfile = match Lockfile::load(&output_dir)? {
+            Some(lockfile) => lockfile,
+            None if options.lock => Lockfile::default(),
+            None => return Ok(()),
+        };
+        let entry = LockedTemplate::new(source, sha256);
+        if lockfile.templates.get(template_name) != Some(&entry) {
+            lockfile.templates.insert(template_name.to_string(), entry);
+            lockfile.save(&output_dir)?;
+        }
+        Ok(())
+    }
 
-        // Check if there's a scaffer_init.py file for custom logic
-        let init_file = template_path.join("scaffer_init.py");
-        if init_file.exists() {
-            println!("Found scaffer_init.py - custom template initialization");
-            // TODO: Implement Python script execution for advanced templates
+    /// Draw the pseudo-variables among the required variables and prompt for the
+    /// remaining ones that have no value yet
+    ///
+    /// Pseudo-variables are kept out of `var_map`: their values are substituted as they
+    /// are, without being converted to the case style of each token.
+    fn fill_variables(
+        &self,
+        required_vars: HashSet<String>,
+        var_map: &mut HashMap<String, String>,
+        pseudo: &mut PseudoVariables,
+        manifest: &TemplateManifest,
+        options: &GenerateOptions,
+    ) -> Result<()> {
+        // Sorted so that seeded pseudo-variables are drawn in a stable order
+        let mut required_vars: Vec<String> = required_vars.into_iter().collect();
+        required_vars.sort();
+
+        let defaults = self.variable_defaults(manifest);
+
+        // Variables that are neither given, pseudo-variables nor defaulted are missing;
+        // the form of --interactive-vars also lists the given and defaulted ones
+        let mut missing_vars = Vec::new();
+        let mut form_vars = Vec::new();
+        for var_name in required_vars {
+            if var_map.contains_key(&var_name) {
+                form_vars.push(var_name);
+            } else if pseudo.value(&var_name).is_some() {
+                continue;
+            } else if let Some(value) = defaults.get(&var_name) {
+                var_map.insert(var_name.clone(), value.clone());
+                form_vars.push(var_name);
+            } else {
+                form_vars.push(var_name.clone());
+                missing_vars.push(var_name);
+            }
         }
 
-        // Scan all files in the template
-        for entry in WalkDir::new(template_path)
-            .into_iter()
-            .filter_map(|e| e.ok())
+        // Variables with a `required_if` condition are skipped, and not treated as
+        // missing, unless their controlling variable is true
+        let processor = self.processor(manifest)?;
+        let pseudo_values = pseudo.values();
+        let controller_of = |var_name: &str| controlling_variable(manifest, &processor, var_name);
+        let is_required = |var_name: &str, var_map: &HashMap<String, String>| {
+            controller_of(var_name).is_none_or(|controller| {
+                condition_value(&controller, var_map, pseudo_values)
+                    .is_some_and(|value| manifest::is_truthy(&value))
+            })
+        };
+
+        // With a placeholder for missing values, a run that can't prompt goes ahead
+        // without them
+        if options.missing_placeholder.is_some()
+            && (options.no_input || !std::io::stdin().is_terminal())
         {
-            let path = entry.path();
+            return Ok(());
+        }
 
-            // Extract variables from file path
-            if let Some(path_str) = path.to_str() {
-                let path_vars = processor.extract_variables(path_str);
-                variables.extend(path_vars);
+        if options.no_input {
+            if let Some(var_name) = missing_vars
+                .iter()
+                .find(|var_name| is_required(var_name, var_map))
+            {
+                bail!(
+                    "No value given for variable '{}'",
+                    self.config.get_internal_case().name(var_name)
+                );
             }
+        }
 
-            // Extract variables from file contents
-            if entry.file_type().is_file() {
-                if let Ok(content) = fs::read_to_string(path) {
-                    let content_vars = processor.extract_variables(&content);
-                    variables.extend(content_vars);
-                }
-            }
+        // Without a terminal for the form, fall back to prompting one by one
+        if options.interactive_vars && !form_vars.is_empty() && std::io::stdin().is_terminal() {
+            let form_vars: Vec<String> = manifest
+                .prompt_order(&form_vars)
+                .into_iter()
+                .flat_map(|(_, var_names)| var_names)
+                .collect();
+            return self.edit_variables(&form_vars, var_map, manifest, options, &is_required);
         }
 
-        Ok(variables)
+        // Given values are only asked for again when they can be
+        let prompt_all = options.prompt_all && !options.no_input && std::io::stdin().is_terminal();
+        ask_variables(
+            if prompt_all {
+                &form_vars
+            } else {
+                &missing_vars
+            },
+            var_map,
+            manifest,
+            &controller_of,
+            &is_required,
+            &mut |var_name, current| self.prompt_for_variable(var_name, current, manifest, options),
+        )
     }
 
-    fn process_template(
+    /// Check the values of variables with an `int` or `enum` type, asking for an invalid
+    /// one again when prompts can be answered and failing otherwise
+    fn check_variable_types(
         &self,
-        template_path: &Path,
-        variables: HashMap<String, String>,
-        force: bool,
-        dry_run: bool,
+        var_map: &mut HashMap<String, String>,
+        manifest: &TemplateManifest,
+        options: &GenerateOptions,
     ) -> Result<()> {
-        let mut processor = TemplateProcessor::new();
-        processor.set_variables(variables);
+        let mut typed: Vec<(&String, &manifest::VariableSpec)> = manifest
+            .variables
+            .iter()
+            .filter(|(var_name, _)| var_map.contains_key(*var_name))
+            .collect();
+        typed.sort_by_key(|(var_name, _)| *var_name);
+
+        let can_prompt = !options.no_input && std::io::stdin().is_terminal();
+        for (var_name, spec) in typed {
+            let value = &var_map[var_name];
+            let Err(problem) = spec.validate(value) else {
+                continue;
+            };
+            let name = self.config.get_internal_case().name(var_name);
+            if !can_prompt {
+                bail!("Invalid value for variable '{name}': {problem}");
+            }
+            eprintln!("Warning: invalid value for '{name}': {problem}");
+            let value = self.prompt_for_variable(var_name, Some(value), manifest, options)?;
+            var_map.insert(var_name.clone(), value);
+        }
+        Ok(())
+    }
 
-        let current_dir = std::env::current_dir().context("Failed to get current directory")?;
+    /// Show all variables with their current values in a form, editing the selected
+    /// one until the form is submitted with every required variable set
+    fn edit_variables(
+        &self,
+        var_names: &[String],
+        var_map: &mut HashMap<String, String>,
+        manifest: &TemplateManifest,
+        options: &GenerateOptions,
+        is_required: &dyn Fn(&str, &HashMap<String, String>) -> bool,
+    ) -> Result<()> {
+        let internal_case = self.config.get_internal_case();
+        let mut selected = 0;
+        loop {
+            let mut items: Vec<String> = var_names
+                .iter()
+                .map(|var_name| {
+                    let value = match var_map.get(var_name) {
+                        Some(_) if manifest.is_secret(var_name) => SECRET_MASK,
+                        Some(value) => value.as_str(),
+                        None if is_required(var_name, var_map) => "<required>",
+                        None => "<not needed>",
+                    };
+                    let description = manifest
+                        .variables
+                        .get(var_name)
+                        .and_then(|spec| spec.description.as_deref());
+                    let name = internal_case.name(var_name);
+                    match description {
+                        Some(description) => format!("{name} = {value}  ({description})"),
+                        None => format!("{name} = {value}"),
+                    }
+                })
+                .collect();
+            items.push("Generate".to_string());
+
+            selected = Select::new()
+                .with_prompt("Variables (select one to edit)")
+                .items(&items)
+                .default(selected)
+                .interact()?;
 
-        println!("Processing template from: {}", template_path.display());
+            let Some(var_name) = var_names.get(selected) else {
+                let missing: Vec<String> = var_names
+                    .iter()
+                    .filter(|var_name| {
+                        !var_map.contains_key(*var_name) && is_required(var_name, var_map)
+                    })
+                    .map(|var_name| internal_case.name(var_name))
+                    .collect();
+                if missing.is_empty() {
+                    return Ok(());
+                }
+                println!("Values are still needed for: {}", missing.join(", "));
+                continue;
+            };
 
-        if dry_run {
-            println!("DRY RUN - No files will be created");
+            let current = var_map.get(var_name).cloned();
+            let value =
+                self.prompt_for_variable(var_name, current.as_deref(), manifest, options)?;
+            var_map.insert(var_name.clone(), value);
+            // Move on to the next field
+            selected += 1;
         }
+    }
 
-        let mut files_created = 0;
-        let mut files_skipped = 0;
+    /// Create a template processor for the variable syntax in effect: the manifest's,
+    /// else the configuration's, else `SCAFFER_PREFIX`/`SCAFFER_CASES`, else the defaults
+    pub fn processor(&self, manifest: &TemplateManifest) -> Result<TemplateProcessor> {
+        let prefix = if let Some(prefix) = &manifest.prefix {
+            template::parse_prefix(prefix).context("Invalid prefix in scaffer.toml")?
+        } else if let Some(prefix) = self.config.get_prefix() {
+            template::parse_prefix(&prefix).context("Invalid prefix in scaffer configuration")?
+        } else if let Ok(prefix) = std::env::var("SCAFFER_PREFIX") {
+            template::parse_prefix(&prefix).context("Invalid SCAFFER_PREFIX")?
+        } else {
+            template::DEFAULT_PREFIX.to_string()
+        };
 
-        for entry in WalkDir::new(template_path)
-            .into_iter()
-            .filter_map(|e| e.ok())
+        let cases = if let Some(cases) = &manifest.cases {
+            template::parse_cases(cases).context("Invalid cases in scaffer.toml")?
+        } else if let Some(cases) = self.config.get_cases() {
+            template::parse_cases(&cases).context("Invalid cases in scaffer configuration")?
+        } else if let Ok(cases) = std::env::var("SCAFFER_CASES") {
+            let names: Vec<&str> = cases.split(',').collect();
+            template::parse_cases(&names).context("Invalid SCAFFER_CASES")?
+        } else {
+            CaseStyle::ALL.to_vec()
+        };
+
+        if let Some(acronym) = manifest
+            .acronyms
+            .iter()
+            .find(|acronym| acronym.is_empty() || !acronym.chars().all(char::is_alphanumeric))
         {
-            let src_path = entry.path();
+            bail!("Invalid acronym '{acronym}' in scaffer.toml: expected a single word");
+        }
 
-            // Skip the template root directory itself
-            if src_path == template_path {
-                continue;
-            }
+        let mut processor = TemplateProcessor::new();
+        processor.set_syntax(&prefix, &cases);
+        processor.set_acronyms(&manifest.acronyms);
+        processor.deny_tokens(&self.config.get_substitution_denylist());
+        processor.deny_tokens(&manifest.substitution_denylist);
+        if let Some(partials_dir) = self.config.get_partials_dir() {
+            processor.set_shared_partials(vec![utils::expand_path(Path::new(&partials_dir))?]);
+        }
+        Ok(processor)
+    }
 
-            // Calculate relative path from template root
-            let rel_path = src_path
-                .strip_prefix(template_path)
-                .context("Failed to calculate relative path")?;
+    /// The default values of variables, keyed in kebab-case; defaults from the manifest
+    /// win over the configuration's global ones
+    fn variable_defaults(&self, manifest: &TemplateManifest) -> HashMap<String, String> {
+        let mut defaults = self.config.get_global_var_defaults();
+        defaults.extend(manifest.variables.iter().filter_map(|(var_name, spec)| {
+            Some((var_name.to_case(Case::Kebab), spec.default.clone()?))
+        }));
+        defaults
+    }
 
-            // Process the path with variable substitution
-            let processed_rel_path = processor.process_path(&rel_path.to_string_lossy());
-            let dest_path = current_dir.join(&processed_rel_path);
+    /// Ask for a variable's value, offering the manifest's choices when there are any
+    fn prompt_for_variable(
+        &self,
+        var_name: &str,
+        current: Option<&str>,
+        manifest: &TemplateManifest,
+        options: &GenerateOptions,
+    ) -> Result<String> {
+        let name = self.config.get_internal_case().name(var_name);
+        // Secrets are neither echoed nor remembered
+        if manifest.is_secret(var_name) {
+            return Ok(Password::new()
+                .with_prompt(format!("Enter value for '{name}'"))
+                .interact()?);
+        }
 
-            if entry.file_type().is_dir() {
-                // Create directory
-                if !dry_run {
-                    fs::create_dir_all(&dest_path).with_context(|| {
-                        format!("Failed to create directory: {}", dest_path.display())
-                    })?;
-                }
-                println!("Created directory: {processed_rel_path}");
-            } else if entry.file_type().is_file() {
-                // Skip scaffer_init.py
-                if src_path.file_name() == Some(std::ffi::OsStr::new("scaffer_init.py")) {
-                    continue;
-                }
+        let spec = manifest.variables.get(var_name);
+        let choices = match spec {
+            Some(spec) => spec
+                .choices(&options.output_dir()?)
+                .with_context(|| format!("Failed to list choices for '{name}'"))?,
+            None => Vec::new(),
+        };
 
-                // Check if file already exists
-                if dest_path.exists() && !force {
-                    if dry_run {
-                        println!("Would skip existing file: {processed_rel_path}");
-                        files_skipped += 1;
-                        continue;
-                    }
+        // Without a current value, the value entered last time is offered
+        let mut history = (!options.no_value_history).then(History::load);
+        let last = history
+            .as_ref()
+            .and_then(|history| history.last_value(var_name))
+            .map(str::to_string);
+        let suggested = current.map(str::to_string).or(last);
 
-                    let overwrite = Confirm::new()
-                        .with_prompt(format!(
-                            "File '{processed_rel_path}' already exists. Overwrite?"
-                        ))
-                        .default(false)
-                        .interact()?;
+        let value = if choices.is_empty() {
+            let mut input = Input::new().with_prompt(format!("Enter value for '{name}'"));
+            input = match (current, suggested) {
+                (Some(current), _) => input.with_initial_text(current),
+                (None, Some(last)) => input.default(last),
+                (None, None) => input,
+            };
+            // A typed variable is asked for until its value is valid
+            if let Some(spec) = spec {
+                input = input.validate_with(|value: &String| spec.validate(value));
+            }
+            input.interact_text()?
+        } else {
+            let selection = Select::new()
+                .with_prompt(format!("Select value for '{name}'"))
+                .items(&choices)
+                .default(
+                    suggested
+                        .and_then(|suggested| {
+                            choices.iter().position(|choice| *choice == suggested)
+                        })
+                        .unwrap_or(0),
+                )
+                .interact()?;
+            choices[selection].clone()
+        };
 
-                    if !overwrite {
-                        println!("Skipped: {processed_rel_path}");
-                        files_skipped += 1;
-                        continue;
-                    }
-                }
+        if let Some(history) = &mut history {
+            history.record_value(var_name, &value);
+            // History is a convenience; failing to save it should not fail generation
+            let _ = history.save();
+        }
+        Ok(value)
+    }
 
-                // Read and process file content
-                let content = fs::read_to_string(src_path).with_context(|| {
-                    format!("Failed to read template file: {}", src_path.display())
-                })?;
+    fn prompt_for_template(&self, options: &GenerateOptions) -> Result<String> {
+        let templates = self.config.find_templates()?;
 
-                let processed_content = processor.process_text(&content);
+        if templates.is_empty() {
+            bail!("No templates found. Run 'scaffer setup' to configure template directories.");
+        }
 
-                if !dry_run {
-                    // Ensure parent directory exists
-                    if let Some(parent) = dest_path.parent() {
-                        fs::create_dir_all(parent).with_context(|| {
-                            format!("Failed to create parent directory: {}", parent.display())
-                        })?;
-                    }
+        let recent = if options.no_history {
+            Vec::new()
+        } else {
+            History::load().recent_templates()
+        };
+        let (recent, rest) = history::order_by_recent(&templates, &recent);
 
-                    // Write processed file
-                    fs::write(&dest_path, processed_content).with_context(|| {
-                        format!("Failed to write file: {}", dest_path.display())
-                    })?;
-                }
+        // Recently used templates first, separated from the alphabetical rest
+        const SEPARATOR: &str = "──────────";
+        let mut items = recent.clone();
+        if !recent.is_empty() && !rest.is_empty() {
+            items.push(SEPARATOR.to_string());
+        }
+        items.extend(rest);
+
+        loop {
+            let selection = Select::new()
+                .with_prompt("Select a template")
+                .items(&items)
+                .default(0)
+                .interact()?;
+
+            if items[selection] != SEPARATOR {
+                return Ok(items[selection].clone());
+            }
+        }
+    }
+
+    /// Return the download URL for a template given as a URL or configured URL name
+    ///
+    /// A name that no template has may be served by a wildcard entry, when the
+    /// configuration opts in with `wildcard_urls`. `{name}` in a
+    /// configured URL is the requested template name; it is replaced here, so a `name`
+    /// variable given with `-v` never reaches it.
+    fn resolve_template_url(&self, template_name: &str) -> Result<Option<String>> {
+        if template_name.starts_with("http://")
+            || template_name.starts_with("https://")
+            || template_name.starts_with(oci::SCHEME)
+        {
+            return Ok(Some(template_name.to_string()));
+        }
+
+        // A direct path takes precedence over configured URLs
+        if Path::new(template_name).exists() {
+            return Ok(None);
+        }
 
-                println!("Created file: {processed_rel_path}");
-                files_created += 1;
+        let urls = self.config.get_template_urls();
+        let url = match urls.get(template_name) {
+            Some(url) => Some(url),
+            None if self.config.get_wildcard_urls()
+                && self.find_template(template_name).is_err() =>
+            {
+                config::wildcard_url(&urls, template_name)
             }
+            None => None,
+        };
+        Ok(url.map(|url| url.replace("{name}", template_name)))
+    }
+
+    /// Substitute `{var}` placeholders in a template URL, prompting for missing values
+    ///
+    /// Placeholders are filled from the given variables. Values prompted for only fill
+    /// the URL: they never become template variables.
+    fn expand_url_variables(
+        &self,
+        url: &str,
+        var_map: &HashMap<String, String>,
+        options: &GenerateOptions,
+    ) -> Result<String> {
+        let mut values = HashMap::new();
+        for name in utils::url_placeholders(url) {
+            let value = match var_map.get(&name.to_case(Case::Kebab)) {
+                Some(value) => value.clone(),
+                None if options.no_input => bail!("No value given for URL variable '{name}'"),
+                None => Input::new()
+                    .with_prompt(format!("Enter value for URL variable '{name}'"))
+                    .interact_text()?,
+            };
+            values.insert(name, value);
         }
 
-        println!("\nTemplate processing complete!");
-        println!("Files created: {files_created}");
+        utils::expand_url_placeholders(url, &values)
+    }
+
+    /// Create a temporary directory for a downloaded or single-file template, in the
+    /// `--temp-dir` directory, else `SCAFFER_TMPDIR`, else the configured `temp_dir`,
+    /// else the system temp directory
+    ///
+    /// The directory is removed when the returned handle is dropped.
+    fn temp_dir(&self, options: &GenerateOptions) -> Result<TempDir> {
+        let base = match &options.temp_dir {
+            Some(dir) => Some(dir.clone()),
+            None => match std::env::var_os("SCAFFER_TMPDIR").filter(|dir| !dir.is_empty()) {
+                Some(dir) => Some(PathBuf::from(dir)),
+                None => self.config.get_temp_dir().map(PathBuf::from),
+            },
+        };
+
+        match base {
+            Some(base) => {
+                let base = utils::expand_path(&base)?;
+                fs::create_dir_all(&base).with_context(|| {
+                    format!("Failed to create temporary directory {}", base.display())
+                })?;
+                tempfile::Builder::new()
+                    .prefix("scaffer-")
+                    .tempdir_in(&base)
+                    .with_context(|| {
+                        format!("Failed to create temporary directory in {}", base.display())
+                    })
+            }
+            None => TempDir::new().context("Failed to create temporary directory"),
+        }
+    }
 
-        if files_skipped > 0 {
-            println!("Files skipped: {files_skipped}");
+    /// Download and extract a template into `temp_dir`, returning it along with the
+    /// template root inside it and the SHA-256 of the archive
+    fn download_template(
+        &self,
+        url: &str,
+        temp_dir: TempDir,
+        options: &GenerateOptions,
+    ) -> Result<(TempDir, PathBuf, String)> {
+        let show_progress = options.verbose() && !options.quiet_download;
+        if show_progress {
+            println!("Downloading template from {url}...");
         }
 
-        if dry_run {
-            println!("This was a dry run - no files were actually created.");
+        if url.starts_with(oci::SCHEME) {
+            return self.pull_oci_template(url, temp_dir);
         }
 
-        Ok(())
+        let response = http::get(url)?
+            .send()
+            .with_context(|| format!("Failed to download template from {url}"))?;
+
+        if response.status_code != 200 {
+            bail!("Failed to download template: HTTP {}", response.status_code);
+        }
+
+        let bytes = response.into_bytes();
+        let sha256 = utils::sha256_hex(&bytes);
+        if options.quiet_download {
+            // On stderr, so that it stays out of --stdout output
+            eprintln!("Downloaded {url}: {} bytes, sha256 {sha256}", bytes.len());
+        }
+
+        let zip_path = temp_dir.path().join("template.zip");
+        fs::write(&zip_path, bytes).context("Failed to write template zip file")?;
+
+        // Extract zip file
+        let extract_dir = temp_dir.path().join("extracted");
+        fs::create_dir_all(&extract_dir).context("Failed to create extraction directory")?;
+
+        if show_progress {
+            // The running count is redrawn in place, so it goes to stderr and only
+            // when that is a terminal
+            let live = !options.stdout && std::io::stderr().is_terminal();
+            let mut files_extracted = 0;
+            let mut bytes_extracted = 0;
+            let extracted =
+                utils::extract_zip_with_progress(&zip_path, &extract_dir, &mut |progress| {
+                    if !progress.name.ends_with('/') {
+                        files_extracted += 1;
+                        bytes_extracted += progress.bytes;
+                    }
+                    if live {
+                        eprint!("\rExtracting {}/{}", progress.index + 1, progress.total);
+                        let _ = std::io::stderr().flush();
+                    }
+                });
+            if live {
+                eprint!("\r\x1b[K");
+            }
+            extracted.context("Failed to extract template zip")?;
+            println!("Extracted {files_extracted} files ({bytes_extracted} bytes)");
+        } else {
+            utils::extract_zip(&zip_path, &extract_dir)
+                .context("Failed to extract template zip")?;
+        }
+
+        // Find the actual template directory (might be nested)
+        let processor = self.processor(&TemplateManifest::default())?;
+        let template_dir = utils::find_template_root(&extract_dir, &processor)?;
+
+        Ok((temp_dir, template_dir, sha256))
+    }
+
+    /// Pull a template distributed as an OCI artifact from a container registry
+    fn pull_oci_template(
+        &self,
+        url: &str,
+        temp_dir: TempDir,
+    ) -> Result<(TempDir, PathBuf, String)> {
+        let (extract_dir, sha256) = oci::pull(url, temp_dir.path())?;
+
+        // Find the actual template directory (might be nested)
+        let processor = self.processor(&TemplateManifest::default())?;
+        let template_dir = utils::find_template_root(&extract_dir, &processor)?;
+
+        Ok((temp_dir, template_dir, sha256))
+    }
+
+    /// Globs of the variable names to ignore, from the manifest and the configuration
+    fn ignored_vars(&self, manifest: &TemplateManifest) -> Result<globset::GlobSet> {
+        let mut patterns = self.config.get_ignore_vars();
+        patterns.extend(manifest.ignore_vars.iter().cloned());
+        utils::build_globset(&patterns).context("Invalid ignore_vars")
+    }
+
+    /// Point out when a template name is provided by more than one template directory;
+    /// only the local file system is checked, so that generation never waits on a
+    /// registry for it
+    fn warn_if_shadowing(&self, template_name: &str) -> Result<()> {
+        let candidates = self.config.directory_candidates(template_name)?;
+        if let Some((used, shadowed)) = candidates.split_first() {
+            if !shadowed.is_empty() {
+                let shadowed: Vec<String> = shadowed
+                    .iter()
+                    .map(|info| info.source.to_string())
+                    .collect();
+                eprintln!(
+                    "Warning: template '{template_name}' is provided by several sources; using {} (shadowing {})",
+                    used.source,
+                    shadowed.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Process one file of a local template with the given `name=value` variables,
+    /// returning its content without writing anything
+    ///
+    /// Variables without a value are left as their tokens and named in a warning.
+    pub fn preview_file(
+        &self,
+        template: &str,
+        variables: &[String],
+        path: &str,
+    ) -> Result<Vec<u8>> {
+        let template_path = self.find_template(template)?;
+        let src_path = template_path.join(path);
+        if !template_path.is_dir() || !src_path.is_file() {
+            bail!(
+                "'{path}' is not a file in template {}",
+                template_path.display()
+            );
+        }
+
+        let manifest = TemplateManifest::load(&template_path)?;
+        let mut processor = self.processor(&manifest)?;
+        let mut var_map: HashMap<String, String> = variables
+            .iter()
+            .filter_map(|var_str| var_str.split_once('='))
+            .map(|(key, value)| (key.to_case(Case::Kebab), value.to_string()))
+            .collect();
+
+        // The variables of the file get defaults and pseudo-variable values as they
+        // would when generating; those without a value are left as they are
+        let rules = FileRules::new(&manifest)?;
+        let text = fs::read_to_string(&src_path)
+            .ok()
+            .filter(|_| processor.substitutes_content())
+            .map(|text| {
+                let rel_path = src_path.strip_prefix(&template_path).unwrap_or(&src_path);
+                let text = rules.substituted_part(rel_path, &text);
+                processor
+                    .expand_includes(text, &template_path)
+                    .unwrap_or_else(|_| text.to_string())
+            });
+        let file_vars = text
+            .as_deref()
+            .map(|text| processor.extract_variables(text))
+            .unwrap_or_default();
+        let mut pseudo = PseudoVariables::new(None);
+        pseudo.set_template(template, &template_path.display().to_string());
+        let options = GenerateOptions {
+            no_input: true,
+            missing_placeholder: Some(String::new()),
+            ..Default::default()
+        };
+        self.fill_variables(
+            file_vars.clone(),
+            &mut var_map,
+            &mut pseudo,
+            &manifest,
+            &options,
+        )?;
+        processor.set_variables(var_map);
+        for (name, value) in pseudo.values() {
+            processor.set_literal(name, value);
+        }
+
+        let content = render_file(&processor, &manifest, &rules, &src_path, &template_path)?;
+        let mut missing: Vec<String> = file_vars
+            .into_iter()
+            .filter(|var| processor.variable(var).is_none())
+            .collect();
+        missing.sort();
+        if !missing.is_empty() {
+            eprintln!(
+                "Warning: no value given for {}; left as in the template",
+                missing.join(", ")
+            );
+        }
+        Ok(content)
+    }
+
+    /// The steps of resolving a template name, as `--explain` prints them: the
+    /// configuration consulted, the places searched, and what the name resolves to
+    fn resolution_steps(&self, template_name: &str) -> Result<Vec<String>> {
+        let mut steps = Vec::new();
+        match ScafferConfig::find_local_file()? {
+            Some(path) => steps.push(format!("local configuration: {}", path.display())),
+            None => steps.push("local configuration: none in this or a parent directory".into()),
+        }
+        let global = ScafferConfig::global_file()?;
+        let found = if global.exists() { "" } else { " (not found)" };
+        steps.push(format!("global configuration: {}{found}", global.display()));
+
+        if template_name.contains("://") {
+            steps.push(format!("'{template_name}' is a URL and is downloaded"));
+            return Ok(steps);
+        }
+        if Path::new(template_name).exists() {
+            steps.push(format!(
+                "'{template_name}' is an existing path and is used as it is"
+            ));
+            return Ok(steps);
+        }
+
+        for registry_url in self.config.get_registries() {
+            steps.push(format!(
+                "registry {registry_url}: {}",
+                registry::cache_state(&registry_url)
+            ));
+        }
+        for dir in self.config.get_template_directories() {
+            let missing = if dir.is_dir() { "" } else { " (missing)" };
+            steps.push(format!("template directory: {}{missing}", dir.display()));
+        }
+        if self.config.discover {
+            let count = self.config.get_discovered_templates()?.len();
+            steps.push(format!("templates discovered in the repository: {count}"));
+        }
+
+        let candidates = self.config.template_candidates(template_name)?;
+        for (i, info) in candidates.iter().enumerate() {
+            let role = if i == 0 { "used" } else { "shadowed" };
+            steps.push(format!(
+                "'{template_name}' is provided by {} ({role})",
+                info.source
+            ));
+        }
+
+        match self.resolve_template_url(template_name)? {
+            Some(url) => {
+                if candidates.is_empty() {
+                    steps.push(format!("'{template_name}' matches a wildcard template URL"));
+                }
+                steps.push(format!(
+                    "resolved to {url}, downloaded for this run (template downloads aren't cached)"
+                ));
+            }
+            None => match self.find_template(template_name) {
+                Ok(path) => steps.push(format!("resolved to {}", path.display())),
+                Err(_) => steps.push(format!("'{template_name}' was not found")),
+            },
+        }
+        Ok(steps)
+    }
+
+    /// Resolve a template name (or path) to a local template directory or file
+    pub fn find_template(&self, template_name: &str) -> Result<PathBuf> {
+        // First check if it's a direct path
+        let direct_path = PathBuf::from(template_name);
+        if direct_path.exists() {
+            return Ok(direct_path);
+        }
+
+        // Directory templates, in the template directories and then those discovered in
+        // the repository, by their manifest name or else their directory name
+        if let Some(template_path) = self.config.find_local_template(template_name)? {
+            return Ok(template_path);
+        }
+
+        // Single-file templates and nested paths inside template directories
+        for template_dir in self.config.get_template_directories() {
+            let template_path = template_dir.join(template_name);
+            if template_path.is_file()
+                || (template_path.is_dir() && template_name.contains(['/', '\\']))
+            {
+                return Ok(template_path);
+            }
+        }
+
+        bail!("Template '{}' not found", template_name);
+    }
+
+    fn scan_template_variables(
+        &self,
+        template_path: &Path,
+        manifest: &TemplateManifest,
+        options: &GenerateOptions,
+    ) -> Result<HashSet<String>> {
+        let mut processor = self.processor(manifest)?;
+        processor.set_scope(options.scope());
+
+        // Check if there's a scaffer_init.py file for custom logic
+        let init_file = template_path.join("scaffer_init.py");
+        if init_file.exists() && options.verbose() {
+            println!("Found scaffer_init.py - custom template initialization");
+            // TODO: Implement Python script execution for advanced templates
+        }
+
+        // Scan all files in the template, except conditional directories whose
+        // variables are only needed once their condition is known
+        let conditional_dirs: Vec<PathBuf> = conditional_dir_paths(manifest).collect();
+        let rules = FileRules::new(manifest)?;
+        let mut variables = extract_entry_variables(
+            &processor,
+            template_path,
+            &rules,
+            template_entries(template_path, manifest, &conditional_dirs),
+        );
+
+        // Of the manifest, the header and the rename destinations are substituted
+        if processor.substitutes_content()
+            && let Some(header) = &manifest.header
+        {
+            variables.extend(processor.extract_variables(header));
+        }
+        if processor.substitutes_names() {
+            for renamed in manifest.renames.values().chain(&manifest.root_as) {
+                variables.extend(processor.extract_variables(renamed));
+            }
+        }
+
+        // Shared partials are outside the template, so their variables are found
+        // through the files that include them
+        if processor.substitutes_content() {
+            for entry in template_entries(template_path, manifest, &conditional_dirs)
+                .filter(|e| e.file_type().is_file())
+            {
+                let rel_path = entry
+                    .path()
+                    .strip_prefix(template_path)
+                    .unwrap_or(entry.path());
+                if let Ok(content) = fs::read_to_string(entry.path())
+                    && let content = rules.substituted_part(rel_path, &content)
+                    && !processor.find_includes(content).is_empty()
+                    && let Ok(expanded) = processor.expand_includes(content, template_path)
+                {
+                    variables.extend(processor.extract_variables(&expanded));
+                }
+            }
+        }
+        Ok(variables)
+    }
+
+    /// Generate the files of a template with the given variables, and the values of
+    /// pseudo-variables, which are substituted as they are
+    fn process_template(
+        &self,
+        template_path: &Path,
+        variables: HashMap<String, String>,
+        pseudo_values: &HashMap<String, String>,
+        manifest: &TemplateManifest,
+        options: &GenerateOptions,
+        report: &mut GenerationReport,
+    ) -> Result<()> {
+        let dry_run = options.dry_run;
+        let verbose = options.verbose();
+        let mut processor = self.processor(manifest)?;
+        let skipped_dirs = inactive_dirs(manifest, &processor, &variables, pseudo_values);
+        processor.set_variables(variables);
+        for (name, value) in pseudo_values {
+            processor.set_literal(name, value);
+        }
+        if let Some(placeholder) = &options.missing_placeholder {
+            for var in &report.missing_vars {
+                processor.set_placeholder(var, &placeholder.replace("{name}", var));
+            }
+        }
+        processor.set_scope(options.scope());
+        processor.set_filename_case(
+            options
+                .filename_case
+                .or(manifest.filename_case)
+                .unwrap_or_default(),
+        );
+        // Text is written as rendered unless an encoding is asked for
+        let encoding = options.output_encoding.or(manifest.output_encoding);
+        let encode = |content: Vec<u8>| match encoding {
+            Some(encoding) => encoding.encode(content),
+            None => content,
+        };
+
+        let output_dir = options.output_dir()?;
+
+        if verbose {
+            println!("Processing template from: {}", template_path.display());
+
+            if dry_run {
+                println!("DRY RUN - No files will be created");
+                print_variables(&report.variables, &report.secrets, options.json)?;
+            }
+        }
+
+        // Files pulled in via include directives are partials, not standalone outputs
+        let partials = self.collect_partials(template_path, manifest, &processor, &skipped_dirs);
+
+        // Refuse to write anything if two sources would produce the same file
+        let rules = FileRules::new(manifest)?;
+        let case_collisions = check_destination_collisions(
+            template_path,
+            &processor,
+            manifest,
+            &rules,
+            &partials,
+            &skipped_dirs,
+            options.strip_components,
+        )?;
+        for (first, second) in case_collisions {
+            let message = format!(
+                "{first} and {second} differ only in letter case; on a case-insensitive \
+                 file system (macOS, Windows) one would overwrite the other"
+            );
+            if options.strict {
+                bail!("{message} (--strict)");
+            }
+            eprintln!("Warning: {message}");
+        }
+
+        let validator = if options.validate {
+            if !options.allow_scripts {
+                bail!("--validate runs external commands and requires --allow-scripts");
+            }
+            Some(Validator::new(self.config.get_validators()))
+        } else {
+            None
+        };
+
+        // Destinations matching these globs are regenerated without prompting
+        let mut auto_overwrite_patterns = self.config.get_auto_overwrite();
+        auto_overwrite_patterns.extend(manifest.auto_overwrite.iter().cloned());
+        let auto_overwrite = utils::build_globset(&auto_overwrite_patterns)?;
+        let modes = file_modes(manifest)?;
+        let merges = merge_rules(manifest)?;
+        // With --force, files are replaced as a whole rather than updated
+        let region_files = if options.force {
+            globset::GlobSet::empty()
+        } else {
+            utils::build_globset(&manifest.update_regions)
+                .context("Invalid update_regions in scaffer.toml")?
+        };
+
+        // With --atomic, files are written to a staging directory that is discarded
+        // on failure. It is placed in the output directory when possible so that
+        // files can be moved into place without copying.
+        let staging = if options.atomic && !dry_run {
+            let staging = if output_dir.is_dir() {
+                tempfile::Builder::new()
+                    .prefix(".scaffer-staging-")
+                    .tempdir_in(&output_dir)
+                    .context("Failed to create staging directory")?
+            } else {
+                self.temp_dir(options)?
+            };
+            Some(staging)
+        } else {
+            None
+        };
+
+        // With --patch, changes are collected as a diff and nothing else is written
+        let mut patch = options.patch.as_ref().map(|_| String::new());
+
+        // With --stdout, processed files are collected and printed once all succeeded
+        let mut printed: Option<Vec<(String, Vec<u8>)>> = options.stdout.then(Vec::new);
+
+        // With --to-zip, processed files are collected, with their Unix permissions, and
+        // written to the archive once all succeeded
+        let mut archived: Option<Vec<utils::ArchiveEntry>> =
+            options.to_zip.as_ref().map(|_| Vec::new());
+
+        let mut files_invalid = 0;
+        let mut created_dirs: HashSet<PathBuf> = HashSet::new();
+
+        // Large templates show a progress bar, counted from a first walk over the files
+        let total_files = template_entries(template_path, manifest, &skipped_dirs)
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| {
+                e.path()
+                    .strip_prefix(template_path)
+                    .is_ok_and(|rel_path| is_output_file(e.path(), rel_path, &partials))
+            })
+            .count();
+        let mut progress = Progress::new(
+            total_files,
+            options.verbosity != Verbosity::Quiet
+                && !options.stdout
+                && std::io::stderr().is_terminal(),
+        );
+
+        // Ctrl-C stops the run between files, with a report of what was written
+        let _interrupt_guard = interrupt::guard();
+        let mut interrupted = false;
+
+        for entry in template_entries(template_path, manifest, &skipped_dirs) {
+            let src_path = entry.path();
+
+            // Skip the template root directory itself
+            if src_path == template_path {
+                continue;
+            }
+
+            // Calculate relative path from template root
+            let rel_path = src_path
+                .strip_prefix(template_path)
+                .context("Failed to calculate relative path")?;
+
+            // Directories are created lazily when a file is written into them,
+            // so subtrees whose files are all skipped leave no empty folders
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            // Skip scaffer_init.py, the manifest and partials
+            if !is_output_file(src_path, rel_path, &partials) {
+                continue;
+            }
+
+            progress.clear();
+            if interrupt::interrupted() {
+                interrupted = true;
+                break;
+            }
+
+            // Process the path with variable substitution; a file inside each-directories
+            // is generated once per item
+            for (processor, processed_rel_path) in file_outputs(
+                &processor,
+                manifest,
+                &rules,
+                rel_path,
+                options.strip_components,
+            )? {
+                if let Some(printed) = &mut printed {
+                    let content =
+                        render_file(&processor, manifest, &rules, src_path, template_path)?;
+                    report.record(src_path, &processed_rel_path, FileOutcome::Created);
+                    printed.push((processed_rel_path, content));
+                    continue;
+                }
+
+                if let Some(archived) = &mut archived {
+                    let content =
+                        render_file(&processor, manifest, &rules, src_path, template_path)?;
+                    if verbose {
+                        let verb = if dry_run { "Would archive" } else { "Archived" };
+                        println!("{verb} file: {processed_rel_path}");
+                    }
+                    report.record(src_path, &processed_rel_path, FileOutcome::Created);
+                    archived.push(utils::ArchiveEntry {
+                        name: utils::glob_path(&processed_rel_path),
+                        content: encode(content),
+                        mode: mode_for(&modes, &processed_rel_path)
+                            .or_else(|| utils::unix_mode(src_path)),
+                    });
+                    continue;
+                }
+
+                let dest_path = utils::long_path(&output_dir.join(&processed_rel_path));
+                let dest_exists = dest_path.exists();
+                let merge = merge_for(&merges, &processed_rel_path).filter(|_| dest_exists);
+                let updates_regions = dest_exists
+                    && merge.is_none()
+                    && region_files.is_match(utils::glob_path(&processed_rel_path));
+
+                // Scaffolding into an existing project only ever adds files
+                if dest_exists && options.into_existing {
+                    report.record(src_path, &processed_rel_path, FileOutcome::Skipped);
+                    continue;
+                }
+
+                if let Some(patch) = &mut patch {
+                    let mut content =
+                        render_file(&processor, manifest, &rules, src_path, template_path)?;
+                    if let Some(arrays) = merge {
+                        content = merge::merge_into_file(&dest_path, &content, arrays)?;
+                    } else if updates_regions
+                        && let Some(merged) = merge_marked(&dest_path, &content)
+                    {
+                        content = merged;
+                    }
+                    let outcome = add_to_patch(patch, content, &processed_rel_path, &dest_path)?;
+                    if verbose && outcome != FileOutcome::Skipped {
+                        println!("Patched file: {processed_rel_path}");
+                    }
+                    report.record(src_path, &processed_rel_path, outcome);
+                    continue;
+                }
+
+                // A file that an earlier template of the chain wrote with exactly the
+                // same content is left as it is, without asking
+                if dest_exists
+                    && let Some(written) = report.hashes.get(&utils::glob_path(&processed_rel_path))
+                {
+                    let content = encode(render_file(
+                        &processor,
+                        manifest,
+                        &rules,
+                        src_path,
+                        template_path,
+                    )?);
+                    if *written == utils::content_hash(&content)
+                        && fs::read(&dest_path).is_ok_and(|existing| existing == content)
+                    {
+                        if verbose {
+                            println!("Unchanged: {processed_rel_path}");
+                        }
+                        report.record(src_path, &processed_rel_path, FileOutcome::Unchanged);
+                        continue;
+                    }
+                }
+
+                let auto_overwritten =
+                    dest_exists && auto_overwrite.is_match(utils::glob_path(&processed_rel_path));
+
+                // An existing file of `update_regions` with marked regions that the
+                // template has too only gets those regions updated
+                let marked = if updates_regions {
+                    let content =
+                        render_file(&processor, manifest, &rules, src_path, template_path)?;
+                    merge_marked(&dest_path, &content)
+                } else {
+                    None
+                };
+
+                // Check if file already exists; a file merged into isn't replaced
+                if dest_exists
+                    && !options.force
+                    && !auto_overwritten
+                    && merge.is_none()
+                    && marked.is_none()
+                {
+                    if dry_run {
+                        if verbose {
+                            println!("Would skip existing file: {processed_rel_path}");
+                        }
+                        report.record(src_path, &processed_rel_path, FileOutcome::Skipped);
+                        continue;
+                    }
+
+                    if options.no_input {
+                        bail!(
+                            "File '{processed_rel_path}' already exists (use --force to overwrite)"
+                        );
+                    }
+
+                    let overwrite = confirm_overwrite(
+                        &processor,
+                        manifest,
+                        &rules,
+                        src_path,
+                        template_path,
+                        &processed_rel_path,
+                        &dest_path,
+                    )?;
+
+                    if !overwrite {
+                        if verbose {
+                            println!("Skipped: {processed_rel_path}");
+                        }
+                        report.record(src_path, &processed_rel_path, FileOutcome::Skipped);
+                        continue;
+                    }
+                }
+
+                // Read and process file content
+                let mut processed_content = match marked {
+                    Some(merged) => {
+                        if verbose {
+                            println!("Updated marked regions in: {processed_rel_path}");
+                        }
+                        merged
+                    }
+                    None => render_file(&processor, manifest, &rules, src_path, template_path)?,
+                };
+                if let Some(arrays) = merge {
+                    processed_content =
+                        merge::merge_into_file(&dest_path, &processed_content, arrays)?;
+                    if verbose {
+                        println!("Merged into: {processed_rel_path}");
+                    }
+                }
+
+                if let Some(validator) = &validator {
+                    match validator.validate(&processed_rel_path, &processed_content)? {
+                        Some(ValidationOutcome::Invalid(message)) => {
+                            eprintln!("Invalid: {processed_rel_path}\n{message}");
+                            files_invalid += 1;
+                        }
+                        Some(ValidationOutcome::Valid) if verbose => {
+                            println!("Valid: {processed_rel_path}");
+                        }
+                        _ => {}
+                    }
+                }
+
+                let processed_content = encode(processed_content);
+
+                let outcome = if auto_overwritten {
+                    FileOutcome::AutoOverwritten
+                } else if dest_exists {
+                    FileOutcome::Overwritten
+                } else {
+                    FileOutcome::Created
+                };
+
+                // Materialize the parent directories of this file
+                if let Some(parent) = dest_path.parent() {
+                    let mut missing = Vec::new();
+                    let mut dir = parent;
+                    while dir != output_dir && !dir.exists() && !created_dirs.contains(dir) {
+                        missing.push(dir.to_path_buf());
+                        match dir.parent() {
+                            Some(next) => dir = next,
+                            None => break,
+                        }
+                    }
+
+                    if !dry_run && staging.is_none() {
+                        fs::create_dir_all(parent).with_context(|| {
+                            format!("Failed to create parent directory: {}", parent.display())
+                        })?;
+                    }
+
+                    for dir in missing.into_iter().rev() {
+                        if verbose {
+                            let rel_dir = dir.strip_prefix(&output_dir).unwrap_or(&dir);
+                            println!("Created directory: {}", rel_dir.display());
+                        }
+                        created_dirs.insert(dir);
+                    }
+                }
+
+                if !dry_run {
+                    let write_path = match &staging {
+                        Some(staging) => {
+                            let staged =
+                                utils::long_path(&staging.path().join(&processed_rel_path));
+                            if let Some(parent) = staged.parent() {
+                                fs::create_dir_all(parent).with_context(|| {
+                                    format!(
+                                        "Failed to create staging directory: {}",
+                                        parent.display()
+                                    )
+                                })?;
+                            }
+                            staged
+                        }
+                        None => dest_path.clone(),
+                    };
+
+                    // Write processed file
+                    fs::write(&write_path, &processed_content).with_context(|| {
+                        format!("Failed to write file: {}", write_path.display())
+                    })?;
+                    if let Some(mode) = mode_for(&modes, &processed_rel_path) {
+                        utils::set_unix_mode(&write_path, mode)?;
+                    }
+                    report.bytes_written += processed_content.len() as u64;
+                }
+
+                if !dry_run {
+                    report.hashes.insert(
+                        utils::glob_path(&processed_rel_path),
+                        utils::content_hash(&processed_content),
+                    );
+                }
+                if verbose {
+                    match outcome {
+                        FileOutcome::AutoOverwritten => {
+                            println!("Auto-overwrote file: {processed_rel_path}")
+                        }
+                        _ => println!("Created file: {processed_rel_path}"),
+                    }
+                }
+                report.record(src_path, &processed_rel_path, outcome);
+            }
+
+            progress.advance();
+        }
+        drop(progress);
+
+        if interrupted {
+            let writes_files = !dry_run
+                && staging.is_none()
+                && patch.is_none()
+                && printed.is_none()
+                && archived.is_none();
+            report_interruption(report, total_files, writes_files);
+            bail!("Generation interrupted");
+        }
+
+        if let Some(staging) = &staging {
+            commit_staged(staging.path(), &output_dir, report)?;
+        }
+
+        if patch.is_none() && printed.is_none() && archived.is_none() {
+            let destination = match output_root(&processor, manifest) {
+                Some(root) => format!("{root}/.gitignore"),
+                None => ".gitignore".to_string(),
+            };
+            add_gitignore(
+                manifest,
+                options,
+                template_path,
+                &output_dir,
+                &destination,
+                report,
+            )?;
+        } else if !manifest.gitignore_stacks.is_empty() || options.with_gitignore {
+            eprintln!("Warning: no .gitignore is added with --to-zip, --patch or --stdout");
+        }
+
+        if let Some(printed) = &printed {
+            print_files(printed)?;
+        }
+
+        if let (Some(zip_path), Some(archived)) = (&options.to_zip, &archived) {
+            if !dry_run {
+                let zip_path = utils::expand_path(zip_path)?;
+                report.bytes_written = utils::write_zip(&zip_path, archived)?;
+                if options.verbosity != Verbosity::Quiet {
+                    println!("Archive written to {}", zip_path.display());
+                }
+            }
+        }
+
+        if let (Some(patch_path), Some(patch)) = (&options.patch, &patch) {
+            fs::write(patch_path, patch)
+                .with_context(|| format!("Failed to write patch: {}", patch_path.display()))?;
+            if options.verbosity != Verbosity::Quiet {
+                println!("Patch written to {}", patch_path.display());
+            }
+        }
+
+        let files_created = report.written();
+        let files_skipped = report.count(FileOutcome::Skipped);
+        let files_auto_overwritten = report.count(FileOutcome::AutoOverwritten);
+        let files_unchanged = report.count(FileOutcome::Unchanged);
+        // With --patch, the files only go into the patch
+        let (created_label, created_verb) = if options.patch.is_some() {
+            ("Files in patch", "patched")
+        } else {
+            ("Files created", "created")
+        };
+
+        match options.verbosity {
+            Verbosity::Normal => {
+                println!("\nTemplate processing complete!");
+                println!("{created_label}: {files_created}");
+
+                if files_auto_overwritten > 0 {
+                    println!("Files auto-overwritten: {files_auto_overwritten}");
+                }
+
+                if files_unchanged > 0 {
+                    println!("Files unchanged: {files_unchanged}");
+                }
+
+                if files_skipped > 0 {
+                    println!("Files skipped: {files_skipped}");
+                }
+
+                if options.into_existing && files_skipped > 0 {
+                    println!("\nExisting files left untouched:");
+                    for action in &report.actions {
+                        if action.outcome == FileOutcome::Skipped {
+                            println!("  {}", action.destination);
+                        }
+                    }
+                }
+
+                if dry_run {
+                    println!("{}", utils::DRY_RUN_NOTICE);
+                }
+            }
+            Verbosity::SummaryOnly => {
+                let suffix = if dry_run { " (dry run)" } else { "" };
+                println!(
+                    "scaffer: {files_created} {created_verb}, {files_skipped} skipped{suffix}"
+                );
+            }
+            Verbosity::Quiet => {}
+        }
+
+        if files_invalid > 0 {
+            bail!("{files_invalid} file(s) failed validation");
+        }
+
+        Ok(())
+    }
+
+    /// Collect files referenced by include directives anywhere in the template
+    fn collect_partials(
+        &self,
+        template_path: &Path,
+        manifest: &TemplateManifest,
+        processor: &TemplateProcessor,
+        skipped_dirs: &[PathBuf],
+    ) -> HashSet<PathBuf> {
+        let mut partials = HashSet::new();
+
+        for entry in template_entries(template_path, manifest, skipped_dirs)
+            .filter(|e| e.file_type().is_file())
+        {
+            if let Ok(content) = fs::read_to_string(entry.path()) {
+                for include in processor.find_includes(&content) {
+                    if let Some(partial) = processor.resolve_include(&include, template_path) {
+                        partials.insert(partial);
+                    }
+                }
+            }
+        }
+
+        partials
+    }
+}
+
+/// Print how far an interrupted run got and which files it wrote, so that they can
+/// be cleaned up
+fn report_interruption(report: &GenerationReport, total_files: usize, writes_files: bool) {
+    let processed: HashSet<&Path> = report.actions.iter().map(|a| a.source.as_path()).collect();
+    eprintln!(
+        "Interrupted after {} of {total_files} template files",
+        processed.len()
+    );
+
+    let written: Vec<&FileAction> = report
+        .actions
+        .iter()
+        .filter(|action| {
+            matches!(
+                action.outcome,
+                FileOutcome::Created | FileOutcome::Overwritten | FileOutcome::AutoOverwritten
+            )
+        })
+        .collect();
+    if !writes_files || written.is_empty() {
+        eprintln!("No files were written");
+        return;
+    }
+
+    eprintln!("Files written before the interruption:");
+    for action in written {
+        let note = match action.outcome {
+            FileOutcome::Created => "",
+            _ => " (overwritten)",
+        };
+        eprintln!("  {}{note}", action.destination);
+    }
+}
+
+/// Print the resolved variables of a run, sorted by name, as a table or a JSON object
+fn print_variables(
+    variables: &HashMap<String, String>,
+    secrets: &HashSet<String>,
+    json: bool,
+) -> Result<()> {
+    let variables: BTreeMap<&String, &str> = variables
+        .iter()
+        .map(|(name, value)| {
+            if secrets.contains(name) {
+                (name, SECRET_MASK)
+            } else {
+                (name, value.as_str())
+            }
+        })
+        .collect();
+    if json {
+        println!("{}", serde_json::to_string(&variables)?);
+        return Ok(());
+    }
+
+    println!("Variables:");
+    let width = variables.keys().map(|name| name.len()).max().unwrap_or(0);
+    for (name, value) in variables {
+        println!("  {name:width$} = {value}");
+    }
+    Ok(())
+}
+
+/// Set up `temp_dir` as a template directory holding just the given file, so that a
+/// single file can be generated like any template
+fn single_file_template(path: &Path, temp_dir: TempDir) -> Result<(TempDir, PathBuf)> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Invalid template file: {}", path.display()))?;
+    fs::copy(path, temp_dir.path().join(file_name))
+        .with_context(|| format!("Failed to read template file: {}", path.display()))?;
+
+    let template_path = temp_dir.path().to_path_buf();
+    Ok((temp_dir, template_path))
+}
+
+/// The manifest's `modes`: a glob matcher for destinations and the mode to give them
+fn file_modes(manifest: &TemplateManifest) -> Result<Vec<(globset::GlobMatcher, u32)>> {
+    manifest
+        .modes
+        .iter()
+        .map(|(pattern, mode)| {
+            let matcher = globset::Glob::new(pattern)
+                .with_context(|| format!("Invalid glob pattern in modes: {pattern}"))?
+                .compile_matcher();
+            let mode = u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                .ok()
+                .filter(|mode| *mode <= 0o7777)
+                .with_context(|| {
+                    format!("Invalid mode '{mode}' for '{pattern}' in scaffer.toml: expected an octal mode such as \"0755\"")
+                })?;
+            Ok((matcher, mode))
+        })
+        .collect()
+}
+
+/// Add the ignore rules of the manifest's `gitignore_stacks` to the `.gitignore` at
+/// `destination` in the output, or with `--with-gitignore` those of the stacks
+/// detected in the output
+///
+/// An existing `.gitignore`, whether the project's or one the template generated, only
+/// gets the rules it lacks.
+fn add_gitignore(
+    manifest: &TemplateManifest,
+    options: &GenerateOptions,
+    template_path: &Path,
+    output_dir: &Path,
+    destination: &str,
+    report: &mut GenerationReport,
+) -> Result<()> {
+    let path = output_dir.join(destination);
+    let stacks = if !manifest.gitignore_stacks.is_empty() {
+        gitignore::stacks_by_name(&manifest.gitignore_stacks)
+            .context("Invalid gitignore_stacks in scaffer.toml")?
+    } else if options.with_gitignore {
+        // Nothing is written on a dry run, so the stacks are told by the template
+        let project_dir = if options.dry_run {
+            template_path
+        } else {
+            path.parent().unwrap_or(output_dir)
+        };
+        let stacks = gitignore::detect_stacks(project_dir);
+        if stacks.is_empty() {
+            eprintln!("Warning: no project type detected, so no .gitignore was added");
+            return Ok(());
+        }
+        stacks
+    } else {
+        return Ok(());
+    };
+
+    let rules = gitignore::render(&stacks);
+    let (content, outcome) = if path.exists() {
+        let existing = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        match gitignore::append(&existing, &rules) {
+            (_, 0) => (existing, FileOutcome::Unchanged),
+            (merged, _) => (merged, FileOutcome::Overwritten),
+        }
+    } else {
+        (rules, FileOutcome::Created)
+    };
+
+    if !options.dry_run && outcome != FileOutcome::Unchanged {
+        fs::write(&path, &content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        report.bytes_written += content.len() as u64;
+    }
+    if !options.dry_run {
+        report.hashes.insert(
+            destination.to_string(),
+            utils::content_hash(content.as_bytes()),
+        );
+    }
+    if options.verbose() {
+        let names: Vec<&str> = stacks.iter().map(|stack| stack.name).collect();
+        println!("Ignore rules for {} in: {destination}", names.join(", "));
+    }
+
+    // A .gitignore the template generated itself is already accounted for
+    if !report
+        .actions
+        .iter()
+        .any(|action| utils::glob_path(&action.destination) == destination)
+    {
+        report.record(
+            &template_path.join(manifest::MANIFEST_FILE),
+            destination,
+            outcome,
+        );
+    }
+    Ok(())
+}
+
+/// The manifest's `merge` rules: a glob matcher for destinations and how to merge
+/// arrays into them
+fn merge_rules(manifest: &TemplateManifest) -> Result<Vec<(globset::GlobMatcher, ArrayStrategy)>> {
+    manifest
+        .merge
+        .iter()
+        .map(|(pattern, rule)| {
+            let matcher = globset::Glob::new(pattern)
+                .with_context(|| format!("Invalid glob pattern in merge: {pattern}"))?
+                .compile_matcher();
+            Ok((matcher, rule.arrays))
+        })
+        .collect()
+}
+
+/// The content of an existing file with the marked regions it shares with the generated
+/// content updated; `None` when they share none, or when the markers are broken, which
+/// is warned about, so that the file is handled like any other existing file
+fn merge_marked(dest_path: &Path, content: &[u8]) -> Option<Vec<u8>> {
+    merge::merge_marked_file(dest_path, content).unwrap_or_else(|err| {
+        eprintln!("Warning: {err:#}; handling it like any other existing file");
+        None
+    })
+}
+
+/// How to merge into a destination, by the first of `merges` whose glob matches it
+fn merge_for(
+    merges: &[(globset::GlobMatcher, ArrayStrategy)],
+    rel_path: &str,
+) -> Option<ArrayStrategy> {
+    let rel_path = utils::glob_path(rel_path);
+    merges
+        .iter()
+        .find(|(matcher, _)| matcher.is_match(&rel_path))
+        .map(|(_, arrays)| *arrays)
+}
+
+/// The mode of the first of `modes` whose glob matches a destination
+fn mode_for(modes: &[(globset::GlobMatcher, u32)], rel_path: &str) -> Option<u32> {
+    let rel_path = utils::glob_path(rel_path);
+    modes
+        .iter()
+        .find(|(matcher, _)| matcher.is_match(&rel_path))
+        .map(|(_, mode)| *mode)
+}
+
+/// How a template source (directory or URL) is told apart from others in a chain
+fn chain_key(source: &str) -> String {
+    match fs::canonicalize(source) {
+        Ok(path) => path.display().to_string(),
+        Err(_) => source.to_string(),
+    }
+}
+
+/// Prompt for variables in the manifest's order, offering the value each has (if any)
+/// to accept or change, and skipping those whose `required_if` condition is false
+///
+/// A controlling variable that is to be asked too is asked before the variables it
+/// controls.
+fn ask_variables(
+    var_names: &[String],
+    var_map: &mut HashMap<String, String>,
+    manifest: &TemplateManifest,
+    controller_of: &dyn Fn(&str) -> Option<String>,
+    is_required: &dyn Fn(&str, &HashMap<String, String>) -> bool,
+    prompt: &mut dyn FnMut(&str, Option<&str>) -> Result<String>,
+) -> Result<()> {
+    let mut asked = HashSet::new();
+    for (label, group) in manifest.prompt_order(var_names) {
+        if let Some(label) = label {
+            eprintln!("\n{label}");
+        }
+        for var_name in group {
+            if let Some(controller) = controller_of(&var_name) {
+                if var_names.contains(&controller) && asked.insert(controller.clone()) {
+                    let value = prompt(&controller, var_map.get(&controller).map(String::as_str))?;
+                    var_map.insert(controller, value);
+                }
+            }
+            if asked.contains(&var_name) || !is_required(&var_name, var_map) {
+                continue;
+            }
+            let value = prompt(&var_name, var_map.get(&var_name).map(String::as_str))?;
+            var_map.insert(var_name.clone(), value);
+            asked.insert(var_name);
+        }
+    }
+    Ok(())
+}
+
+/// The variable named by a variable's `required_if` condition, if it has one
+fn controlling_variable(
+    manifest: &TemplateManifest,
+    processor: &TemplateProcessor,
+    var_name: &str,
+) -> Option<String> {
+    let condition = manifest.variables.get(var_name)?.required_if.as_deref()?;
+    Some(condition_variable(processor, condition))
+}
+
+/// The variable a condition refers to, given either as a template token or as a
+/// plain variable name
+fn condition_variable(processor: &TemplateProcessor, condition: &str) -> String {
+    let condition = condition.trim();
+    let mut tokens = processor.extract_variables(condition).into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(token), None) => token,
+        _ => condition.to_case(Case::Kebab),
+    }
+}
+
+/// Split a conditional directory's condition into its variable and the value it is
+/// compared with, if any: `scf-os == windows` is true when `os` is `windows`,
+/// `scf-os != windows` when it is anything else
+fn split_comparison(condition: &str) -> (&str, Option<(bool, &str)>) {
+    for (operator, equal) in [("!=", false), ("==", true)] {
+        if let Some((variable, value)) = condition.split_once(operator) {
+            return (variable, Some((equal, value.trim())));
+        }
+    }
+    (condition, None)
+}
+
+/// The value a condition sees for a variable: its own value, else the pseudo-variable's,
+/// else for `env-<name>` the environment variable's
+fn condition_value(
+    variable: &str,
+    variables: &HashMap<String, String>,
+    pseudo_values: &HashMap<String, String>,
+) -> Option<String> {
+    variables
+        .get(variable)
+        .or_else(|| pseudo_values.get(variable))
+        .cloned()
+        .or_else(|| pseudo::env_value(variable))
+}
+
+/// Whether a conditional directory's condition holds: its variable is true, or it
+/// compares as asked (ignoring case)
+fn condition_holds(
+    processor: &TemplateProcessor,
+    condition: &str,
+    variables: &HashMap<String, String>,
+    pseudo_values: &HashMap<String, String>,
+) -> bool {
+    let (variable, comparison) = split_comparison(condition);
+    let variable = condition_variable(processor, variable);
+    let value = condition_value(&variable, variables, pseudo_values);
+    match comparison {
+        None => value.is_some_and(|value| manifest::is_truthy(&value)),
+        Some((equal, expected)) => {
+            value.is_some_and(|value| value.trim().eq_ignore_ascii_case(expected)) == equal
+        }
+    }
+}
+
+/// Relative paths of the manifest's conditional directories
+fn conditional_dir_paths(manifest: &TemplateManifest) -> impl Iterator<Item = PathBuf> {
+    manifest
+        .conditional_dirs
+        .keys()
+        .map(|dir| PathBuf::from(dir.trim_end_matches('/')))
+}
+
+/// Conditional directories left out of generation because their condition is false
+fn inactive_dirs(
+    manifest: &TemplateManifest,
+    processor: &TemplateProcessor,
+    variables: &HashMap<String, String>,
+    pseudo_values: &HashMap<String, String>,
+) -> Vec<PathBuf> {
+    manifest
+        .conditional_dirs
+        .iter()
+        .filter(|(_, condition)| !condition_holds(processor, condition, variables, pseudo_values))
+        .map(|(dir, _)| PathBuf::from(dir.trim_end_matches('/')))
+        .collect()
+}
+
+/// Walk all entries of a template, leaving out `.git` metadata unless the manifest
+/// asks for it, the manifest's readme, and the subtrees of `skipped_dirs` (relative to
+/// the template root)
+fn template_entries(
+    template_path: &Path,
+    manifest: &TemplateManifest,
+    skipped_dirs: &[PathBuf],
+) -> impl Iterator<Item = walkdir::DirEntry> {
+    let include_vcs = manifest.include_vcs;
+    let readme = manifest.readme.as_ref().map(PathBuf::from);
+    WalkDir::new(template_path)
+        .into_iter()
+        .filter_entry(move |e| {
+            let skipped = e.path().strip_prefix(template_path).is_ok_and(|rel| {
+                skipped_dirs.iter().any(|dir| rel == dir) || readme.as_deref() == Some(rel)
+            });
+            !skipped && (include_vcs || e.depth() == 0 || e.file_name() != ".git")
+        })
+        .filter_map(|e| e.ok())
+}
+
+/// Variables used in the paths and contents of template entries, as far as the
+/// processor substitutes them
+fn extract_entry_variables(
+    processor: &TemplateProcessor,
+    template_path: &Path,
+    rules: &FileRules,
+    entries: impl Iterator<Item = walkdir::DirEntry>,
+) -> HashSet<String> {
+    let mut variables = HashSet::new();
+
+    for entry in entries {
+        let path = entry.path();
+        let rel_path = path.strip_prefix(template_path).unwrap_or(path);
+
+        let mut skipped = BTreeSet::new();
+
+        // Extract variables from file path, up to the name of a file that keeps it
+        let named_path = match path.parent() {
+            Some(parent) if entry.file_type().is_file() && rules.keeps_name(rel_path) => parent,
+            _ => path,
+        };
+        if let Some(path_str) = named_path
+            .to_str()
+            .filter(|_| processor.substitutes_names())
+        {
+            variables.extend(processor.extract_variables_reporting(path_str, |token| {
+                skipped.insert(token.to_string());
+            }));
+        }
+
+        // Extract variables from file contents; the manifest and variables files
+        // aren't generated, and their globs (e.g. `verbatim = ["scf-raw.txt"]`) name
+        // files that keep their tokens, so they would ask for variables nothing uses
+        if entry.file_type().is_file()
+            && processor.substitutes_content()
+            && !rules.is_copied(rel_path)
+            && !manifest::is_manifest_file(rel_path)
+        {
+            if let Ok(content) = fs::read_to_string(path) {
+                let content = rules.substituted_part(rel_path, &content);
+                variables.extend(processor.extract_variables_reporting(content, |token| {
+                    skipped.insert(token.to_string());
+                }));
+            }
+        }
+
+        for token in skipped {
+            eprintln!(
+                "Warning: {token} in {} looks like a variable but no value can be substituted for it; ignoring it",
+                path.display()
+            );
+        }
+    }
+
+    variables
+}
+
+/// The manifest's globs for template files that are substituted only in part,
+/// matched against paths relative to the template root
+pub struct FileRules {
+    frontmatter_only: globset::GlobSet,
+    static_files: globset::GlobSet,
+    verbatim: globset::GlobSet,
+}
+
+impl FileRules {
+    pub fn new(manifest: &TemplateManifest) -> Result<Self> {
+        Ok(Self {
+            frontmatter_only: utils::build_globset(&manifest.frontmatter_only)
+                .context("Invalid frontmatter_only in scaffer.toml")?,
+            static_files: utils::build_globset(&manifest.static_files)
+                .context("Invalid static in scaffer.toml")?,
+            verbatim: utils::build_globset(&manifest.verbatim)
+                .context("Invalid verbatim in scaffer.toml")?,
+        })
+    }
+
+    /// Whether a file's contents are copied as they are, without being scanned: it
+    /// matches `static` or `verbatim`
+    pub fn is_copied(&self, rel_path: &Path) -> bool {
+        let rel_path = utils::glob_path(&rel_path.to_string_lossy());
+        self.static_files.is_match(&rel_path) || self.verbatim.is_match(&rel_path)
+    }
+
+    /// Whether a file keeps its name as in the template: it matches `verbatim`
+    pub fn keeps_name(&self, rel_path: &Path) -> bool {
+        self.verbatim
+            .is_match(utils::glob_path(&rel_path.to_string_lossy()))
+    }
+
+    /// Whether only the frontmatter of a file is substituted
+    fn is_frontmatter_only(&self, rel_path: &Path) -> bool {
+        self.frontmatter_only
+            .is_match(utils::glob_path(&rel_path.to_string_lossy()))
+    }
+
+    /// The part of a template file's text that variables are substituted in: nothing
+    /// for copied files, only the frontmatter of a `frontmatter_only` file (nothing
+    /// when it has none), else the whole text
+    pub fn substituted_part<'a>(&self, rel_path: &Path, text: &'a str) -> &'a str {
+        if self.is_copied(rel_path) {
+            return "";
+        }
+        if !self.is_frontmatter_only(rel_path) {
+            return text;
+        }
+        template::split_frontmatter(text).map_or("", |(frontmatter, _)| frontmatter)
+    }
+}
+
+/// Whether a template file is generated, as opposed to being template support
+/// (scaffer_init.py, the manifest and variables files, or a partial pulled in by an
+/// include)
+fn is_output_file(src_path: &Path, rel_path: &Path, partials: &HashSet<PathBuf>) -> bool {
+    src_path.file_name() != Some(std::ffi::OsStr::new("scaffer_init.py"))
+        && !manifest::is_manifest_file(rel_path)
+        && !rel_path.starts_with(template::PARTIALS_DIR)
+        && !partials.contains(src_path)
+}
+
+/// Append the change writing a file's generated content would make to the patch
+///
+/// Files whose content would not change, and binary files, which a text patch
+/// can't carry, are skipped.
+fn add_to_patch(
+    patch: &mut String,
+    content: Vec<u8>,
+    processed_rel_path: &str,
+    dest_path: &Path,
+) -> Result<FileOutcome> {
+    let existing = if dest_path.exists() {
+        Some(
+            fs::read(dest_path)
+                .with_context(|| format!("Failed to read file: {}", dest_path.display()))?,
+        )
+    } else {
+        None
+    };
+
+    let path = utils::glob_path(processed_rel_path);
+    let (Ok(new), Ok(old)) = (
+        String::from_utf8(content),
+        existing.map(String::from_utf8).transpose(),
+    ) else {
+        eprintln!("Binary file left out of the patch: {path}");
+        return Ok(FileOutcome::Skipped);
+    };
+
+    let diff = patch::file_diff(&path, old.as_deref(), &new);
+    if diff.is_empty() {
+        return Ok(FileOutcome::Skipped);
+    }
+    patch.push_str(&diff);
+
+    Ok(if old.is_some() {
+        FileOutcome::Overwritten
+    } else {
+        FileOutcome::Created
+    })
+}
+
+/// Ask whether to overwrite an existing file, showing the diff of the change on request
+fn confirm_overwrite(
+    processor: &TemplateProcessor,
+    manifest: &TemplateManifest,
+    rules: &FileRules,
+    src_path: &Path,
+    template_path: &Path,
+    processed_rel_path: &str,
+    dest_path: &Path,
+) -> Result<bool> {
+    const OVERWRITE: usize = 0;
+    const SKIP: usize = 1;
+    let items = ["Overwrite", "Skip", "View diff"];
+
+    loop {
+        let selection = Select::new()
+            .with_prompt(format!("File '{processed_rel_path}' already exists"))
+            .items(&items)
+            .default(SKIP)
+            .interact()?;
+
+        match selection {
+            OVERWRITE => return Ok(true),
+            SKIP => return Ok(false),
+            _ => {
+                let content = render_file(processor, manifest, rules, src_path, template_path)?;
+                let existing = fs::read(dest_path)
+                    .with_context(|| format!("Failed to read file: {}", dest_path.display()))?;
+                let path = utils::glob_path(processed_rel_path);
+                match (String::from_utf8(existing), String::from_utf8(content)) {
+                    (Ok(old), Ok(new)) => {
+                        let diff = patch::file_diff(&path, Some(&old), &new);
+                        if diff.is_empty() {
+                            println!("No differences in {path}");
+                        } else {
+                            print!("{diff}");
+                        }
+                    }
+                    _ => println!("Binary file {path} differs"),
+                }
+            }
+        }
+    }
+}
+
+/// Write processed files to stdout: a single file as it is, several files each under
+/// a `==> path <==` header
+fn print_files(files: &[(String, Vec<u8>)]) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    if let [(_, content)] = files {
+        stdout.write_all(content)?;
+        return Ok(stdout.flush()?);
+    }
+
+    for (index, (path, content)) in files.iter().enumerate() {
+        if index > 0 {
+            writeln!(stdout)?;
+        }
+        writeln!(stdout, "==> {path} <==")?;
+        stdout.write_all(content)?;
+        if !content.is_empty() && !content.ends_with(b"\n") {
+            writeln!(stdout)?;
+        }
+    }
+    Ok(stdout.flush()?)
+}
+
+/// Move the written files of a run from the staging directory into the output directory
+fn commit_staged(staging: &Path, output_dir: &Path, report: &GenerationReport) -> Result<()> {
+    for action in &report.actions {
+        if matches!(
+            action.outcome,
+            FileOutcome::Skipped | FileOutcome::Unchanged
+        ) {
+            continue;
+        }
+
+        let dest_path = utils::long_path(&output_dir.join(&action.destination));
+        if let Some(parent) = dest_path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("Failed to create parent directory: {}", parent.display())
+            })?;
+        }
+        utils::move_file(
+            &utils::long_path(&staging.join(&action.destination)),
+            &dest_path,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Compute the output path of a template file relative to the output directory,
+/// honoring the manifest's `renames` and keeping the names of `verbatim` files
+fn destination_path(
+    processor: &TemplateProcessor,
+    manifest: &TemplateManifest,
+    rules: &FileRules,
+    rel_path: &Path,
+) -> String {
+    let glob_path = utils::glob_path(&rel_path.to_string_lossy());
+    if let Some(renamed) = manifest.renames.get(&glob_path) {
+        return processor.process_path(renamed);
+    }
+    match glob_path.rsplit_once('/') {
+        Some((parent, name)) if rules.keeps_name(rel_path) => {
+            format!("{}/{name}", processor.process_path(parent))
+        }
+        None if rules.keeps_name(rel_path) => glob_path,
+        _ => processor.process_path(&glob_path),
+    }
+}
+
+/// The directory the manifest's `root_as` names for the template's contents, substituted
+fn output_root(processor: &TemplateProcessor, manifest: &TemplateManifest) -> Option<String> {
+    let root = processor.process_path(manifest.root_as.as_deref()?.trim_matches('/'));
+    (!root.is_empty()).then_some(root)
+}
+
+/// The list variable of an each-directory, named `{{#each scf-entities}}`, whose
+/// contents are generated once per comma-separated item of the variable's value
+fn each_list(processor: &TemplateProcessor, dir_name: &std::ffi::OsStr) -> Option<String> {
+    let list = dir_name
+        .to_str()?
+        .strip_prefix("{{#each")?
+        .strip_suffix("}}")?;
+    if !list.starts_with(char::is_whitespace) || list.trim().is_empty() {
+        return None;
+    }
+    Some(condition_variable(processor, list))
+}
+
+/// The list variables of all each-directories in a template
+fn each_dir_lists(
+    template_path: &Path,
+    manifest: &TemplateManifest,
+    processor: &TemplateProcessor,
+) -> HashSet<String> {
+    template_entries(template_path, manifest, &[])
+        .filter(|entry| entry.file_type().is_dir())
+        .filter_map(|entry| each_list(processor, entry.file_name()))
+        .collect()
+}
+
+/// Whether a variable is bound by an each-directory over one of `lists`: `item` and
+/// `seq`, or `<list>-item` and `<list>-seq` for a specific list
+fn is_item_variable(var: &str, lists: &HashSet<String>) -> bool {
+    !lists.is_empty()
+        && (var == "item"
+            || var == "seq"
+            || var
+                .strip_suffix("-item")
+                .or_else(|| var.strip_suffix("-seq"))
+                .is_some_and(|list| lists.contains(list)))
+}
+
+/// Every output of a template file: the processor to render it with and its destination
+///
+/// Inside each-directories there is one output per combination of the items of their
+/// lists. Each-directories leave no directory behind; below one, `item` is bound to
+/// its item (so the innermost one wins in the file itself) and `<list>-item` to the
+/// item of that list, and likewise `seq` and `<list>-seq` to the item's number,
+/// counting from 1. Elsewhere there is just the file itself.
+fn file_outputs<'a>(
+    processor: &'a TemplateProcessor,
+    manifest: &TemplateManifest,
+    rules: &FileRules,
+    rel_path: &Path,
+    strip: usize,
+) -> Result<Vec<(Cow<'a, TemplateProcessor>, String)>> {
+    let lists: Vec<String> = rel_path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .filter_map(|component| each_list(processor, component.as_os_str()))
+        .collect();
+    let root = output_root(processor, manifest);
+    let place = |destination: String| {
+        let Some(stripped) = strip_components(&destination, strip) else {
+            bail!(
+                "Stripping {strip} path component(s) from {destination} leaves no path (--strip-components)"
+            );
+        };
+        let placed = match &root {
+            Some(root) => format!("{root}/{stripped}"),
+            None => stripped,
+        };
+        // Neither the manifest nor variable values may lead out of the output directory
+        if !template::is_contained_path(&placed) {
+            bail!("Destination {placed} is outside the output directory");
+        }
+        Ok(placed)
+    };
+    if lists.is_empty() {
+        return Ok(vec![(
+            Cow::Borrowed(processor),
+            place(destination_path(processor, manifest, rules, rel_path))?,
+        )]);
+    }
+
+    // One numbered item per list, outermost first
+    let mut combinations: Vec<Vec<(usize, &str)>> = vec![Vec::new()];
+    for list in &lists {
+        let items: Vec<&str> = processor
+            .variable(list)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect();
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                items.iter().enumerate().map(move |(index, item)| {
+                    let mut combination = combination.clone();
+                    combination.push((index + 1, *item));
+                    combination
+                })
+            })
+            .collect();
+    }
+
+    let renamed = manifest
+        .renames
+        .get(&utils::glob_path(&rel_path.to_string_lossy()));
+    combinations
+        .into_iter()
+        .map(|combination| {
+            // Each path component is substituted with the items bound above it
+            let mut bound = processor.clone();
+            let mut items = lists.iter().zip(combination);
+            let mut components = Vec::new();
+            let file_name = rel_path.file_name().unwrap_or_default();
+            for component in rel_path.components() {
+                if component.as_os_str() == file_name && rules.keeps_name(rel_path) {
+                    components.push(file_name.to_string_lossy().to_string());
+                } else if each_list(processor, component.as_os_str()).is_some() {
+                    if let Some((list, (seq, item))) = items.next() {
+                        bound.set_variable(format!("{list}-item"), item.to_string());
+                        bound.set_variable("item".to_string(), item.to_string());
+                        bound.set_variable(format!("{list}-seq"), seq.to_string());
+                        bound.set_variable("seq".to_string(), seq.to_string());
+                    }
+                } else {
+                    components.push(bound.process_path(&component.as_os_str().to_string_lossy()));
+                }
+            }
+
+            let destination = match renamed {
+                Some(renamed) => bound.process_path(renamed),
+                None => components.join("/"),
+            };
+            Ok((Cow::Owned(bound), place(destination)?))
+        })
+        .collect()
+}
+
+/// Drop the first `count` components of a destination path, like tar's
+/// `--strip-components`; `None` when no component is left
+fn strip_components(destination: &str, count: usize) -> Option<String> {
+    if count == 0 {
+        return Some(destination.to_string());
+    }
+    let rest: Vec<&str> = destination
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty())
+        .skip(count)
+        .collect();
+    (!rest.is_empty()).then(|| rest.join("/"))
+}
+
+/// Fail if two template files would be written to the same destination after
+/// substitution, returning the pairs of destinations that differ only in letter case
+fn check_destination_collisions(
+    template_path: &Path,
+    processor: &TemplateProcessor,
+    manifest: &TemplateManifest,
+    rules: &FileRules,
+    partials: &HashSet<PathBuf>,
+    skipped_dirs: &[PathBuf],
+    strip: usize,
+) -> Result<Vec<(String, String)>> {
+    let mut destinations: HashMap<String, PathBuf> = HashMap::new();
+    let mut folded: HashMap<String, String> = HashMap::new();
+    let mut case_collisions = Vec::new();
+
+    for entry in
+        template_entries(template_path, manifest, skipped_dirs).filter(|e| e.file_type().is_file())
+    {
+        let src_path = entry.path();
+        let rel_path = src_path
+            .strip_prefix(template_path)
+            .context("Failed to calculate relative path")?;
+        if !is_output_file(src_path, rel_path, partials) {
+            continue;
+        }
+
+        for (_, dest) in file_outputs(processor, manifest, rules, rel_path, strip)? {
+            match destinations.insert(dest.clone(), src_path.to_path_buf()) {
+                Some(previous) if previous == src_path => bail!(
+                    "Template file {} would be written to {dest} for several items (its path should use scf-item)",
+                    src_path.display()
+                ),
+                Some(previous) => bail!(
+                    "Template files {} and {} would both be written to {dest}",
+                    previous.display(),
+                    src_path.display()
+                ),
+                None => {}
+            }
+            match folded.entry(dest.to_lowercase()) {
+                Entry::Occupied(entry) => case_collisions.push((entry.get().clone(), dest)),
+                Entry::Vacant(entry) => {
+                    entry.insert(dest);
+                }
+            }
+        }
+    }
+
+    Ok(case_collisions)
+}
+
+/// Read a template file and produce its output content
+///
+/// Text files have includes expanded and variables substituted. Binary files
+/// (anything that is not valid UTF-8 or contains NUL bytes), and every file when
+/// contents aren't substituted, are copied byte for byte, and so are files matching the
+/// manifest's `static` or `verbatim`. Files matching its `frontmatter_only` are
+/// substituted only in their frontmatter.
+/// Text files with one of the manifest's `header_extensions` get its `header` on top.
+fn render_file(
+    processor: &TemplateProcessor,
+    manifest: &TemplateManifest,
+    rules: &FileRules,
+    src_path: &Path,
+    template_path: &Path,
+) -> Result<Vec<u8>> {
+    let bytes = fs::read(src_path)
+        .with_context(|| format!("Failed to read template file: {}", src_path.display()))?;
+
+    let rel_path = src_path.strip_prefix(template_path).unwrap_or(src_path);
+    if bytes.contains(&0) || !processor.substitutes_content() || rules.is_copied(rel_path) {
+        return Ok(bytes);
+    }
+    let content = match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(err) => return Ok(err.into_bytes()),
+    };
+
+    // A verbatim file is copied as it is, includes and all, without its directive line
+    if let Some(body) = template::verbatim_body(&content) {
+        return Ok(body.as_bytes().to_vec());
+    }
+
+    let substitute = |text: &str| -> Result<String> {
+        let text = processor
+            .expand_includes(text, template_path)
+            .with_context(|| format!("Failed to resolve includes in: {}", src_path.display()))?;
+        Ok(processor.process_text(&text))
+    };
+    let content = if rules.is_frontmatter_only(rel_path) {
+        // The body is copied as it is, e.g. for docs with literal examples
+        match template::split_frontmatter(&content) {
+            Some((frontmatter, body)) => substitute(frontmatter)? + body,
+            None => content,
+        }
+    } else {
+        substitute(&content)?
+    };
+
+    let extension = src_path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    if let (Some(header), Some(extension)) = (&manifest.header, extension) {
+        let has_header = manifest
+            .header_extensions
+            .iter()
+            .any(|ext| ext.trim_start_matches('.').eq_ignore_ascii_case(&extension));
+        if has_header {
+            let header = processor.process_text(header);
+            return Ok(header::prepend(&content, &header, &extension)
+                .with_context(|| format!("Failed to add header to: {}", src_path.display()))?
+                .into_bytes());
+        }
+    }
+
+    Ok(content.into_bytes())
+}
+
+/// A notice for a template in which no variables were found, which usually means its
+/// tokens use another prefix; `None` when there are variables or none are expected
+fn no_variables_notice(
+    wanted_vars: &HashSet<String>,
+    manifest: &TemplateManifest,
+    processor: &TemplateProcessor,
+) -> Option<String> {
+    if !wanted_vars.is_empty() || manifest.no_variables || !manifest.optional.is_empty() {
+        return None;
+    }
+    let example = processor.token_forms("name").into_iter().next()?;
+    Some(format!(
+        "Note: no template variables found, copying verbatim; is the prefix correct? \
+         (expected tokens like {example}, set no_variables = true in {} to silence this)",
+        manifest::MANIFEST_FILE
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InternalCase;
+
+    /// Generate `template` into `output` with the default config, without prompts,
+    /// history or the scan cache
+    fn generate_quiet(
+        template: &Path,
+        output: &Path,
+        vars: &[(&str, &str)],
+    ) -> Result<GenerationReport> {
+        generate_quiet_with(
+            &ScafferConfig::default(),
+            template,
+            output,
+            vars,
+            GenerateOptions::default(),
+        )
+    }
+
+    /// Like [`generate_quiet`], with a config and the options to start from
+    fn generate_quiet_with(
+        config: &ScafferConfig,
+        template: &Path,
+        output: &Path,
+        vars: &[(&str, &str)],
+        options: GenerateOptions,
+    ) -> Result<GenerationReport> {
+        let generator = TemplateGenerator {
+            config: config.clone(),
+        };
+        let options = GenerateOptions {
+            verbosity: Verbosity::Quiet,
+            output_dir: Some(output.to_path_buf()),
+            no_input: true,
+            no_history: true,
+            no_scan_cache: true,
+            ..options
+        };
+        let variables = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        generator.generate_with_variables(
+            Some(template.to_string_lossy().to_string()),
+            variables,
+            &options,
+        )
+    }
+
+    #[test]
+    fn test_binary_file_keeps_content_but_renames() {
+        let dir = tempfile::tempdir().unwrap();
+        let logo = dir.path().join("scf-name-logo.png");
+        let bytes = b"\x89PNG\r\n\x1a\n\0\0ScfName\xff\xfe".to_vec();
+        fs::write(&logo, &bytes).unwrap();
+
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "acme".to_string());
+
+        assert_eq!(
+            processor.process_path("scf-name-logo.png"),
+            "scf-acme-logo.png"
+        );
+        assert_eq!(
+            render_file(
+                &processor,
+                &TemplateManifest::default(),
+                &FileRules::new(&TemplateManifest::default()).unwrap(),
+                &logo,
+                dir.path()
+            )
+            .unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn test_binary_files_are_generated_verbatim() {
+        let template = tempfile::tempdir().unwrap();
+        let png = b"\x89PNG\r\n\x1a\n\0\0ScfOther\xff\xfe".to_vec();
+        let latin1 = b"caf\xe9 ScfOther".to_vec();
+        fs::write(template.path().join("ScfName.png"), &png).unwrap();
+        fs::write(template.path().join("ScfName.txt"), &latin1).unwrap();
+
+        // Tokens in binary content are no variables to ask for
+        let output = tempfile::tempdir().unwrap();
+        generate_quiet(template.path(), output.path(), &[("name", "acme")]).unwrap();
+
+        let files = Vec::from_iter(crate::golden::relative_files(output.path()).unwrap());
+        assert_eq!(
+            files,
+            vec![PathBuf::from("ScfAcme.png"), PathBuf::from("ScfAcme.txt")]
+        );
+        assert_eq!(fs::read(output.path().join("ScfAcme.png")).unwrap(), png);
+        assert_eq!(fs::read(output.path().join("ScfAcme.txt")).unwrap(), latin1);
+    }
+
+    #[test]
+    fn test_destination_collision_is_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("ScfName.rs"), "a").unwrap();
+        fs::write(dir.path().join("ScfOther.rs"), "b").unwrap();
+
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "foo".to_string());
+        processor.set_variable("other".to_string(), "bar".to_string());
+        let manifest = TemplateManifest::default();
+        let rules = FileRules::new(&manifest).unwrap();
+        let check = |processor: &TemplateProcessor| {
+            check_destination_collisions(
+                dir.path(),
+                processor,
+                &manifest,
+                &rules,
+                &HashSet::new(),
+                &[],
+                0,
+            )
+        };
+        assert!(check(&processor).is_ok());
+
+        processor.set_variable("other".to_string(), "foo".to_string());
+        let err = check(&processor).unwrap_err().to_string();
+        assert!(err.contains("ScfName.rs"));
+        assert!(err.contains("ScfOther.rs"));
+        assert!(err.contains("ScfFoo.rs"));
+    }
+
+    #[test]
+    fn test_case_only_collision() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(template.path().join("ScfName.ts"), "a").unwrap();
+        fs::write(template.path().join("scfname.ts"), "b").unwrap();
+        fs::write(template.path().join("scf-name.ts"), "c").unwrap();
+
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "app".to_string());
+        let manifest = TemplateManifest::default();
+        let mut collisions = check_destination_collisions(
+            template.path(),
+            &processor,
+            &manifest,
+            &FileRules::new(&manifest).unwrap(),
+            &HashSet::new(),
+            &[],
+            0,
+        )
+        .unwrap();
+        collisions.iter_mut().for_each(|pair| {
+            if pair.0 > pair.1 {
+                std::mem::swap(&mut pair.0, &mut pair.1);
+            }
+        });
+        assert_eq!(
+            collisions,
+            [("ScfApp.ts".to_string(), "scfapp.ts".to_string())]
+        );
+
+        // Generation warns, or fails with --strict before writing anything
+        let generate = |strict: bool| {
+            let output = tempfile::tempdir().unwrap();
+            let result = generate_quiet_with(
+                &ScafferConfig::default(),
+                template.path(),
+                output.path(),
+                &[("name", "app")],
+                GenerateOptions {
+                    strict,
+                    ..Default::default()
+                },
+            );
+            (result, fs::read_dir(output.path()).unwrap().count())
+        };
+        let (result, _) = generate(false);
+        assert!(result.is_ok());
+        let (result, written) = generate(true);
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("differ only in letter case")
+        );
+        assert_eq!(written, 0);
+    }
+
+    #[test]
+    fn test_directories_without_output_are_not_created() {
+        let template = tempfile::tempdir().unwrap();
+        fs::create_dir_all(template.path().join("partials")).unwrap();
+        fs::create_dir_all(template.path().join("src/ScfName")).unwrap();
+        fs::write(template.path().join("partials/header.txt"), "// header\n").unwrap();
+        fs::write(
+            template.path().join("src/ScfName/mod.rs"),
+            "{{include \"partials/header.txt\"}}struct ScfName;",
+        )
+        .unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        let report = generate_quiet(template.path(), output.path(), &[("name", "acme")]).unwrap();
+
+        assert_eq!(report.written(), 1);
+        assert!(output.path().join("src/ScfAcme/mod.rs").is_file());
+        assert!(!output.path().join("partials").exists());
+    }
+
+    #[test]
+    fn test_renames_change_destination() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[renames]\n\"ci.yml\" = \".github/workflows/scf-name.yml\"\n",
+        )
+        .unwrap();
+        fs::write(template.path().join("ci.yml"), "name: scf-name").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        generate_quiet(template.path(), output.path(), &[("name", "build")]).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(output.path().join(".github/workflows/scf-build.yml")).unwrap(),
+            "name: scf-build"
+        );
+        assert!(!output.path().join("ci.yml").exists());
+    }
+
+    #[test]
+    fn test_git_metadata_is_not_generated() {
+        let template = tempfile::tempdir().unwrap();
+        fs::create_dir_all(template.path().join(".git/refs")).unwrap();
+        fs::write(
+            template.path().join(".git/HEAD"),
+            "ref: refs/heads/scf-name",
+        )
+        .unwrap();
+        fs::write(template.path().join("README.md"), "# ScfName").unwrap();
+
+        let manifest = TemplateManifest::default();
+        let generator = TemplateGenerator {
+            config: ScafferConfig::default(),
+        };
+        let options = GenerateOptions::default();
+        let variables = generator
+            .scan_template_variables(template.path(), &manifest, &options)
+            .unwrap();
+        assert_eq!(variables, HashSet::from(["name".to_string()]));
+
+        let generate = |include_vcs: bool| {
+            fs::write(
+                template.path().join(manifest::MANIFEST_FILE),
+                format!("include_vcs = {include_vcs}\n"),
+            )
+            .unwrap();
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet(template.path(), output.path(), &[("name", "demo")]).unwrap();
+            output
+        };
+
+        let output = generate(false);
+        assert!(output.path().join("README.md").exists());
+        assert!(!output.path().join(".git").exists());
+
+        let output = generate(true);
+        assert_eq!(
+            fs::read_to_string(output.path().join(".git/HEAD")).unwrap(),
+            "ref: refs/heads/scf-demo"
+        );
+    }
+
+    #[test]
+    fn test_required_if_condition() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[variables.db-password]\nrequired_if = \"scf-use-db\"\n",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("config.txt"),
+            "db=scf-use-db password=scf-db-password",
+        )
+        .unwrap();
+
+        let generate = |variables: &[(&str, &str)]| {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet(template.path(), output.path(), variables)
+                .map(|_| fs::read_to_string(output.path().join("config.txt")).unwrap())
+        };
+
+        // Condition not met: the variable is not needed
+        assert_eq!(
+            generate(&[("use-db", "no")]).unwrap(),
+            "db=scf-no password=scf-db-password"
+        );
+
+        // Condition met: the variable is required
+        let err = generate(&[("use-db", "yes")]).unwrap_err();
+        assert!(err.to_string().contains("'db-password'"));
+        assert_eq!(
+            generate(&[("use-db", "yes"), ("db-password", "secret")]).unwrap(),
+            "db=scf-yes password=scf-secret"
+        );
+    }
+
+    #[test]
+    fn test_conditional_dirs() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[conditional_dirs]\ndocker = \"scf-use-docker\"\n",
+        )
+        .unwrap();
+        fs::write(template.path().join("README.md"), "scf-name").unwrap();
+        fs::create_dir(template.path().join("docker")).unwrap();
+        fs::write(
+            template.path().join("docker/Dockerfile"),
+            "FROM scf-base-image",
+        )
+        .unwrap();
+
+        let generate = |variables: &[(&str, &str)]| {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet(template.path(), output.path(), variables)
+                .map(|_| fs::read_to_string(output.path().join("docker/Dockerfile")).ok())
+        };
+
+        // The condition variable is required even though no file uses it
+        let err = generate(&[("name", "app")]).unwrap_err();
+        assert!(err.to_string().contains("'use-docker'"));
+
+        // Condition false: the directory and its variables are skipped
+        assert_eq!(
+            generate(&[("name", "app"), ("use-docker", "no")]).unwrap(),
+            None
+        );
+
+        // Condition true: the directory's variables are required
+        let err = generate(&[("name", "app"), ("use-docker", "yes")]).unwrap_err();
+        assert!(err.to_string().contains("'base-image'"));
+        assert_eq!(
+            generate(&[
+                ("name", "app"),
+                ("use-docker", "yes"),
+                ("base-image", "alpine")
+            ])
+            .unwrap()
+            .as_deref(),
+            Some("FROM scf-alpine")
+        );
+    }
+
+    #[test]
+    fn test_conditional_files_per_os() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[conditional_dirs]\n\
+             \"build.bat\" = \"scf-os == windows\"\n\
+             \"build.sh\" = \"scf-os != windows\"\n",
+        )
+        .unwrap();
+        fs::write(template.path().join("build.bat"), "@echo off").unwrap();
+        fs::write(template.path().join("build.sh"), "#!/bin/sh").unwrap();
+
+        let generate = |variables: &[(&str, &str)]| {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet(template.path(), output.path(), variables).unwrap();
+            Vec::from_iter(crate::golden::relative_files(output.path()).unwrap())
+        };
+
+        // The host OS decides by default, and can be overridden like any variable
+        let host = if cfg!(windows) {
+            "build.bat"
+        } else {
+            "build.sh"
+        };
+        assert_eq!(generate(&[]), vec![PathBuf::from(host)]);
+        assert_eq!(
+            generate(&[("os", "Windows")]),
+            vec![PathBuf::from("build.bat")]
+        );
+        assert_eq!(
+            generate(&[("os", "macos")]),
+            vec![PathBuf::from("build.sh")]
+        );
+    }
+
+    #[test]
+    fn test_environment_only_decides_conditions() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[conditional_dirs]\n\
+             \"path.txt\" = \"scf-env-path\"\n\
+             \"unset.txt\" = \"scf-env-scaffer-test-unset\"\n",
+        )
+        .unwrap();
+        fs::write(template.path().join("path.txt"), "scf-name").unwrap();
+        fs::write(template.path().join("unset.txt"), "scf-name").unwrap();
+
+        // Set environment variables are true; the values appear nowhere
+        let output = tempfile::tempdir().unwrap();
+        let log = output.path().join("runs.jsonl");
+        let report = generate_quiet_with(
+            &ScafferConfig::default(),
+            template.path(),
+            output.path(),
+            &[("name", "app")],
+            GenerateOptions {
+                log_file: Some(log.clone()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(report.written(), 1);
+        assert!(output.path().join("path.txt").is_file());
+        assert!(!output.path().join("unset.txt").exists());
+        assert_eq!(report.variables.len(), 1);
+        assert!(!fs::read_to_string(&log).unwrap().contains("env-path"));
+
+        // In files, such a token is an ordinary variable
+        fs::write(template.path().join("path.txt"), "scf-env-path").unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let err = generate_quiet(template.path(), output.path(), &[])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("env-path"), "{err}");
+    }
+
+    #[test]
+    fn test_stdout_writes_no_files() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(template.path().join("ScfName.txt"), "hello ScfName").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        let generate = |vars: &[(&str, &str)]| {
+            generate_quiet_with(
+                &ScafferConfig::default(),
+                template.path(),
+                output.path(),
+                vars,
+                GenerateOptions {
+                    stdout: true,
+                    ..Default::default()
+                },
+            )
+        };
+        let report = generate(&[("name", "app")]).unwrap();
+
+        assert_eq!(report.actions[0].destination, "ScfApp.txt");
+        assert_eq!(fs::read_dir(output.path()).unwrap().count(), 0);
+
+        // Missing variables can't be asked for
+        let err = generate(&[]).unwrap_err();
+        assert!(err.to_string().contains("'name'"));
+    }
+
+    #[test]
+    fn test_to_zip_writes_an_archive() {
+        let template = tempfile::tempdir().unwrap();
+        fs::create_dir(template.path().join("src")).unwrap();
+        fs::write(template.path().join("src/ScfName.rs"), "struct ScfName;").unwrap();
+        fs::write(template.path().join("README.md"), "# scf-name").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        let archive = output.path().join("app.zip");
+        let generate = |dry_run: bool| {
+            generate_quiet_with(
+                &ScafferConfig::default(),
+                template.path(),
+                output.path(),
+                &[("name", "app")],
+                GenerateOptions {
+                    dry_run,
+                    to_zip: Some(archive.clone()),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        // A dry run lists the entries without writing the archive
+        assert_eq!(generate(true).written(), 2);
+        assert!(!archive.exists());
+
+        let report = generate(false);
+        assert_eq!(report.bytes_written, fs::metadata(&archive).unwrap().len());
+        let mut zip = zip::ZipArchive::new(fs::File::open(&archive).unwrap()).unwrap();
+        let mut names: Vec<String> = zip.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(names, vec!["README.md", "src/ScfApp.rs"]);
+
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut zip.by_name("src/ScfApp.rs").unwrap(), &mut content)
+            .unwrap();
+        assert_eq!(content, "struct ScfApp;");
+        // Nothing but the archive is written
+        assert_eq!(fs::read_dir(output.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_required_and_optional_vars() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "required = [\"author\"]\noptional = [\"suffix\"]\n",
+        )
+        .unwrap();
+        fs::write(template.path().join("header.txt"), "by scf-author").unwrap();
+        fs::write(
+            template.path().join("ScfName.txt"),
+            "{{include \"header.txt\"}} ScfName ScfSuffix",
+        )
+        .unwrap();
+
+        let generate = |vars: &[(&str, &str)]| {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet(template.path(), output.path(), vars)
+                .map(|_| fs::read_to_string(output.path().join("ScfApp.txt")).unwrap())
+        };
+
+        // `author` is forced even though only the partial uses it
+        let err = generate(&[("name", "app")]).unwrap_err();
+        assert!(err.to_string().contains("'author'"));
+
+        // `suffix` is never asked for, and substituted only when given
+        assert_eq!(
+            generate(&[("name", "app"), ("author", "me")]).unwrap(),
+            "by scf-me ScfApp ScfSuffix"
+        );
+        assert_eq!(
+            generate(&[("name", "app"), ("author", "me"), ("suffix", "v2")]).unwrap(),
+            "by scf-me ScfApp ScfV2"
+        );
+    }
+
+    #[test]
+    fn test_ask_variables_offers_given_values() {
+        let manifest: TemplateManifest =
+            toml::from_str("[variables.db-name]\nrequired_if = \"use-db\"\n").unwrap();
+        let generator = TemplateGenerator {
+            config: ScafferConfig::default(),
+        };
+        let processor = generator.processor(&manifest).unwrap();
+        let controller_of = |var_name: &str| controlling_variable(&manifest, &processor, var_name);
+        let is_required = |var_name: &str, var_map: &HashMap<String, String>| {
+            controller_of(var_name)
+                .is_none_or(|controller| var_map.get(&controller).is_some_and(|v| v == "yes"))
+        };
+
+        let var_names: Vec<String> = ["db-name", "name", "port", "use-db"]
+            .map(String::from)
+            .to_vec();
+        let mut var_map = HashMap::from([
+            ("name".to_string(), "app".to_string()),
+            ("use-db".to_string(), "no".to_string()),
+        ]);
+        let mut asked = Vec::new();
+        ask_variables(
+            &var_names,
+            &mut var_map,
+            &manifest,
+            &controller_of,
+            &is_required,
+            &mut |var_name, current| {
+                asked.push((var_name.to_string(), current.map(str::to_string)));
+                Ok(if var_name == "use-db" {
+                    "yes".to_string()
+                } else {
+                    format!("new-{var_name}")
+                })
+            },
+        )
+        .unwrap();
+
+        // Given values are offered as defaults; the controller is asked first and
+        // its new answer decides whether the controlled variable is asked
+        assert_eq!(
+            asked,
+            vec![
+                ("use-db".to_string(), Some("no".to_string())),
+                ("db-name".to_string(), None),
+                ("name".to_string(), Some("app".to_string())),
+                ("port".to_string(), None),
+            ]
+        );
+        assert_eq!(var_map["name"], "new-name");
+        assert_eq!(var_map["db-name"], "new-db-name");
+    }
+
+    #[test]
+    fn test_variables_given_in_any_case_unify() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(template.path().join("app.txt"), "ScfMyVar scf_my_var").unwrap();
+
+        let generate = |internal_case: Option<InternalCase>, name: &str| {
+            let config = ScafferConfig {
+                internal_case,
+                ..Default::default()
+            };
+            let output = tempfile::tempdir().unwrap();
+            let report = generate_quiet_with(
+                &config,
+                template.path(),
+                output.path(),
+                &[(name, "hello-world")],
+                GenerateOptions::default(),
+            )?;
+            assert_eq!(
+                fs::read_to_string(output.path().join("app.txt")).unwrap(),
+                "ScfHelloWorld scf_hello_world"
+            );
+            Ok::<_, anyhow::Error>(report.variables.into_keys().collect::<Vec<_>>())
+        };
+
+        for name in ["my_var", "my-var", "MyVar"] {
+            assert_eq!(generate(None, name).unwrap(), vec!["my-var"]);
+            assert_eq!(
+                generate(Some(InternalCase::Snake), name).unwrap(),
+                vec!["my_var"]
+            );
+        }
+
+        // Diagnostics name variables in the internal case too
+        let config = ScafferConfig {
+            internal_case: Some(InternalCase::Snake),
+            ..Default::default()
+        };
+        let output = tempfile::tempdir().unwrap();
+        let err = generate_quiet_with(
+            &config,
+            template.path(),
+            output.path(),
+            &[],
+            GenerateOptions {
+                dry_run: true,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("'my_var'"));
+    }
+
+    #[test]
+    fn test_template_chain() {
+        let templates = tempfile::tempdir().unwrap();
+        for (name, after) in [("service", Some("tests")), ("tests", None)] {
+            let dir = templates.path().join(name);
+            fs::create_dir(&dir).unwrap();
+            fs::write(dir.join(format!("{name}-scf-name.txt")), "ScfName").unwrap();
+            if let Some(after) = after {
+                fs::write(
+                    dir.join(manifest::MANIFEST_FILE),
+                    format!("after = \"../{after}\"\n"),
+                )
+                .unwrap();
+            }
+        }
+
+        let generate = |no_chain: bool| {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet_with(
+                &ScafferConfig::default(),
+                &templates.path().join("service"),
+                output.path(),
+                &[("name", "user")],
+                GenerateOptions {
+                    no_chain,
+                    ..Default::default()
+                },
+            )
+            .map(|report| {
+                let files = Vec::from_iter(crate::golden::relative_files(output.path()).unwrap());
+                (report, files)
+            })
+        };
+
+        // The chained template shares the variables and the output directory
+        let (report, files) = generate(false).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("service-scf-user.txt"),
+                PathBuf::from("tests-scf-user.txt")
+            ]
+        );
+        assert_eq!(report.chain.len(), 2);
+        assert_eq!(
+            report.chain[1],
+            templates
+                .path()
+                .join("service/../tests")
+                .display()
+                .to_string()
+        );
+        assert_eq!(report.actions.len(), 2);
+
+        let (report, files) = generate(true).unwrap();
+        assert_eq!(files, vec![PathBuf::from("service-scf-user.txt")]);
+        assert!(report.chain.is_empty());
+
+        // A template chained back to an earlier one is a cycle
+        fs::write(
+            templates.path().join("tests").join(manifest::MANIFEST_FILE),
+            "after = \"../service\"\n",
+        )
+        .unwrap();
+        let err = generate(false).unwrap_err();
+        assert!(format!("{err:#}").contains("has a cycle"));
+    }
+
+    #[test]
+    fn test_chain_passes_only_given_variables() {
+        let templates = tempfile::tempdir().unwrap();
+        for (name, manifest) in [
+            (
+                "first",
+                "after = \"../second\"\n[variables.kind]\ndefault = \"api\"\n",
+            ),
+            ("second", "[variables.kind]\ndefault = \"test\"\n"),
+        ] {
+            let dir = templates.path().join(name);
+            fs::create_dir(&dir).unwrap();
+            fs::write(dir.join(manifest::MANIFEST_FILE), manifest).unwrap();
+            fs::write(
+                dir.join(format!("{name}.txt")),
+                "scf-name scf-kind scf-uuid",
+            )
+            .unwrap();
+        }
+
+        let output = tempfile::tempdir().unwrap();
+        generate_quiet(
+            &templates.path().join("first"),
+            output.path(),
+            &[("name", "user")],
+        )
+        .unwrap();
+
+        // The given name is passed on, while the chained template uses its own default
+        // and draws its own pseudo-variable values
+        let read = |name: &str| fs::read_to_string(output.path().join(name)).unwrap();
+        let (first, second) = (read("first.txt"), read("second.txt"));
+        let first_uuid = first.strip_prefix("scf-user scf-api scf-").unwrap();
+        let second_uuid = second.strip_prefix("scf-user scf-test scf-").unwrap();
+        assert_ne!(first_uuid, second_uuid);
+    }
+
+    #[test]
+    fn test_chain_skips_files_written_alike() {
+        let templates = tempfile::tempdir().unwrap();
+        for (name, shared) in [("first", "mod ScfName;"), ("second", "mod ScfName;")] {
+            let dir = templates.path().join(name);
+            fs::create_dir(&dir).unwrap();
+            fs::write(dir.join("lib.rs"), shared).unwrap();
+            fs::write(dir.join(format!("{name}.rs")), "").unwrap();
+        }
+        fs::write(
+            templates.path().join("first").join(manifest::MANIFEST_FILE),
+            "after = \"../second\"\n",
+        )
+        .unwrap();
+
+        let generate = || {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet(
+                &templates.path().join("first"),
+                output.path(),
+                &[("name", "user")],
+            )
+        };
+
+        // Without a prompt, an existing file would be an error
+        let report = generate().unwrap();
+        assert_eq!(report.count(FileOutcome::Created), 3);
+        assert_eq!(report.count(FileOutcome::Unchanged), 1);
+
+        // Only exactly the same content is skipped
+        fs::write(templates.path().join("second/lib.rs"), "mod ScfName; ").unwrap();
+        let err = generate().unwrap_err();
+        assert!(format!("{err:#}").contains("'lib.rs' already exists"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[modes]\n\"scripts/*.sh\" = \"0755\"\n\"*.key\" = \"600\"\n",
+        )
+        .unwrap();
+        fs::create_dir(template.path().join("scripts")).unwrap();
+        fs::write(template.path().join("scripts/scf-name.sh"), "#!/bin/sh").unwrap();
+        fs::write(template.path().join("app.key"), "").unwrap();
+        fs::write(template.path().join("README.md"), "").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        generate_quiet(template.path(), output.path(), &[("name", "build")]).unwrap();
+
+        let mode = |path: &str| {
+            fs::metadata(output.path().join(path))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o7777
+        };
+        assert_eq!(mode("scripts/scf-build.sh"), 0o755);
+        assert_eq!(mode("app.key"), 0o600);
+        assert_ne!(mode("README.md"), 0o755);
+
+        // A mode that isn't octal is an error
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[modes]\n\"*.sh\" = \"rwx\"\n",
+        )
+        .unwrap();
+        let err = generate_quiet_with(
+            &ScafferConfig::default(),
+            template.path(),
+            output.path(),
+            &[("name", "build")],
+            GenerateOptions {
+                force: true,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Invalid mode 'rwx'"));
+    }
+
+    #[test]
+    fn test_shared_partials() {
+        let template = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        fs::create_dir(template.path().join(template::PARTIALS_DIR)).unwrap();
+        fs::write(
+            template
+                .path()
+                .join(template::PARTIALS_DIR)
+                .join("header.txt"),
+            "// ScfName\n",
+        )
+        .unwrap();
+        fs::write(shared.path().join("license.txt"), "// (c) ScfAuthor\n").unwrap();
+        fs::write(
+            template.path().join("main.rs"),
+            "{{include \"license.txt\"}}{{include \"header.txt\"}}fn main() {}",
+        )
+        .unwrap();
+
+        let config = ScafferConfig {
+            partials_dir: Some(shared.path().display().to_string()),
+            ..Default::default()
+        };
+        let generate = |vars: &[(&str, &str)]| {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet_with(
+                &config,
+                template.path(),
+                output.path(),
+                vars,
+                GenerateOptions::default(),
+            )
+            .map(|_| {
+                let files = Vec::from_iter(crate::golden::relative_files(output.path()).unwrap());
+                (
+                    files,
+                    fs::read_to_string(output.path().join("main.rs")).unwrap(),
+                )
+            })
+        };
+
+        // The shared partial's variables are asked for too
+        let err = generate(&[("name", "app")]).unwrap_err();
+        assert!(err.to_string().contains("'author'"));
+
+        let (files, main) = generate(&[("name", "app"), ("author", "acme")]).unwrap();
+        assert_eq!(files, vec![PathBuf::from("main.rs")]);
+        assert_eq!(main, "// (c) ScfAcme\n// ScfApp\nfn main() {}");
+    }
+
+    #[test]
+    fn test_scan_cache_tracks_shared_partials() {
+        let template = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        fs::write(shared.path().join("license.txt"), "// (c) acme\n").unwrap();
+        fs::write(
+            template.path().join("main.rs"),
+            "{{include \"license.txt\"}}// ScfName\n",
+        )
+        .unwrap();
+
+        let generator = TemplateGenerator {
+            config: ScafferConfig {
+                partials_dir: Some(shared.path().display().to_string()),
+                ..Default::default()
+            },
+        };
+        let generate = || {
+            let output = tempfile::tempdir().unwrap();
+            let options = GenerateOptions {
+                verbosity: Verbosity::Quiet,
+                output_dir: Some(output.path().to_path_buf()),
+                no_input: true,
+                no_history: true,
+                ..Default::default()
+            };
+            generator.generate_with_variables(
+                Some(template.path().to_string_lossy().to_string()),
+                HashMap::from([("name".to_string(), "app".to_string())]),
+                &options,
+            )
+        };
+
+        generate().unwrap();
+        // The cached scan of the template is stale once a shared partial it includes
+        // gets a variable
+        fs::write(shared.path().join("license.txt"), "// (c) ScfAuthor\n").unwrap();
+        let err = generate().unwrap_err();
+        assert!(err.to_string().contains("'author'"), "{err}");
+    }
+
+    #[test]
+    fn test_readme_is_not_generated() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "readme = \"USAGE.md\"\n",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("USAGE.md"),
+            "Give scf-name and scf-port",
+        )
+        .unwrap();
+        fs::write(template.path().join("scf-name.txt"), "ScfName").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        // `port` is only mentioned in the readme, so it isn't asked for
+        generate_quiet(template.path(), output.path(), &[("name", "app")]).unwrap();
+
+        assert_eq!(
+            Vec::from_iter(crate::golden::relative_files(output.path()).unwrap()),
+            vec![PathBuf::from("scf-app.txt")]
+        );
+    }
+
+    #[test]
+    fn test_no_variables_notice() {
+        let generator = TemplateGenerator {
+            config: ScafferConfig::default(),
+        };
+        let mut manifest = TemplateManifest::default();
+        let processor = generator.processor(&manifest).unwrap();
+
+        let notice = no_variables_notice(&HashSet::new(), &manifest, &processor).unwrap();
+        assert!(notice.contains("no template variables found"));
+        assert!(notice.contains("ScfName"));
+
+        let found = HashSet::from(["name".to_string()]);
+        assert!(no_variables_notice(&found, &manifest, &processor).is_none());
+
+        manifest.no_variables = true;
+        assert!(no_variables_notice(&HashSet::new(), &manifest, &processor).is_none());
+    }
+
+    #[test]
+    fn test_ignore_vars() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "ignore_vars = [\"g\", \"legacy-*\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("config.txt"),
+            "scfg=1 scf-legacy-id ScfName",
+        )
+        .unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        // Only `name` is asked for, and an ignored variable isn't substituted even
+        // when given
+        generate_quiet(
+            template.path(),
+            output.path(),
+            &[("name", "app"), ("legacy-id", "7")],
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(output.path().join("config.txt")).unwrap(),
+            "scfg=1 scf-legacy-id ScfApp"
+        );
+    }
+
+    #[test]
+    fn test_each_dirs() {
+        let template = tempfile::tempdir().unwrap();
+        let models = template.path().join("models/{{#each scf-entities}}");
+        fs::create_dir_all(&models).unwrap();
+        fs::write(models.join("scf_item.rs"), "struct ScfItem; // ScfName").unwrap();
+        let endpoints = template
+            .path()
+            .join("{{#each scf-services}}/scf-item/{{#each scf-endpoints}}");
+        fs::create_dir_all(&endpoints).unwrap();
+        fs::write(endpoints.join("scf-item.txt"), "scf-services-item scf-item").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        // `item` is bound by the each-directories, so it isn't asked for
+        let report = generate_quiet(
+            template.path(),
+            output.path(),
+            &[
+                ("name", "shop"),
+                ("entities", "user, order"),
+                ("services", "api,web"),
+                ("endpoints", "get,put"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(report.count(FileOutcome::Created), 6);
+        assert_eq!(
+            fs::read_to_string(output.path().join("models/scf_order.rs")).unwrap(),
+            "struct ScfOrder; // ScfShop"
+        );
+        assert!(output.path().join("models/scf_user.rs").is_file());
+        assert_eq!(
+            fs::read_to_string(output.path().join("scf-web/scf-put.txt")).unwrap(),
+            "scf-web scf-put"
+        );
+        assert!(output.path().join("scf-api/scf-get.txt").is_file());
+    }
+
+    #[test]
+    fn test_pseudo_variables_keep_their_case() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(template.path().join("ids.txt"), "scf-uuid\nScfUuid\n").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        let report = generate_quiet_with(
+            &ScafferConfig::default(),
+            template.path(),
+            output.path(),
+            &[],
+            GenerateOptions {
+                seed: Some(7),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(report.missing_vars.is_empty());
+
+        // Both tokens get the same UUID, in the lowercase hex it was drawn in
+        let content = fs::read_to_string(output.path().join("ids.txt")).unwrap();
+        let (kebab, pascal) = content.split_once('\n').unwrap();
+        let uuid = kebab.strip_prefix("scf-").unwrap();
+        assert_eq!(pascal.strip_prefix("Scf").unwrap(), format!("{uuid}\n"));
+        let groups: Vec<usize> = uuid.split('-').map(str::len).collect();
+        assert_eq!(groups, [8, 4, 4, 4, 12]);
+        assert!(u128::from_str_radix(&uuid.replace('-', ""), 16).is_ok());
+        assert_eq!(uuid, uuid.to_lowercase());
+        assert_eq!(&uuid[14..15], "4");
+    }
+
+    #[test]
+    fn test_template_name_and_source_are_kept() {
+        let templates = tempfile::tempdir().unwrap();
+        let template = templates.path().join("api-v2");
+        fs::create_dir(&template).unwrap();
+        fs::write(
+            template.join("NOTICE"),
+            "Generated by scf-template-name from scf-template-source",
+        )
+        .unwrap();
+        let config = ScafferConfig {
+            scaffer: vec![templates.path().display().to_string()],
+            ..Default::default()
+        };
+
+        let output = tempfile::tempdir().unwrap();
+        generate_quiet_with(
+            &config,
+            Path::new("api-v2"),
+            output.path(),
+            &[],
+            GenerateOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(output.path().join("NOTICE")).unwrap(),
+            format!("Generated by scf-api-v2 from scf-{}", template.display())
+        );
+    }
+
+    #[test]
+    fn test_numbered_migrations() {
+        let template = tempfile::tempdir().unwrap();
+        let migrations = template.path().join("migrations/{{#each scf-steps}}");
+        fs::create_dir_all(&migrations).unwrap();
+        fs::write(
+            migrations.join("scf-timestamp-scf-seq-scf-item.sql"),
+            "-- step scf-seq of scf-steps-item",
+        )
+        .unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        let report = generate_quiet_with(
+            &ScafferConfig::default(),
+            template.path(),
+            output.path(),
+            &[("steps", "create_users,add_email")],
+            GenerateOptions {
+                seed: Some(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(report.missing_vars.is_empty());
+
+        // A seed pins the timestamp, unless SOURCE_DATE_EPOCH does
+        let timestamp = PseudoVariables::new(Some(1)).value("timestamp").unwrap();
+        assert_eq!(
+            fs::read_to_string(output.path().join(format!(
+                "migrations/scf-{timestamp}-scf-2-scf-add-email.sql"
+            )))
+            .unwrap(),
+            "-- step scf-2 of scf-add-email"
+        );
+        assert!(
+            output
+                .path()
+                .join(format!(
+                    "migrations/scf-{timestamp}-scf-1-scf-create-users.sql"
+                ))
+                .is_file()
+        );
+    }
+
+    #[test]
+    fn test_into_existing_only_adds_files() {
+        let template = tempfile::tempdir().unwrap();
+        fs::create_dir(template.path().join("src")).unwrap();
+        fs::write(template.path().join("src/scf-name.rs"), "new").unwrap();
+        fs::write(template.path().join("src/lib.rs"), "new").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        fs::create_dir(output.path().join("src")).unwrap();
+        fs::write(output.path().join("src/lib.rs"), "existing").unwrap();
+
+        let report = generate_quiet_with(
+            &ScafferConfig::default(),
+            template.path(),
+            output.path(),
+            &[("name", "feature")],
+            GenerateOptions {
+                into_existing: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(output.path().join("src/lib.rs")).unwrap(),
+            "existing"
+        );
+        assert_eq!(
+            fs::read_to_string(output.path().join("src/scf-feature.rs")).unwrap(),
+            "new"
+        );
+        assert_eq!(report.count(FileOutcome::Skipped), 1);
+        assert_eq!(report.count(FileOutcome::Created), 1);
+    }
+
+    #[test]
+    fn test_wildcard_template_url() {
+        let templates = tempfile::tempdir().unwrap();
+        fs::create_dir(templates.path().join("local")).unwrap();
+        let generator = TemplateGenerator {
+            config: ScafferConfig {
+                scaffer: vec![templates.path().display().to_string()],
+                scaffer_template_urls: Some(HashMap::from([
+                    (
+                        "*".to_string(),
+                        "https://x/{name}/{version}.zip".to_string(),
+                    ),
+                    ("fixed".to_string(), "https://x/{name}.zip".to_string()),
+                ])),
+                ..Default::default()
+            },
+        };
+
+        // Wildcard entries are only used when the configuration opts in
+        assert!(generator.resolve_template_url("widget").unwrap().is_none());
+        let mut generator = generator;
+        generator.config.wildcard_urls = Some(true);
+        let url = generator.resolve_template_url("widget").unwrap().unwrap();
+        assert_eq!(url, "https://x/widget/{version}.zip");
+        assert_eq!(
+            generator.resolve_template_url("fixed").unwrap().unwrap(),
+            "https://x/fixed.zip"
+        );
+        // A template that exists isn't shadowed by the wildcard
+        assert!(generator.resolve_template_url("local").unwrap().is_none());
+
+        let options = GenerateOptions {
+            no_input: true,
+            ..Default::default()
+        };
+        let mut var_map = HashMap::new();
+        let err = generator
+            .expand_url_variables(&url, &var_map, &options)
+            .unwrap_err();
+        assert!(err.to_string().contains("'version'"));
+        var_map.insert("version".to_string(), "2".to_string());
+        assert_eq!(
+            generator
+                .expand_url_variables(&url, &var_map, &options)
+                .unwrap(),
+            "https://x/widget/2.zip"
+        );
+
+        // Placeholders match variables given in any case
+        let url = "https://x/{api_version}.zip";
+        var_map.insert("api-version".to_string(), "3".to_string());
+        assert_eq!(
+            generator
+                .expand_url_variables(url, &var_map, &options)
+                .unwrap(),
+            "https://x/3.zip"
+        );
+
+        // `{name}` is the template name, whatever `name` variable is given
+        var_map.insert("name".to_string(), "other".to_string());
+        let url = generator.resolve_template_url("fixed").unwrap().unwrap();
+        assert_eq!(
+            generator
+                .expand_url_variables(&url, &var_map, &options)
+                .unwrap(),
+            "https://x/fixed.zip"
+        );
+    }
+
+    #[test]
+    fn test_preview_file() {
+        let template = tempfile::tempdir().unwrap();
+        fs::create_dir(template.path().join("src")).unwrap();
+        fs::write(
+            template.path().join("src/scf-name.rs"),
+            "struct ScfName; // ScfAuthor",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("src/scf-kind.rs"),
+            "// ScfKind by ScfLicense, scf-uuid",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[variables.kind]\ndefault = \"model\"\n",
+        )
+        .unwrap();
+        let mut config = ScafferConfig::default();
+        config
+            .global_var_defaults
+            .insert("license".to_string(), "mit".to_string());
+        let generator = TemplateGenerator { config };
+        let template_name = template.path().to_string_lossy().to_string();
+
+        let content = generator
+            .preview_file(
+                &template_name,
+                &["name=user-model".to_string()],
+                "src/scf-name.rs",
+            )
+            .unwrap();
+        assert_eq!(content, b"struct ScfUserModel; // ScfAuthor");
+
+        // Defaults and pseudo-variables are filled in as when generating
+        let content = generator
+            .preview_file(&template_name, &[], "src/scf-kind.rs")
+            .unwrap();
+        let content = String::from_utf8(content).unwrap();
+        let uuid = content.strip_prefix("// ScfModel by ScfMit, scf-").unwrap();
+        assert_eq!(uuid.len(), 36, "{content}");
+        assert!(u128::from_str_radix(&uuid.replace('-', ""), 16).is_ok());
+
+        let err = generator
+            .preview_file(&template_name, &[], "src/missing.rs")
+            .unwrap_err();
+        assert!(err.to_string().contains("'src/missing.rs' is not a file"));
+        assert!(generator.preview_file(&template_name, &[], "src").is_err());
+    }
+
+    #[test]
+    fn test_merge_into_existing_json() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[merge.\"*.json\"]\narrays = \"concat-dedup\"\n",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("tsconfig.json"),
+            r#"{"compilerOptions": {"strict": true}, "include": ["src", "scf-name"]}"#,
+        )
+        .unwrap();
+        let output = tempfile::tempdir().unwrap();
+        fs::write(
+            output.path().join("tsconfig.json"),
+            r#"{"compilerOptions": {"target": "es2022", "strict": false}, "include": ["src"]}"#,
+        )
+        .unwrap();
+
+        // Merged without asking to overwrite
+        let report = generate_quiet(template.path(), output.path(), &[("name", "tests")]).unwrap();
+
+        assert_eq!(report.count(FileOutcome::Overwritten), 1);
+        let merged: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output.path().join("tsconfig.json")).unwrap())
+                .unwrap();
+        assert_eq!(
+            merged,
+            serde_json::json!({
+                "compilerOptions": {"target": "es2022", "strict": true},
+                "include": ["src", "scf-tests"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_resolution_steps() {
+        let templates = tempfile::tempdir().unwrap();
+        fs::create_dir(templates.path().join("service")).unwrap();
+        let generator = TemplateGenerator {
+            config: ScafferConfig {
+                scaffer: vec![
+                    templates.path().display().to_string(),
+                    "/nonexistent/templates".to_string(),
+                ],
+                ..Default::default()
+            },
+        };
+
+        let steps = generator.resolution_steps("service").unwrap();
+        let dir = templates.path().display();
+        assert!(steps.contains(&format!("template directory: {dir}")));
+        assert!(
+            steps.contains(&"template directory: /nonexistent/templates (missing)".to_string())
+        );
+        assert!(
+            steps.iter().any(
+                |step| step.starts_with("'service' is provided by") && step.ends_with("(used)")
+            )
+        );
+        assert_eq!(
+            steps.last().unwrap(),
+            &format!("resolved to {}", templates.path().join("service").display())
+        );
+
+        let steps = generator.resolution_steps("missing").unwrap();
+        assert_eq!(steps.last().unwrap(), "'missing' was not found");
+    }
+
+    #[test]
+    fn test_frontmatter_only() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "frontmatter_only = [\"docs/*.md\"]\n",
+        )
+        .unwrap();
+        fs::create_dir(template.path().join("docs")).unwrap();
+        fs::write(
+            template.path().join("docs/scf-name.md"),
+            "---\ntitle: ScfName\n---\nGenerate with `-v name=...` to replace ScfName and scf-example.\n",
+        )
+        .unwrap();
+        fs::write(template.path().join("docs/plain.md"), "Literal ScfName\n").unwrap();
+        fs::write(template.path().join("README.txt"), "About ScfName\n").unwrap();
+        let output = tempfile::tempdir().unwrap();
+
+        // Variables only in the bodies (`example`) are not asked for
+        let report = generate_quiet(template.path(), output.path(), &[("name", "widget")]).unwrap();
+        assert!(report.missing_vars.is_empty());
+
+        assert_eq!(
+            fs::read_to_string(output.path().join("docs/scf-widget.md")).unwrap(),
+            "---\ntitle: ScfWidget\n---\nGenerate with `-v name=...` to replace ScfName and scf-example.\n"
+        );
+        // Without frontmatter, nothing is substituted
+        assert_eq!(
+            fs::read_to_string(output.path().join("docs/plain.md")).unwrap(),
+            "Literal ScfName\n"
+        );
+        assert_eq!(
+            fs::read_to_string(output.path().join("README.txt")).unwrap(),
+            "About ScfWidget\n"
+        );
+    }
+
+    #[test]
+    fn test_static_and_verbatim_files() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "static = [\"config/*.json\"]\nverbatim = [\"scf-raw.txt\"]\n",
+        )
+        .unwrap();
+        fs::create_dir(template.path().join("config")).unwrap();
+        fs::write(
+            template.path().join("config/scf-name.json"),
+            "{\"port\": \"SCF_PORT\"}",
+        )
+        .unwrap();
+        fs::write(template.path().join("scf-raw.txt"), "ScfName SCF_PORT").unwrap();
+        fs::write(template.path().join("ScfName.rs"), "struct ScfName;").unwrap();
+        let output = tempfile::tempdir().unwrap();
+
+        // `port` is only used in copied files, and `raw` only in a manifest glob, so
+        // neither is asked for
+        let report = generate_quiet(template.path(), output.path(), &[("name", "app")]).unwrap();
+        assert!(report.missing_vars.is_empty());
+
+        // A static file gets its name substituted, a verbatim one doesn't
+        assert_eq!(
+            fs::read_to_string(output.path().join("config/scf-app.json")).unwrap(),
+            "{\"port\": \"SCF_PORT\"}"
+        );
+        assert_eq!(
+            fs::read_to_string(output.path().join("scf-raw.txt")).unwrap(),
+            "ScfName SCF_PORT"
+        );
+        assert_eq!(
+            fs::read_to_string(output.path().join("ScfApp.rs")).unwrap(),
+            "struct ScfApp;"
+        );
+    }
+
+    #[test]
+    fn test_typed_variables_are_validated() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[variables.port]\ntype = \"int\"\nmin = 1\nmax = 65535\n\
+             [variables.db]\ntype = \"enum\"\noptions = [\"postgres\", \"sqlite\"]\n",
+        )
+        .unwrap();
+        fs::write(template.path().join("config.txt"), "scf-db:scf-port").unwrap();
+
+        let generate = |port: &str, db: &str| {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet(
+                template.path(),
+                output.path(),
+                &[("port", port), ("db", db)],
+            )
+            .map(|_| fs::read_to_string(output.path().join("config.txt")).unwrap())
+        };
+
+        assert_eq!(generate("8080", "sqlite").unwrap(), "scf-sqlite:scf-8080");
+        assert_eq!(
+            generate("70000", "sqlite").unwrap_err().to_string(),
+            "Invalid value for variable 'port': 70000 is greater than 65535"
+        );
+        assert_eq!(
+            generate("8080", "mysql").unwrap_err().to_string(),
+            "Invalid value for variable 'db': 'mysql' is not one of postgres, sqlite"
+        );
+    }
+
+    #[test]
+    fn test_root_as() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "root_as = \"packages/scf-name\"\ngitignore_stacks = [\"node\"]\n",
+        )
+        .unwrap();
+        fs::create_dir(template.path().join("src")).unwrap();
+        fs::write(
+            template.path().join("src/index.ts"),
+            "export const ScfName = 1;",
+        )
+        .unwrap();
+        fs::write(template.path().join("package.json"), "{}").unwrap();
+        let output = tempfile::tempdir().unwrap();
+
+        let report = generate_quiet(template.path(), output.path(), &[("name", "ui-kit")]).unwrap();
+
+        let root = output.path().join("packages/scf-ui-kit");
+        assert_eq!(
+            fs::read_to_string(root.join("src/index.ts")).unwrap(),
+            "export const ScfUiKit = 1;"
+        );
+        assert!(root.join("package.json").is_file());
+        assert!(root.join(".gitignore").is_file());
+        assert!(!output.path().join("src").exists());
+        let mut destinations: Vec<&str> = report
+            .actions
+            .iter()
+            .map(|action| action.destination.as_str())
+            .collect();
+        destinations.sort();
+        assert_eq!(
+            destinations,
+            [
+                "packages/scf-ui-kit/.gitignore",
+                "packages/scf-ui-kit/package.json",
+                "packages/scf-ui-kit/src/index.ts"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gitignore_stacks() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "gitignore_stacks = [\"rust\"]\n",
+        )
+        .unwrap();
+        fs::write(template.path().join(".gitignore"), "/scf-name.db\n").unwrap();
+        let output = tempfile::tempdir().unwrap();
+
+        let report = generate_quiet(template.path(), output.path(), &[("name", "app")]).unwrap();
+
+        let gitignore = fs::read_to_string(output.path().join(".gitignore")).unwrap();
+        assert!(gitignore.starts_with("/scf-app.db\n"));
+        assert!(gitignore.contains("\n# Rust\ntarget/\n"));
+        assert_eq!(report.actions.len(), 1);
+        assert_eq!(report.count(FileOutcome::Created), 1);
+    }
+
+    #[test]
+    fn test_require_output() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(template.path().join("scf-name.txt"), "ScfName").unwrap();
+        let output = tempfile::tempdir().unwrap();
+
+        let generate = |dry_run: bool| {
+            generate_quiet_with(
+                &ScafferConfig::default(),
+                template.path(),
+                output.path(),
+                &[("name", "app")],
+                GenerateOptions {
+                    into_existing: true,
+                    require_output: true,
+                    dry_run,
+                    ..Default::default()
+                },
+            )
+        };
+
+        assert!(generate(false).is_ok());
+        // Now the only file exists and is skipped
+        let err = generate(false).unwrap_err();
+        assert!(err.to_string().contains("No files were created"));
+        assert!(generate(true).is_ok());
+    }
+
+    #[test]
+    fn test_names_only_and_content_only() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(template.path().join("ScfName.rs"), "struct ScfName;").unwrap();
+
+        let generate = |names_only: bool, content_only: bool| {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet_with(
+                &ScafferConfig::default(),
+                template.path(),
+                output.path(),
+                &[("name", "widget")],
+                GenerateOptions {
+                    names_only,
+                    content_only,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let entry = fs::read_dir(output.path())
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap();
+            (
+                entry.file_name().to_string_lossy().to_string(),
+                fs::read_to_string(entry.path()).unwrap(),
+            )
+        };
+
+        assert_eq!(
+            generate(false, false),
+            ("ScfWidget.rs".to_string(), "struct ScfWidget;".to_string())
+        );
+        assert_eq!(
+            generate(true, false),
+            ("ScfWidget.rs".to_string(), "struct ScfName;".to_string())
+        );
+        assert_eq!(
+            generate(false, true),
+            ("ScfName.rs".to_string(), "struct ScfWidget;".to_string())
+        );
+    }
+
+    #[test]
+    fn test_temp_dir_option() {
+        let base = tempfile::tempdir().unwrap();
+        let dir = base.path().join("downloads");
+        let generator = TemplateGenerator {
+            config: ScafferConfig::default(),
+        };
+        let options = GenerateOptions {
+            temp_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+
+        let temp_dir = generator.temp_dir(&options).unwrap();
+        let path = temp_dir.path().to_path_buf();
+        assert_eq!(path.parent(), Some(dir.as_path()));
+        assert!(path.is_dir());
+
+        drop(temp_dir);
+        assert!(!path.exists());
+        assert!(dir.is_dir());
+    }
+
+    #[test]
+    fn test_single_file_template() {
+        let templates = tempfile::tempdir().unwrap();
+        let template = templates.path().join("ScfName.template.ts");
+        fs::write(&template, "export class ScfName {}").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        let report = generate_quiet(&template, output.path(), &[("name", "widget")]).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(output.path().join("ScfWidget.template.ts")).unwrap(),
+            "export class ScfWidget {}"
+        );
+        assert_eq!(report.count(FileOutcome::Created), 1);
+        assert_eq!(fs::read_dir(output.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_atomic_generation_is_all_or_nothing() {
+        let template = tempfile::tempdir().unwrap();
+        fs::create_dir(template.path().join("src")).unwrap();
+        fs::write(template.path().join("src/scf-name.rs"), "ScfName").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        let generate = || {
+            generate_quiet_with(
+                &ScafferConfig::default(),
+                template.path(),
+                output.path(),
+                &[("name", "demo")],
+                GenerateOptions {
+                    atomic: true,
+                    ..Default::default()
+                },
+            )
+        };
+
+        generate().unwrap();
+        assert_eq!(
+            fs::read_to_string(output.path().join("src/scf-demo.rs")).unwrap(),
+            "ScfDemo"
+        );
+        // Only the generated tree is left behind, not the staging directory
+        assert_eq!(fs::read_dir(output.path()).unwrap().count(), 1);
+
+        // A failure anywhere in the run leaves the output directory untouched
+        fs::remove_dir_all(output.path().join("src")).unwrap();
+        fs::write(
+            template.path().join("broken.txt"),
+            "{{include \"missing.txt\"}}",
+        )
+        .unwrap();
+        assert!(generate().is_err());
+        assert_eq!(fs::read_dir(output.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_auto_overwrite_skips_prompt() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "auto_overwrite = [\"*.generated.ts\"]\n",
+        )
+        .unwrap();
+        fs::write(template.path().join("api.generated.ts"), "new").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        fs::write(output.path().join("api.generated.ts"), "old").unwrap();
+
+        // With no_input, an ordinary existing file would be an error
+        let report = generate_quiet(template.path(), output.path(), &[]).unwrap();
+
+        assert_eq!(report.count(FileOutcome::AutoOverwritten), 1);
+        assert_eq!(report.bytes_written, 3);
+        assert_eq!(
+            fs::read_to_string(output.path().join("api.generated.ts")).unwrap(),
+            "new"
+        );
+
+        // Generating again writes the file again
+        let report = generate_quiet(template.path(), output.path(), &[]).unwrap();
+        assert_eq!(report.count(FileOutcome::AutoOverwritten), 1);
+        assert_eq!(report.bytes_written, 3);
+    }
+
+    #[test]
+    fn test_text_file_is_substituted() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("main.rs");
+        fs::write(&src, "struct ScfName;").unwrap();
+
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "acme".to_string());
+
+        assert_eq!(
+            render_file(
+                &processor,
+                &TemplateManifest::default(),
+                &FileRules::new(&TemplateManifest::default()).unwrap(),
+                &src,
+                dir.path()
+            )
+            .unwrap(),
+            b"struct ScfAcme;"
+        );
+    }
+
+    #[test]
+    fn test_header_is_added_to_matching_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "struct ScfName;").unwrap();
+        fs::write(dir.path().join("notes.txt"), "ScfName").unwrap();
+
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "acme".to_string());
+        let manifest = TemplateManifest {
+            header: Some("Copyright ScfName".to_string()),
+            header_extensions: vec!["rs".to_string()],
+            ..Default::default()
+        };
+        let rules = FileRules::new(&manifest).unwrap();
+
+        assert_eq!(
+            render_file(
+                &processor,
+                &manifest,
+                &rules,
+                &dir.path().join("main.rs"),
+                dir.path()
+            )
+            .unwrap(),
+            b"// Copyright ScfAcme\n\nstruct ScfAcme;"
+        );
+        assert_eq!(
+            render_file(
+                &processor,
+                &manifest,
+                &rules,
+                &dir.path().join("notes.txt"),
+                dir.path()
+            )
+            .unwrap(),
+            b"ScfAcme"
+        );
+    }
+
+    #[test]
+    fn test_lockfile() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(template.path().join("scf-name.txt"), "ScfName").unwrap();
+        let template_name = template.path().to_string_lossy().to_string();
+        let output = tempfile::tempdir().unwrap();
+
+        let generate = |lock: bool, locked: bool| {
+            generate_quiet_with(
+                &ScafferConfig::default(),
+                template.path(),
+                output.path(),
+                &[("name", "app")],
+                GenerateOptions {
+                    force: true,
+                    lock,
+                    locked,
+                    ..Default::default()
+                },
+            )
+        };
+
+        // Nothing to check against yet
+        let err = generate(false, true).unwrap_err().to_string();
+        assert!(err.contains("No scaffer.lock"), "{err}");
+        generate(false, false).unwrap();
+        assert!(!output.path().join(LOCK_FILE).exists());
+
+        generate(true, false).unwrap();
+        let lockfile = Lockfile::load(output.path()).unwrap().unwrap();
+        assert_eq!(
+            lockfile.templates[&template_name],
+            LockedTemplate::new(&template_name, None)
+        );
+        generate(false, true).unwrap();
+
+        // A template that no longer matches its locked archive fails
+        let mut tampered = lockfile.clone();
+        tampered.templates.get_mut(&template_name).unwrap().sha256 = Some("abc123".to_string());
+        tampered.save(output.path()).unwrap();
+        let err = generate(false, true).unwrap_err().to_string();
+        assert!(err.contains("does not match scaffer.lock"), "{err}");
+
+        // An existing lockfile is kept up to date without --lock
+        generate(false, false).unwrap();
+        assert_eq!(Lockfile::load(output.path()).unwrap().unwrap(), lockfile);
+    }
+
+    #[test]
+    fn test_strip_components() {
+        assert_eq!(
+            strip_components("a/b/c.txt", 0).as_deref(),
+            Some("a/b/c.txt")
+        );
+        assert_eq!(strip_components("a/b/c.txt", 1).as_deref(), Some("b/c.txt"));
+        assert_eq!(strip_components("a/b/c.txt", 3), None);
+
+        let template = tempfile::tempdir().unwrap();
+        let wrapper = template.path().join("repo-main");
+        fs::create_dir_all(wrapper.join("src")).unwrap();
+        fs::write(wrapper.join("src/scf-name.rs"), "ScfName").unwrap();
+        fs::write(wrapper.join("README.md"), "# scf-name").unwrap();
+        let output = tempfile::tempdir().unwrap();
+
+        let generate = |strip_components: usize| {
+            generate_quiet_with(
+                &ScafferConfig::default(),
+                template.path(),
+                output.path(),
+                &[("name", "app")],
+                GenerateOptions {
+                    strip_components,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let report = generate(1).unwrap();
+        let mut destinations: Vec<&str> = report
+            .actions
+            .iter()
+            .map(|action| action.destination.as_str())
+            .collect();
+        destinations.sort();
+        assert_eq!(destinations, ["README.md", "src/scf-app.rs"]);
+        assert_eq!(
+            fs::read_to_string(output.path().join("src/scf-app.rs")).unwrap(),
+            "ScfApp"
+        );
+        assert!(!output.path().join("repo-main").exists());
+
+        // README.md would be left without a path, so nothing is written
+        let output_files = fs::read_dir(output.path()).unwrap().count();
+        let err = generate(2).unwrap_err().to_string();
+        assert!(err.contains("leaves no path"), "{err}");
+        assert_eq!(fs::read_dir(output.path()).unwrap().count(), output_files);
+    }
+
+    #[test]
+    fn test_global_var_defaults() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "[variables.license]\ndefault = \"MIT\"\n",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("NOTICE"),
+            "scf-name by scf-author, scf-license",
+        )
+        .unwrap();
+
+        let config = ScafferConfig {
+            global_var_defaults: HashMap::from([
+                ("Author".to_string(), "Ada".to_string()),
+                ("license".to_string(), "GPL".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let generate = |variables: &[(&str, &str)]| {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet_with(
+                &config,
+                template.path(),
+                output.path(),
+                variables,
+                GenerateOptions::default(),
+            )
+            .unwrap();
+            fs::read_to_string(output.path().join("NOTICE")).unwrap()
+        };
+
+        // The manifest's default wins over the global one, which fills the rest
+        assert_eq!(generate(&[("name", "app")]), "scf-app by scf-ada, scf-mit");
+        assert_eq!(
+            generate(&[("name", "app"), ("author", "Bob"), ("license", "BSD")]),
+            "scf-app by scf-bob, scf-bsd"
+        );
+    }
+
+    #[test]
+    fn test_marked_regions_are_updated() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "update_regions = [\"*.ts\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("README.md"),
+            "Mark regions with scaffer:begin <name>\n",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("routes.ts"),
+            "// scaffer:begin routes\nexport const ScfName = \"/scf-name\";\n// scaffer:end routes\n",
+        )
+        .unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let routes = output.path().join("routes.ts");
+        let existing =
+            "import x from \"y\";\n// scaffer:begin routes\nold\n// scaffer:end routes\nmine();\n";
+        fs::write(&routes, existing).unwrap();
+
+        let generate = |force: bool| {
+            generate_quiet_with(
+                &ScafferConfig::default(),
+                template.path(),
+                output.path(),
+                &[("name", "users")],
+                GenerateOptions {
+                    force,
+                    ..Default::default()
+                },
+            )
+        };
+
+        // Only the region is replaced, without asking to overwrite the file
+        let report = generate(false).unwrap();
+        assert_eq!(report.count(FileOutcome::Overwritten), 1);
+        assert_eq!(
+            fs::read_to_string(&routes).unwrap(),
+            "import x from \"y\";\n// scaffer:begin routes\nexport const ScfUsers = \"/scf-users\";\n// scaffer:end routes\nmine();\n"
+        );
+
+        // Without markers, or with broken ones, the file is an existing file as usual
+        for existing in ["mine();\n", "mine();\n// scaffer:begin routes\n"] {
+            fs::write(&routes, existing).unwrap();
+            let err = generate(false).unwrap_err().to_string();
+            assert!(err.contains("already exists"), "{err}");
+            assert_eq!(fs::read_to_string(&routes).unwrap(), existing);
+        }
+
+        // --force overwrites files as a whole, and files outside update_regions are
+        // never read for markers
+        fs::write(&routes, existing).unwrap();
+        fs::write(output.path().join("README.md"), "// scaffer:begin x\n").unwrap();
+        let report = generate(true).unwrap();
+        assert_eq!(report.count(FileOutcome::Overwritten), 2);
+        assert_eq!(
+            fs::read_to_string(&routes).unwrap(),
+            "// scaffer:begin routes\nexport const ScfUsers = \"/scf-users\";\n// scaffer:end routes\n"
+        );
+    }
+
+    #[test]
+    fn test_output_encoding() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "output_encoding = \"utf8-bom\"\n",
+        )
+        .unwrap();
+        fs::write(template.path().join("run.cmd"), "echo scf-name").unwrap();
+        fs::write(template.path().join("logo.bin"), b"\x00scf-name").unwrap();
+
+        let generate = |output_encoding: Option<OutputEncoding>| {
+            let output = tempfile::tempdir().unwrap();
+            generate_quiet_with(
+                &ScafferConfig::default(),
+                template.path(),
+                output.path(),
+                &[("name", "app")],
+                GenerateOptions {
+                    output_encoding,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let read = |name: &str| fs::read(output.path().join(name)).unwrap();
+            (read("run.cmd"), read("logo.bin"))
+        };
+
+        // The manifest's encoding, overridden by the option; binary files are copied
+        let (text, binary) = generate(None);
+        assert_eq!(text, b"\xef\xbb\xbfecho scf-app");
+        assert_eq!(binary, b"\x00scf-name");
+        assert_eq!(generate(Some(OutputEncoding::Utf8)).0, b"echo scf-app");
+        let (text, binary) = generate(Some(OutputEncoding::Utf16le));
+        assert_eq!(&text[..6], b"\xff\xfee\x00c\x00");
+        assert_eq!(text.len(), 2 + 2 * "echo scf-app".len());
+        assert_eq!(binary, b"\x00scf-name");
+    }
+
+    #[test]
+    fn test_merge_into_encoded_file() {
+        let template = tempfile::tempdir().unwrap();
+        fs::write(
+            template.path().join(manifest::MANIFEST_FILE),
+            "output_encoding = \"utf16le\"\nupdate_regions = [\"run.cmd\"]\n\n[merge.\"settings.json\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("settings.json"),
+            r#"{"name": "scf-name"}"#,
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("run.cmd"),
+            "rem scaffer:begin app\necho scf-name\nrem scaffer:end app\n",
+        )
+        .unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        let utf16 = |text: &str| OutputEncoding::Utf16le.encode(text.as_bytes().to_vec());
+        fs::write(
+            output.path().join("settings.json"),
+            utf16(r#"{"port": 80}"#),
+        )
+        .unwrap();
+        fs::write(
+            output.path().join("run.cmd"),
+            utf16("cd app\nrem scaffer:begin app\nrem scaffer:end app\n"),
+        )
+        .unwrap();
+
+        // Existing files written in the output encoding are decoded to merge into them
+        let report = generate_quiet(template.path(), output.path(), &[("name", "app")]).unwrap();
+        assert_eq!(report.count(FileOutcome::Overwritten), 2);
+        let read = |name: &str| fs::read(output.path().join(name)).unwrap();
+        assert_eq!(
+            read("settings.json"),
+            utf16("{\n  \"port\": 80,\n  \"name\": \"scf-app\"\n}\n")
+        );
+        assert_eq!(
+            read("run.cmd"),
+            utf16("cd app\nrem scaffer:begin app\necho scf-app\nrem scaffer:end app\n")
+        );
     }
 }
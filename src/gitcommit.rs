@@ -0,0 +1,136 @@
+use crate::generator::{FileOutcome, GenerationReport};
+
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Stage the files a run wrote into `output_dir` and commit them, with `message` or,
+/// when it is empty, `scaffold <template>`
+///
+/// Other changes in the repository are left out of the commit. When the output
+/// directory is not in a git work tree, or git can't be run, this only warns.
+pub fn commit_generated(report: &GenerationReport, output_dir: &Path, message: &str) -> Result<()> {
+    let files: Vec<&str> = report
+        .actions
+        .iter()
+        .filter(|action| {
+            !matches!(
+                action.outcome,
+                FileOutcome::Skipped | FileOutcome::Unchanged
+            )
+        })
+        .map(|action| action.destination.as_str())
+        .collect();
+    if files.is_empty() {
+        println!("No files were written, so nothing was committed");
+        return Ok(());
+    }
+
+    match git(output_dir, &["rev-parse", "--is-inside-work-tree"]) {
+        Ok(output) if output.status.success() => {}
+        Ok(_) => {
+            eprintln!(
+                "Warning: {} is not in a git repository; nothing was committed",
+                output_dir.display()
+            );
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("Warning: could not run git ({err}); nothing was committed");
+            return Ok(());
+        }
+    }
+
+    let message = if message.trim().is_empty() {
+        format!(
+            "scaffold {}",
+            report.template.as_deref().unwrap_or("template")
+        )
+    } else {
+        message.to_string()
+    };
+
+    let mut add = vec!["add", "--"];
+    add.extend(&files);
+    run(output_dir, &add)?;
+    let mut commit = vec!["commit", "--quiet", "-m", &message, "--"];
+    commit.extend(&files);
+    run(output_dir, &commit)?;
+
+    println!("Committed {} file(s): {message}", files.len());
+    Ok(())
+}
+
+fn git(dir: &Path, args: &[&str]) -> std::io::Result<Output> {
+    Command::new("git").arg("-C").arg(dir).args(args).output()
+}
+
+/// Run a git command that must succeed, failing with what git printed otherwise
+fn run(dir: &Path, args: &[&str]) -> Result<()> {
+    let output = git(dir, args).with_context(|| format!("Failed to run git {}", args[0]))?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::FileAction;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn action(destination: &str, outcome: FileOutcome) -> FileAction {
+        FileAction {
+            source: PathBuf::from(destination),
+            destination: destination.to_string(),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn test_commit_generated() {
+        let repo = tempfile::tempdir().unwrap();
+        for args in [
+            &["init", "--quiet"][..],
+            &["config", "user.name", "Test"],
+            &["config", "user.email", "test@example.com"],
+        ] {
+            run(repo.path(), args).unwrap();
+        }
+        fs::create_dir(repo.path().join("src")).unwrap();
+        fs::write(repo.path().join("src/app.rs"), "fn main() {}").unwrap();
+        fs::write(repo.path().join("old.txt"), "kept").unwrap();
+        fs::write(repo.path().join("unrelated.txt"), "not generated").unwrap();
+
+        let report = GenerationReport {
+            template: Some("rust-app".to_string()),
+            actions: vec![
+                action("src/app.rs", FileOutcome::Created),
+                action("old.txt", FileOutcome::Skipped),
+            ],
+            ..Default::default()
+        };
+        commit_generated(&report, repo.path(), "").unwrap();
+
+        let log = git(repo.path(), &["log", "--format=%s", "--name-only"]).unwrap();
+        assert_eq!(
+            String::from_utf8(log.stdout).unwrap().trim(),
+            "scaffold rust-app\n\nsrc/app.rs"
+        );
+
+        // Outside a repository there is only a warning
+        let plain = tempfile::tempdir().unwrap();
+        fs::write(plain.path().join("app.rs"), "").unwrap();
+        let report = GenerationReport {
+            actions: vec![action("app.rs", FileOutcome::Created)],
+            ..Default::default()
+        };
+        assert!(commit_generated(&report, plain.path(), "init").is_ok());
+    }
+}
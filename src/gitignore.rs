@@ -0,0 +1,301 @@
+use anyhow::{Result, bail};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// The all-in-one gitignore written by `scaffer gitignore --all`
+pub const ALL_CONTENT: &str = r#"# Dependencies
+node_modules/
+target/
+dist/
+build/
+
+# Environment variables
+.env
+.env.local
+.env.*.local
+
+# IDE
+.vscode/
+.idea/
+*.swp
+*.swo
+
+# OS
+.DS_Store
+Thumbs.db
+
+# Logs
+*.log
+logs/
+
+# Cache
+.cache/
+*.tmp
+*.temp
+"#;
+
+/// A kind of project with its own ignore rules
+pub struct Stack {
+    pub name: &'static str,
+    /// Heading of the stack's section
+    title: &'static str,
+    /// Files whose presence in the project root identifies the stack
+    markers: &'static [&'static str],
+    /// File extensions whose presence in the project root identifies the stack
+    marker_extensions: &'static [&'static str],
+    patterns: &'static [&'static str],
+}
+
+/// Known project stacks, in the order their sections are written
+pub const STACKS: &[Stack] = &[
+    Stack {
+        name: "rust",
+        title: "Rust",
+        markers: &["Cargo.toml"],
+        marker_extensions: &[],
+        patterns: &["target/"],
+    },
+    Stack {
+        name: "node",
+        title: "Node",
+        markers: &["package.json"],
+        marker_extensions: &[],
+        patterns: &[
+            "node_modules/",
+            "dist/",
+            "coverage/",
+            "npm-debug.log*",
+            "yarn-debug.log*",
+            "yarn-error.log*",
+            ".npm/",
+        ],
+    },
+    Stack {
+        name: "python",
+        title: "Python",
+        markers: &[
+            "pyproject.toml",
+            "setup.py",
+            "setup.cfg",
+            "requirements.txt",
+        ],
+        marker_extensions: &[],
+        patterns: &[
+            "__pycache__/",
+            "*.py[cod]",
+            ".venv/",
+            "venv/",
+            "*.egg-info/",
+            ".pytest_cache/",
+            ".mypy_cache/",
+            "dist/",
+            "build/",
+        ],
+    },
+    Stack {
+        name: "go",
+        title: "Go",
+        markers: &["go.mod"],
+        marker_extensions: &[],
+        patterns: &["bin/", "*.test", "*.out"],
+    },
+    Stack {
+        name: "java",
+        title: "Java",
+        markers: &["pom.xml", "build.gradle", "build.gradle.kts"],
+        marker_extensions: &[],
+        patterns: &["target/", "build/", ".gradle/", "*.class"],
+    },
+    Stack {
+        name: "dotnet",
+        title: ".NET",
+        markers: &[],
+        marker_extensions: &["csproj", "fsproj", "sln"],
+        patterns: &["bin/", "obj/", "*.user"],
+    },
+];
+
+/// Sections included regardless of the detected stacks
+const COMMON_SECTIONS: &[(&str, &[&str])] = &[
+    (
+        "Environment variables",
+        &[".env", ".env.local", ".env.*.local"],
+    ),
+    ("IDE", &[".vscode/", ".idea/", "*.swp", "*.swo"]),
+    ("OS", &[".DS_Store", "Thumbs.db"]),
+    ("Logs", &["*.log"]),
+];
+
+/// Look up stacks by name, failing on unknown names
+pub fn stacks_by_name(names: &[String]) -> Result<Vec<&'static Stack>> {
+    names
+        .iter()
+        .map(|name| {
+            let name = name.to_lowercase();
+            match STACKS.iter().find(|stack| stack.name == name) {
+                Some(stack) => Ok(stack),
+                None => bail!(
+                    "Unknown stack '{name}' (expected one of: {})",
+                    STACKS
+                        .iter()
+                        .map(|stack| stack.name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Detect the stacks used by the project in `dir` from marker files in its root
+pub fn detect_stacks(dir: &Path) -> Vec<&'static Stack> {
+    let extensions: HashSet<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    entry
+                        .path()
+                        .extension()
+                        .map(|ext| ext.to_string_lossy().to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    STACKS
+        .iter()
+        .filter(|stack| {
+            stack.markers.iter().any(|marker| dir.join(marker).exists())
+                || stack
+                    .marker_extensions
+                    .iter()
+                    .any(|ext| extensions.contains(*ext))
+        })
+        .collect()
+}
+
+/// Build a gitignore for the given stacks, one section per stack followed by the common sections
+///
+/// Patterns already emitted by an earlier section are not repeated.
+pub fn render(stacks: &[&Stack]) -> String {
+    let sections = stacks
+        .iter()
+        .map(|stack| (stack.title, stack.patterns))
+        .chain(COMMON_SECTIONS.iter().copied());
+
+    let mut seen = HashSet::new();
+    let mut content = String::new();
+    for (title, patterns) in sections {
+        let fresh: Vec<&str> = patterns
+            .iter()
+            .copied()
+            .filter(|pattern| seen.insert(*pattern))
+            .collect();
+        if fresh.is_empty() {
+            continue;
+        }
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        content.push_str(&format!("# {title}\n"));
+        for pattern in fresh {
+            content.push_str(pattern);
+            content.push('\n');
+        }
+    }
+
+    content
+}
+
+/// Merge generated content into an existing gitignore, keeping only patterns it lacks
+///
+/// Returns the merged content and the number of patterns added.
+pub fn append(existing: &str, generated: &str) -> (String, usize) {
+    let present: HashSet<&str> = existing.lines().map(str::trim).collect();
+
+    let mut additions = String::new();
+    let mut added = 0;
+    let mut pending_header: Option<&str> = None;
+    for line in generated.lines() {
+        if line.starts_with('#') {
+            pending_header = Some(line);
+        } else if !line.is_empty() && !present.contains(line) {
+            if let Some(header) = pending_header.take() {
+                if !additions.is_empty() {
+                    additions.push('\n');
+                }
+                additions.push_str(header);
+                additions.push('\n');
+            }
+            additions.push_str(line);
+            additions.push('\n');
+            added += 1;
+        }
+    }
+
+    if added == 0 {
+        return (existing.to_string(), 0);
+    }
+
+    let mut merged = existing.to_string();
+    if !merged.is_empty() {
+        if !merged.ends_with('\n') {
+            merged.push('\n');
+        }
+        merged.push('\n');
+    }
+    merged.push_str(&additions);
+    (merged, added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_render() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+        fs::write(dir.path().join("package.json"), "{}").unwrap();
+        fs::write(dir.path().join("App.csproj"), "").unwrap();
+
+        let stacks = detect_stacks(dir.path());
+        let names: Vec<&str> = stacks.iter().map(|stack| stack.name).collect();
+        assert_eq!(names, vec!["rust", "node", "dotnet"]);
+
+        let content = render(&stacks);
+        assert!(content.starts_with("# Rust\ntarget/\n\n# Node\nnode_modules/\n"));
+        assert!(content.contains("# .NET\nbin/\nobj/\n"));
+        assert!(content.contains("# OS\n.DS_Store\n"));
+    }
+
+    #[test]
+    fn test_render_deduplicates_across_stacks() {
+        let stacks = stacks_by_name(&["java".to_string(), "rust".to_string()]).unwrap();
+        let content = render(&stacks);
+
+        assert_eq!(content.matches("target/").count(), 1);
+        assert!(!content.contains("# Rust"));
+        assert!(stacks_by_name(&["cobol".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_append_keeps_existing_entries() {
+        let existing = "target/\n.env\n/secrets";
+        let generated = render(&stacks_by_name(&["rust".to_string()]).unwrap());
+
+        let (merged, added) = append(existing, &generated);
+        assert!(
+            merged.starts_with("target/\n.env\n/secrets\n\n# Environment variables\n.env.local\n")
+        );
+        assert!(!merged.contains("# Rust"));
+        assert_eq!(merged.matches(".env\n").count(), 1);
+        assert!(added > 0);
+
+        let (unchanged, added) = append(&merged, &generated);
+        assert_eq!(unchanged, merged);
+        assert_eq!(added, 0);
+    }
+}
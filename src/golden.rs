@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A difference between generated output and the expected golden directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// Expected but not generated
+    Missing(String),
+    /// Generated but not expected
+    Unexpected(String),
+    /// Present in both with different content
+    Differs(String),
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Missing(path) => write!(f, "missing:    {path}"),
+            Mismatch::Unexpected(path) => write!(f, "unexpected: {path}"),
+            Mismatch::Differs(path) => write!(f, "differs:    {path}"),
+        }
+    }
+}
+
+/// Compare every file under `actual` with the corresponding file under `expected`
+pub fn compare_dirs(actual: &Path, expected: &Path) -> Result<Vec<Mismatch>> {
+    let actual_files = relative_files(actual)?;
+    let expected_files = relative_files(expected)?;
+
+    let mut mismatches = Vec::new();
+
+    for rel_path in expected_files.union(&actual_files) {
+        let name = rel_path.to_string_lossy().replace('\\', "/");
+
+        match (
+            actual_files.contains(rel_path),
+            expected_files.contains(rel_path),
+        ) {
+            (false, true) => mismatches.push(Mismatch::Missing(name)),
+            (true, false) => mismatches.push(Mismatch::Unexpected(name)),
+            _ => {
+                let actual_content = fs::read(actual.join(rel_path))
+                    .with_context(|| format!("Failed to read generated file {name}"))?;
+                let expected_content = fs::read(expected.join(rel_path))
+                    .with_context(|| format!("Failed to read golden file {name}"))?;
+                if actual_content != expected_content {
+                    mismatches.push(Mismatch::Differs(name));
+                }
+            }
+        }
+    }
+
+    Ok(mismatches)
+}
+
+/// Replace the golden directory with the generated output
+pub fn update_golden(actual: &Path, expected: &Path) -> Result<()> {
+    if expected.exists() {
+        fs::remove_dir_all(expected)
+            .with_context(|| format!("Failed to remove {}", expected.display()))?;
+    }
+
+    for rel_path in relative_files(actual)? {
+        let dest = expected.join(&rel_path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        fs::copy(actual.join(&rel_path), &dest)
+            .with_context(|| format!("Failed to write {}", dest.display()))?;
+    }
+
+    Ok(())
+}
+
+/// All files under a directory, relative to it (empty if the directory does not exist)
+/// Paths of all files below `root`, relative to it
+pub fn relative_files(root: &Path) -> Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+    if !root.exists() {
+        return Ok(files);
+    }
+
+    for entry in WalkDir::new(root) {
+        let entry = entry.with_context(|| format!("Failed to walk {}", root.display()))?;
+        if entry.file_type().is_file() {
+            let rel_path = entry
+                .path()
+                .strip_prefix(root)
+                .context("Failed to calculate relative path")?;
+            files.insert(rel_path.to_path_buf());
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_and_update() {
+        let actual = tempfile::tempdir().unwrap();
+        let expected = tempfile::tempdir().unwrap();
+
+        fs::create_dir(actual.path().join("src")).unwrap();
+        fs::write(actual.path().join("src/lib.rs"), "new").unwrap();
+        fs::write(actual.path().join("extra.txt"), "x").unwrap();
+        fs::create_dir(expected.path().join("src")).unwrap();
+        fs::write(expected.path().join("src/lib.rs"), "old").unwrap();
+        fs::write(expected.path().join("README.md"), "readme").unwrap();
+
+        let mismatches = compare_dirs(actual.path(), expected.path()).unwrap();
+        assert_eq!(
+            mismatches,
+            vec![
+                Mismatch::Missing("README.md".to_string()),
+                Mismatch::Unexpected("extra.txt".to_string()),
+                Mismatch::Differs("src/lib.rs".to_string()),
+            ]
+        );
+
+        update_golden(actual.path(), expected.path()).unwrap();
+        assert!(
+            compare_dirs(actual.path(), expected.path())
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
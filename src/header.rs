@@ -0,0 +1,109 @@
+use anyhow::{Result, bail};
+
+/// How comments are written in a kind of source file
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CommentSyntax {
+    /// Each line starts with the marker, e.g. `//` or `#`
+    Line(&'static str),
+    /// The whole header is wrapped in opening and closing markers
+    Block(&'static str, &'static str),
+}
+
+/// Comment syntax for files with the given extension
+fn comment_syntax(extension: &str) -> Option<CommentSyntax> {
+    use CommentSyntax::{Block, Line};
+
+    let syntax = match extension.to_lowercase().as_str() {
+        "rs" | "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" | "c" | "h" | "cc" | "cpp" | "hpp"
+        | "cs" | "fs" | "java" | "kt" | "kts" | "go" | "swift" | "scala" | "dart" | "php"
+        | "groovy" | "proto" => Line("//"),
+        "py" | "sh" | "bash" | "zsh" | "fish" | "rb" | "pl" | "r" | "ps1" | "yaml" | "yml"
+        | "toml" | "tf" | "cmake" | "nix" => Line("#"),
+        "sql" | "lua" | "hs" | "elm" => Line("--"),
+        "css" | "scss" | "less" => Block("/*", "*/"),
+        "html" | "htm" | "xml" | "vue" | "svelte" | "md" => Block("<!--", "-->"),
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+/// Format a header as a comment for files with the given extension
+fn commented(header: &str, extension: &str) -> Result<String> {
+    let Some(syntax) = comment_syntax(extension) else {
+        bail!("Don't know how to write a header comment in .{extension} files");
+    };
+
+    let header = header.trim_end();
+    let mut comment = String::new();
+    match syntax {
+        CommentSyntax::Line(marker) => {
+            for line in header.lines() {
+                if line.is_empty() {
+                    comment.push_str(marker);
+                } else {
+                    comment.push_str(&format!("{marker} {line}"));
+                }
+                comment.push('\n');
+            }
+        }
+        CommentSyntax::Block(open, close) => {
+            comment.push_str(&format!("{open}\n{header}\n{close}\n"));
+        }
+    }
+
+    Ok(comment)
+}
+
+/// Prepend a header comment to file content, unless the content already has it
+///
+/// A `#!` line stays first so that scripts remain executable.
+pub fn prepend(content: &str, header: &str, extension: &str) -> Result<String> {
+    let comment = commented(header, extension)?;
+    if content.contains(comment.trim_end()) {
+        return Ok(content.to_string());
+    }
+
+    let (shebang, body) = match content.strip_prefix("#!") {
+        Some(_) => content.split_at(content.find('\n').map_or(content.len(), |i| i + 1)),
+        None => ("", content),
+    };
+
+    let mut result = String::with_capacity(content.len() + comment.len() + 2);
+    result.push_str(shebang);
+    if !shebang.is_empty() && !shebang.ends_with('\n') {
+        result.push('\n');
+    }
+    result.push_str(&comment);
+    if !body.is_empty() {
+        result.push('\n');
+    }
+    result.push_str(body);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepend_header() {
+        let header = "Copyright Acme\n\nMIT License\n";
+        let rust = prepend("fn main() {}\n", header, "rs").unwrap();
+        assert_eq!(
+            rust,
+            "// Copyright Acme\n//\n// MIT License\n\nfn main() {}\n"
+        );
+        // Not inserted twice
+        assert_eq!(prepend(&rust, header, "rs").unwrap(), rust);
+
+        assert_eq!(
+            prepend("#!/bin/sh\necho hi\n", "Copyright Acme", "sh").unwrap(),
+            "#!/bin/sh\n# Copyright Acme\n\necho hi\n"
+        );
+        assert_eq!(
+            prepend("", "Copyright Acme", "css").unwrap(),
+            "/*\nCopyright Acme\n*/\n"
+        );
+        assert!(prepend("", "Copyright Acme", "xyz").is_err());
+    }
+}
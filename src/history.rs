@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Maximum number of template uses remembered
+const MAX_TEMPLATE_HISTORY: usize = 20;
+
+/// Maximum number of values remembered per variable
+const MAX_VALUE_HISTORY: usize = 10;
+
+/// Variables whose names contain any of these are never remembered
+const SENSITIVE_WORDS: &[&str] = &["password", "secret", "token"];
+
+/// Usage history stored in the user cache directory
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct History {
+    /// Most recently used first
+    #[serde(default)]
+    pub templates: Vec<TemplateUse>,
+    /// Values entered for variables, by variable name, most recent first
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub values: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateUse {
+    pub name: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl History {
+    /// Load the history, treating a missing or unreadable file as empty
+    pub fn load() -> Self {
+        history_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = history_path().context("Failed to determine cache directory")?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        let content = serde_json::to_string_pretty(self).context("Failed to serialize history")?;
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Remove the history file
+    pub fn clear() -> Result<()> {
+        if let Some(path) = history_path() {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record that a template was used just now
+    pub fn record_template(&mut self, name: &str) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        self.templates.retain(|entry| entry.name != name);
+        self.templates.insert(
+            0,
+            TemplateUse {
+                name: name.to_string(),
+                timestamp,
+            },
+        );
+        self.templates.truncate(MAX_TEMPLATE_HISTORY);
+    }
+
+    /// Follow a template to its new name, keeping its place in the history
+    pub fn rename_template(&mut self, old: &str, new: &str) {
+        self.templates.retain(|entry| entry.name != new);
+        for entry in &mut self.templates {
+            if entry.name == old {
+                entry.name = new.to_string();
+            }
+        }
+    }
+
+    /// Record a value entered for a variable, unless the variable looks sensitive
+    pub fn record_value(&mut self, name: &str, value: &str) {
+        if value.is_empty() || is_sensitive(name) {
+            return;
+        }
+
+        let values = self.values.entry(name.to_string()).or_default();
+        values.retain(|entry| entry != value);
+        values.insert(0, value.to_string());
+        values.truncate(MAX_VALUE_HISTORY);
+    }
+
+    /// The value last entered for a variable
+    pub fn last_value(&self, name: &str) -> Option<&str> {
+        self.values.get(name)?.first().map(String::as_str)
+    }
+
+    /// Names of recently used templates, most recent first
+    pub fn recent_templates(&self) -> Vec<String> {
+        self.templates
+            .iter()
+            .map(|entry| entry.name.clone())
+            .collect()
+    }
+}
+
+/// Whether a variable name suggests a value that must not be stored, such as
+/// `db-password` or `API_TOKEN`
+pub fn is_sensitive(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SENSITIVE_WORDS.iter().any(|word| name.contains(word))
+}
+
+/// Split available templates into recently used ones (most recent first) and the rest
+pub fn order_by_recent(templates: &[String], recent: &[String]) -> (Vec<String>, Vec<String>) {
+    let recent_available: Vec<String> = recent
+        .iter()
+        .filter(|name| templates.contains(name))
+        .cloned()
+        .collect();
+    let rest = templates
+        .iter()
+        .filter(|name| !recent_available.contains(name))
+        .cloned()
+        .collect();
+
+    (recent_available, rest)
+}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("scaffer").join("history.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_templates_first() {
+        let mut history = History::default();
+        history.record_template("b");
+        history.record_template("gone");
+        history.record_template("c");
+        history.record_template("b");
+
+        let templates = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let (recent, rest) = order_by_recent(&templates, &history.recent_templates());
+
+        assert_eq!(recent, vec!["b", "c"]);
+        assert_eq!(rest, vec!["a"]);
+
+        history.rename_template("c", "a");
+        assert_eq!(history.recent_templates(), vec!["b", "a", "gone"]);
+    }
+
+    #[test]
+    fn test_value_history() {
+        let mut history = History::default();
+        history.record_value("company", "Acme");
+        history.record_value("company", "Initech");
+        history.record_value("company", "Acme");
+        history.record_value("company", "");
+        assert_eq!(history.last_value("company"), Some("Acme"));
+        assert_eq!(history.values["company"], vec!["Acme", "Initech"]);
+
+        for name in ["db-password", "client-secret", "API_TOKEN"] {
+            history.record_value(name, "hunter2");
+            assert_eq!(history.last_value(name), None);
+        }
+        assert_eq!(history.last_value("author"), None);
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether files are being generated, so that Ctrl-C should stop after the current one
+static PROCESSING: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl-C was pressed while processing
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+static INSTALL: Once = Once::new();
+
+/// While alive, Ctrl-C asks generation to stop after the current file instead of
+/// killing the process; a second Ctrl-C still exits at once
+pub struct Guard(());
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        PROCESSING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Start handling Ctrl-C for a generation run
+pub fn guard() -> Guard {
+    INSTALL.call_once(|| {
+        // Without a handler Ctrl-C keeps its default behavior, which is still usable
+        let _ = ctrlc::set_handler(|| {
+            if PROCESSING.load(Ordering::SeqCst) && !INTERRUPTED.swap(true, Ordering::SeqCst) {
+                eprintln!("\nInterrupted: stopping after the current file (Ctrl-C again to abort)");
+            } else {
+                std::process::exit(130);
+            }
+        });
+    });
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    PROCESSING.store(true, Ordering::SeqCst);
+    Guard(())
+}
+
+/// Whether Ctrl-C was pressed since the current guard was taken
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,329 @@
+use crate::generator::{FileRules, TemplateGenerator};
+use crate::manifest::{self, TemplateManifest};
+use crate::template::TemplateProcessor;
+
+use anyhow::{Result, bail};
+use convert_case::{Case, Casing};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use walkdir::WalkDir;
+
+/// Characters that can't be used in file names on Windows
+const UNPORTABLE_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*', '\\'];
+
+/// A check `scaffer lint` runs on a template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// Words that look like tokens but are no variable, e.g. `scfName`
+    StrayTokens,
+    /// File names that can't be created on every platform
+    Filenames,
+    /// Manifest fields naming files that don't exist
+    ManifestFiles,
+    /// Variables configured in the manifest that nothing uses
+    UnusedVariables,
+}
+
+impl Check {
+    pub const ALL: [Check; 4] = [
+        Check::StrayTokens,
+        Check::Filenames,
+        Check::ManifestFiles,
+        Check::UnusedVariables,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Check::StrayTokens => "stray-tokens",
+            Check::Filenames => "filenames",
+            Check::ManifestFiles => "manifest-files",
+            Check::UnusedVariables => "unused-variables",
+        }
+    }
+
+    fn severity(self) -> Severity {
+        match self {
+            Check::StrayTokens | Check::UnusedVariables => Severity::Warning,
+            Check::Filenames | Check::ManifestFiles => Severity::Error,
+        }
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Check::ALL.into_iter().find(|check| check.name() == s) {
+            Some(check) => Ok(check),
+            None => Err(format!(
+                "unknown check '{s}' (expected one of: {})",
+                Check::ALL.map(Check::name).join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem a check found
+#[derive(Debug, PartialEq, Eq)]
+struct Finding {
+    check: Check,
+    message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.check.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{severity}[{}]: {}", self.check.name(), self.message)
+    }
+}
+
+/// Check a template for authoring mistakes, printing what the checks not in
+/// `skipped` find, and fail if any of it is an error
+pub fn run(template: &str, skipped: &[Check]) -> Result<()> {
+    let generator = TemplateGenerator::new()?;
+    let template_path = generator.find_template(template)?;
+    let manifest = TemplateManifest::load(&template_path)?;
+    let processor = generator.processor(&manifest)?;
+
+    let checks: Vec<Check> = Check::ALL
+        .into_iter()
+        .filter(|check| !skipped.contains(check))
+        .collect();
+    let findings = lint(&template_path, &manifest, &processor, &checks)?;
+    for finding in &findings {
+        println!("{finding}");
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|finding| finding.check.severity() == Severity::Error)
+        .count();
+    let warnings = findings.len() - errors;
+    if findings.is_empty() {
+        println!("No problems found in template '{template}'");
+    } else {
+        println!("\n{errors} error(s), {warnings} warning(s)");
+    }
+    if errors > 0 {
+        bail!("Template '{template}' has {errors} lint error(s)");
+    }
+    Ok(())
+}
+
+/// Run the checks on a template, returning what they find
+fn lint(
+    template_path: &Path,
+    manifest: &TemplateManifest,
+    processor: &TemplateProcessor,
+    checks: &[Check],
+) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    let mut report = |check: Check, message: String| {
+        if checks.contains(&check) {
+            findings.push(Finding { check, message });
+        }
+    };
+
+    let readme = manifest.readme.as_deref().map(Path::new);
+    let rules = FileRules::new(manifest)?;
+    let mut used = HashSet::new();
+    for entry in WalkDir::new(template_path)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| manifest.include_vcs || e.file_name() != ".git")
+        .filter_map(|e| e.ok())
+    {
+        let Ok(rel_path) = entry.path().strip_prefix(template_path) else {
+            continue;
+        };
+        // Template support files hold tokens only as documentation or configuration
+        if manifest::is_manifest_file(rel_path) || Some(rel_path) == readme {
+            continue;
+        }
+        let rel = rel_path.to_string_lossy().replace('\\', "/");
+
+        let name = entry.file_name().to_string_lossy();
+        let unportable: String = name
+            .chars()
+            .filter(|c| UNPORTABLE_CHARS.contains(c) || c.is_control())
+            .collect();
+        if !unportable.is_empty() {
+            report(
+                Check::Filenames,
+                format!("{rel}: the name has characters not allowed on Windows ({unportable})"),
+            );
+        } else if name.ends_with(['.', ' ']) {
+            report(
+                Check::Filenames,
+                format!("{rel}: the name ends with a dot or space, which Windows drops"),
+            );
+        }
+
+        if processor.substitutes_names() && !rules.keeps_name(rel_path) {
+            used.extend(processor.extract_variables(&rel));
+            used.extend(processor.defaulted_variables(&rel));
+            for token in processor.stray_tokens(&name) {
+                report(
+                    Check::StrayTokens,
+                    format!("{rel}: {token} in the name is not a variable token"),
+                );
+            }
+        }
+
+        if !entry.file_type().is_file() || !processor.substitutes_content() {
+            continue;
+        }
+        // Binary files aren't substituted
+        let Ok(content) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let content = rules.substituted_part(rel_path, &content);
+        used.extend(processor.extract_variables(content));
+        used.extend(processor.defaulted_variables(content));
+        if !processor.find_includes(content).is_empty()
+            && let Ok(expanded) = processor.expand_includes(content, template_path)
+        {
+            used.extend(processor.extract_variables(&expanded));
+        }
+        for token in processor.stray_tokens(content) {
+            report(
+                Check::StrayTokens,
+                format!("{rel}: {token} is not a variable token and is left as it is"),
+            );
+        }
+    }
+
+    // Files named by the manifest, relative to the template root
+    let named_files = manifest
+        .readme
+        .iter()
+        .map(|path| ("readme", path))
+        .chain(manifest.renames.keys().map(|path| ("renames", path)))
+        .chain(
+            manifest
+                .conditional_dirs
+                .keys()
+                .map(|path| ("conditional_dirs", path)),
+        );
+    let mut missing: Vec<String> = named_files
+        .filter(|(_, path)| !template_path.join(path.trim_end_matches('/')).exists())
+        .map(|(field, path)| {
+            format!(
+                "{}: {field} names '{path}', which is not in the template",
+                manifest::MANIFEST_FILE
+            )
+        })
+        .collect();
+    missing.sort();
+    for message in missing {
+        report(Check::ManifestFiles, message);
+    }
+
+    // Variables can also be used by the manifest's conditions, as names or tokens,
+    // and its header
+    let conditions = manifest.conditional_dirs.values().chain(
+        manifest
+            .variables
+            .values()
+            .filter_map(|spec| spec.required_if.as_ref()),
+    );
+    for condition in conditions {
+        used.extend(processor.extract_variables(condition));
+        used.insert(condition.trim().to_case(Case::Kebab));
+    }
+    if let Some(header) = &manifest.header {
+        used.extend(processor.extract_variables(header));
+    }
+
+    let mut configured: Vec<String> = manifest
+        .variables
+        .keys()
+        .chain(manifest.groups.values().flatten())
+        .chain(&manifest.optional)
+        .map(|name| name.to_case(Case::Kebab))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter(|name| !used.contains(name))
+        .collect();
+    configured.sort();
+    for name in configured {
+        report(
+            Check::UnusedVariables,
+            format!(
+                "{}: variable '{name}' is configured but no file uses it",
+                manifest::MANIFEST_FILE
+            ),
+        );
+    }
+
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(manifest::MANIFEST_FILE),
+            "readme = \"GUIDE.md\"\n\
+             conditional_dirs = { \"db\" = \"scf-use-db\" }\n\
+             [variables.name]\n\
+             description = \"Name\"\n\
+             [variables.port]\n\
+             description = \"Unused\"\n\
+             [variables.use-db]\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("scf-name.rs"), "struct ScfName; // scfName").unwrap();
+        fs::write(dir.path().join("what?.txt"), "").unwrap();
+        let manifest = TemplateManifest::load(dir.path()).unwrap();
+        let processor = TemplateProcessor::new();
+
+        let findings = lint(dir.path(), &manifest, &processor, &Check::ALL).unwrap();
+        let messages: Vec<String> = findings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "warning[stray-tokens]: scf-name.rs: scfName is not a variable token and is left as it is",
+                "error[filenames]: what?.txt: the name has characters not allowed on Windows (?)",
+                "error[manifest-files]: scaffer.toml: conditional_dirs names 'db', which is not in the template",
+                "error[manifest-files]: scaffer.toml: readme names 'GUIDE.md', which is not in the template",
+                "warning[unused-variables]: scaffer.toml: variable 'port' is configured but no file uses it",
+            ]
+        );
+
+        // Checks can be left out one by one
+        let findings = lint(
+            dir.path(),
+            &manifest,
+            &processor,
+            &[Check::StrayTokens, Check::UnusedVariables],
+        )
+        .unwrap();
+        assert!(
+            findings
+                .iter()
+                .all(|finding| finding.check.severity() == Severity::Warning)
+        );
+        assert_eq!(
+            "manifest-files".parse::<Check>().unwrap(),
+            Check::ManifestFiles
+        );
+    }
+}
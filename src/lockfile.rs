@@ -0,0 +1,117 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Name of the lockfile, in the output directory
+pub const LOCK_FILE: &str = "scaffer.lock";
+
+/// The exact templates generated into a directory, so that later runs can be checked
+/// against them with `--locked`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// Entries by template name, as given to `scaffer g`
+    #[serde(default)]
+    pub templates: BTreeMap<String, LockedTemplate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedTemplate {
+    /// Resolved download URL or template directory
+    pub source: String,
+    /// SHA-256 of the downloaded archive (URL templates only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Version of scaffer that generated the template
+    pub scaffer_version: String,
+}
+
+impl LockedTemplate {
+    /// An entry for a template resolved by this version of scaffer
+    pub fn new(source: &str, sha256: Option<&str>) -> Self {
+        Self {
+            source: source.to_string(),
+            sha256: sha256.map(str::to_string),
+            scaffer_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+impl Lockfile {
+    /// Load the lockfile of a directory, if it has one
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(LOCK_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let lockfile = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(lockfile))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(LOCK_FILE);
+        let content = serde_json::to_string_pretty(self).context("Failed to serialize lockfile")?;
+        fs::write(&path, content + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Check that a template resolved to the archive its entry was locked with
+    ///
+    /// Templates that aren't downloaded have no hash; they only need an entry.
+    pub fn verify(&self, template: &str, sha256: Option<&str>) -> Result<()> {
+        let Some(locked) = self.templates.get(template) else {
+            bail!("{LOCK_FILE} has no entry for template '{template}' (--locked)");
+        };
+        if locked.sha256.as_deref() != sha256 {
+            bail!(
+                "Template '{template}' does not match {LOCK_FILE} (--locked): sha256 is {}, locked {} from {}",
+                sha256.unwrap_or("none"),
+                locked.sha256.as_deref().unwrap_or("none"),
+                locked.source
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Lockfile::load(dir.path()).unwrap(), None);
+
+        let mut lockfile = Lockfile::default();
+        lockfile.templates.insert(
+            "app".to_string(),
+            LockedTemplate::new("https://example.com/app.zip", Some("abc123")),
+        );
+        lockfile.templates.insert(
+            "local".to_string(),
+            LockedTemplate::new("/templates/local", None),
+        );
+        lockfile.save(dir.path()).unwrap();
+        let loaded = Lockfile::load(dir.path()).unwrap().unwrap();
+        assert_eq!(loaded, lockfile);
+
+        // Matching archives pass
+        loaded.verify("app", Some("abc123")).unwrap();
+        loaded.verify("local", None).unwrap();
+
+        // Changed or missing archives and unknown templates fail
+        let err = loaded
+            .verify("app", Some("def456"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("sha256 is def456, locked abc123"), "{err}");
+        assert!(loaded.verify("app", None).is_err());
+        assert!(loaded.verify("local", Some("abc123")).is_err());
+        assert!(loaded.verify("other", None).is_err());
+    }
+}
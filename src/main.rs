@@ -1,13 +1,43 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use clap::{Parser, Subcommand};
 
 mod config;
+mod discover;
+mod filters;
 mod generator;
+mod gitcommit;
+mod gitignore;
+mod golden;
+mod header;
+mod history;
+mod http;
+mod interrupt;
+mod lint;
+mod lockfile;
+mod manifest;
+mod merge;
+mod oci;
+mod pack;
+mod patch;
+mod progress;
+mod pseudo;
+mod readme;
+mod registry;
+mod runlog;
+mod scancache;
+mod stats;
+mod status;
 mod template;
 mod utils;
+mod validate;
 
 use config::ScafferConfig;
-use generator::TemplateGenerator;
+use generator::{FileOutcome, GenerateOptions, GenerationReport, TemplateGenerator, Verbosity};
+use history::History;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use template::{FilenameCase, OutputEncoding};
 
 #[derive(Parser)]
 #[command(name = "scaffer")]
@@ -18,6 +48,8 @@ struct Cli {
     command: Commands,
 }
 
+// Parsed once per run, so the size of the `Generate` variant doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Generate code from named or downloaded template
@@ -34,13 +66,256 @@ enum Commands {
         /// Dry run, do not create files
         #[arg(long)]
         dry: bool,
+        /// With --dry, print the resolved variables as a JSON object instead of a table
+        #[arg(long, requires = "dry")]
+        json: bool,
+        /// Print only a one-line summary on success
+        #[arg(long)]
+        summary_only: bool,
+        /// Print nothing on success (overrides --summary-only)
+        #[arg(short, long)]
+        quiet: bool,
+        /// Normalize generated file names: preserve, lower or kebab
+        #[arg(long, value_name = "CASE")]
+        filename_case: Option<FilenameCase>,
+        /// Encoding of the generated text files: utf8, utf8-bom or utf16le
+        #[arg(long, value_name = "ENCODING")]
+        output_encoding: Option<OutputEncoding>,
+        /// With --dry, syntax-check processed files using configured validators
+        #[arg(long, requires = "dry")]
+        validate: bool,
+        /// Allow running external commands (validators)
+        #[arg(long)]
+        allow_scripts: bool,
+        /// Seed for random pseudo-variables (scf-uuid, scf-random)
+        #[arg(long, value_name = "N")]
+        seed: Option<u64>,
+        /// Do not use or record recently used templates
+        #[arg(long)]
+        no_history: bool,
+        /// Do not suggest or remember values entered for variables
+        #[arg(long)]
+        no_value_history: bool,
+        /// Append a JSON-lines record of this run to a file
+        #[arg(long, value_name = "PATH")]
+        log_file: Option<PathBuf>,
+        /// Leave variable values out of the log file
+        #[arg(long, requires = "log_file")]
+        log_redact: bool,
+        /// Write the run's file counts and bytes written to a JSON file
+        #[arg(long, value_name = "PATH")]
+        stats: Option<PathBuf>,
+        /// Always scan the template for variables instead of using the cached scan
+        #[arg(long)]
+        no_scan_cache: bool,
+        /// Only add new files: leave every existing file untouched without prompting
+        #[arg(long, conflicts_with = "force")]
+        into_existing: bool,
+        /// Generate into a staging directory and move the files into place only if
+        /// the whole run succeeds
+        #[arg(long)]
+        atomic: bool,
+        /// Write the changes as a git-apply compatible patch instead of writing files
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["dry", "atomic"])]
+        patch: Option<PathBuf>,
+        /// Print the processed files to stdout instead of writing them (several files
+        /// each get a header)
+        #[arg(long, conflicts_with_all = ["patch", "atomic", "open", "output"])]
+        stdout: bool,
+        /// Directory to generate into (`~` and $VARS are expanded); defaults to the
+        /// current directory
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+        /// When variables can't be prompted for, leave them without a value and write
+        /// this instead of their tokens (`{name}` is replaced by the variable name)
+        #[arg(long, value_name = "STR")]
+        missing_placeholder: Option<String>,
+        /// Write the generated files into a zip archive instead of a directory
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["stdout", "patch", "atomic", "open", "output"])]
+        to_zip: Option<PathBuf>,
+        /// Directory for downloaded and extracted templates (defaults to
+        /// $SCAFFER_TMPDIR, the configured `temp_dir` or the system temp directory)
+        #[arg(long, value_name = "DIR")]
+        temp_dir: Option<PathBuf>,
+        /// Instead of download progress, print one line with the URL, size and SHA-256
+        /// of a downloaded template archive (also with --quiet)
+        #[arg(long)]
+        quiet_download: bool,
+        /// Record the resolved template (source, archive SHA-256, scaffer version) in
+        /// scaffer.lock in the output directory; a directory with a scaffer.lock always
+        /// gets it updated
+        #[arg(long, conflicts_with_all = ["dry", "patch", "stdout", "to_zip"])]
+        lock: bool,
+        /// Fail unless the template matches its entry in the output directory's
+        /// scaffer.lock, which is left as it is
+        #[arg(long, conflicts_with = "lock")]
+        locked: bool,
+        /// Drop the first N components of every generated file's path, like tar's
+        /// option (for archives nested deeper than the template root detection expects)
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        strip_components: usize,
+        /// Open the written files in the editor after a successful run
+        #[arg(long, conflicts_with = "patch")]
+        open: bool,
+        /// After a successful run, stage the written files and commit them with
+        /// MESSAGE (default "scaffold <template>")
+        #[arg(
+            long,
+            value_name = "MESSAGE",
+            num_args = 0..=1,
+            default_missing_value = "",
+            conflicts_with_all = ["dry", "patch", "stdout", "to_zip"]
+        )]
+        git_commit: Option<String>,
+        /// Review and edit all variables in a single form before generating
+        #[arg(long)]
+        interactive_vars: bool,
+        /// Prompt for every variable, also those given with -v, offering their current
+        /// value to accept or change
+        #[arg(long, conflicts_with = "interactive_vars")]
+        prompt_all: bool,
+        /// Don't generate the template named by the manifest's `after` field
+        #[arg(long)]
+        no_chain: bool,
+        /// Fail when no files were created, e.g. in CI (not checked with --dry)
+        #[arg(long)]
+        require_output: bool,
+        /// Print how the template was resolved: configuration files, directories
+        /// searched, registries and URLs, and the resolved path
+        #[arg(long)]
+        explain: bool,
+        /// Add a .gitignore for the project types detected in the output when the
+        /// template's manifest names no `gitignore_stacks`
+        #[arg(long)]
+        with_gitignore: bool,
+        /// Fetch the registry indexes again even when their cache is fresh, updating
+        /// the cache
+        #[arg(long)]
+        refresh: bool,
+        /// Fail when generated paths differ only in letter case, instead of warning
+        #[arg(long)]
+        strict: bool,
+        /// Substitute variables only in file and directory names, copying contents verbatim
+        #[arg(long, conflicts_with = "content_only")]
+        names_only: bool,
+        /// Substitute variables only in file contents, keeping the template's file names
+        #[arg(long)]
+        content_only: bool,
+    },
+    /// Generate a template into a temporary directory and compare it with expected output
+    Test {
+        /// Template to test
+        template: String,
+        /// JSON file with variable values, e.g. {"name": "my-app"}
+        #[arg(long, value_name = "FILE")]
+        vars: Option<PathBuf>,
+        /// Directory holding the expected (golden) output
+        #[arg(long, value_name = "DIR")]
+        expect: PathBuf,
+        /// Overwrite the expected output with the generated files
+        #[arg(long)]
+        update: bool,
     },
     /// Add current directory as template root in user global scaffer.json
     Add,
+    /// List available templates and where they come from
+    List {
+        /// Only print where the template NAME resolves to
+        #[arg(long, value_name = "NAME")]
+        which: Option<String>,
+    },
+    /// Show recently used templates and remembered variable values
+    History {
+        /// Forget all recently used templates and remembered variable values
+        #[arg(long)]
+        clear: bool,
+        /// Forget only the remembered variable values
+        #[arg(long, conflicts_with = "clear")]
+        clear_values: bool,
+    },
     /// Create index.ts for current directory
-    Barrel,
-    /// Create .gitignore file
-    Gitignore,
+    Barrel {
+        /// Print the barrel file instead of writing it
+        #[arg(long)]
+        dry: bool,
+    },
+    /// Create .gitignore file tailored to the detected project type
+    Gitignore {
+        /// Stack to write rules for instead of detecting it (can be repeated)
+        #[arg(long = "stack", value_name = "NAME", conflicts_with = "all")]
+        stacks: Vec<String>,
+        /// Add missing rules to an existing .gitignore instead of replacing it
+        #[arg(long)]
+        append: bool,
+        /// Write the generic all-in-one .gitignore
+        #[arg(long)]
+        all: bool,
+        /// Print the .gitignore instead of writing it
+        #[arg(long)]
+        dry: bool,
+    },
+    /// Rename a template directory or a configured template URL
+    #[command(alias = "move")]
+    Mv {
+        /// Current template name
+        old: String,
+        /// New template name
+        new: String,
+    },
+    /// Package a template directory as a zip archive for hosting
+    Pack {
+        /// Template name or directory
+        template: String,
+        /// Archive to write (defaults to <template>.zip in the current directory)
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Keep the template's scaffer.toml (and scaffer.vars files) in the archive
+        #[arg(long)]
+        include_manifest: bool,
+    },
+    /// Print one file of a template processed with the given variables, writing nothing
+    Preview {
+        /// Template name or directory
+        template: String,
+        /// Give value to variable
+        #[arg(short = 'v', long = "var", value_name = "variable=value")]
+        variables: Vec<String>,
+        /// File to process, relative to the template root
+        #[arg(long, value_name = "PATH")]
+        show: String,
+    },
+    /// Print a template's documentation, the `readme` file named in its manifest
+    Readme {
+        /// Template name or directory
+        template: String,
+        /// Print the markdown as it is, also on a terminal
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Check a template for authoring mistakes, failing on errors
+    Lint {
+        /// Template name or directory
+        template: String,
+        /// Leave out a check: stray-tokens, filenames, manifest-files or
+        /// unused-variables (can be repeated)
+        #[arg(long = "skip", value_name = "CHECK")]
+        skipped: Vec<lint::Check>,
+    },
+    /// Fetch the indexes of the configured registries again, refreshing their cache
+    Update {
+        /// Number of registries fetched at the same time
+        #[arg(short, long, default_value_t = 4, value_name = "N")]
+        jobs: usize,
+    },
+    /// Show how generated files changed locally and in their template since generation
+    Status {
+        /// Run log written by `scaffer g --log-file`, holding the generation to compare with
+        #[arg(long, value_name = "PATH")]
+        log_file: PathBuf,
+        /// Directory the files were generated into; defaults to the current directory
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+    },
     /// Setup scaffer configuration
     Setup,
 }
@@ -54,18 +329,170 @@ fn main() -> Result<()> {
             variables,
             force,
             dry,
+            json,
+            summary_only,
+            quiet,
+            filename_case,
+            output_encoding,
+            validate,
+            allow_scripts,
+            seed,
+            no_history,
+            no_value_history,
+            log_file,
+            log_redact,
+            stats,
+            no_scan_cache,
+            into_existing,
+            atomic,
+            patch,
+            stdout,
+            output,
+            temp_dir,
+            quiet_download,
+            lock,
+            locked,
+            strip_components,
+            to_zip,
+            missing_placeholder,
+            open,
+            git_commit,
+            interactive_vars,
+            prompt_all,
+            no_chain,
+            require_output,
+            explain,
+            with_gitignore,
+            refresh,
+            strict,
+            names_only,
+            content_only,
+        } => {
+            // Only the generated content goes to stdout
+            let verbosity = if quiet || stdout {
+                Verbosity::Quiet
+            } else if summary_only {
+                Verbosity::SummaryOnly
+            } else {
+                Verbosity::Normal
+            };
+            let options = GenerateOptions {
+                force,
+                dry_run: dry,
+                verbosity,
+                filename_case,
+                output_encoding,
+                validate,
+                allow_scripts,
+                seed,
+                no_history,
+                no_value_history,
+                log_file,
+                log_redact,
+                stats,
+                no_scan_cache,
+                into_existing,
+                atomic,
+                patch,
+                output_dir: output,
+                temp_dir,
+                quiet_download,
+                lock,
+                locked,
+                strip_components,
+                to_zip,
+                missing_placeholder,
+                // Prompts can't be answered when stdin is the input of a pipeline
+                no_input: stdout && !std::io::stdin().is_terminal(),
+                stdout,
+                json,
+                interactive_vars,
+                prompt_all,
+                no_chain,
+                require_output,
+                explain,
+                with_gitignore,
+                refresh,
+                strict,
+                names_only,
+                content_only,
+            };
+            let generator = TemplateGenerator::new()?;
+            let report = generator.generate(template, variables, &options)?;
+            if let Some(message) = &git_commit {
+                gitcommit::commit_generated(&report, &options.output_dir()?, message)?;
+            }
+            if open && !dry {
+                open_in_editor(&report, &options.output_dir()?)?;
+            }
+        }
+        Commands::Test {
+            template,
+            vars,
+            expect,
+            update,
         } => {
-            let generator = TemplateGenerator::new();
-            generator.generate(template, variables, force, dry)?;
+            test_template(template, vars, expect, update)?;
         }
         Commands::Add => {
             add_current_directory_as_template()?;
         }
-        Commands::Barrel => {
-            create_barrel_file()?;
+        Commands::List { which } => match which {
+            Some(name) => print_template_source(&name)?,
+            None => list_templates()?,
+        },
+        Commands::History {
+            clear,
+            clear_values,
+        } => {
+            show_history(clear, clear_values)?;
         }
-        Commands::Gitignore => {
-            create_gitignore_file()?;
+        Commands::Barrel { dry } => {
+            create_barrel_file(dry)?;
+        }
+        Commands::Gitignore {
+            stacks,
+            append,
+            all,
+            dry,
+        } => {
+            create_gitignore_file(&stacks, append, all, dry)?;
+        }
+        Commands::Mv { old, new } => {
+            move_template(&old, &new)?;
+        }
+        Commands::Pack {
+            template,
+            output,
+            include_manifest,
+        } => {
+            pack_template(&template, output, include_manifest)?;
+        }
+        Commands::Preview {
+            template,
+            variables,
+            show,
+        } => {
+            let content = TemplateGenerator::new()?.preview_file(&template, &variables, &show)?;
+            std::io::stdout()
+                .write_all(&content)
+                .context("Failed to write to stdout")?;
+        }
+        Commands::Readme { template, raw } => {
+            readme::show(&template, raw)?;
+        }
+        Commands::Lint { template, skipped } => {
+            lint::run(&template, &skipped)?;
+        }
+        Commands::Update { jobs } => {
+            update_registries(jobs)?;
+        }
+        Commands::Status { log_file, output } => {
+            let output_dir = match output {
+                Some(output) => utils::expand_path(&output)?,
+                None => PathBuf::from("."),
+            };
+            status::status(&log_file, &output_dir)?;
         }
         Commands::Setup => {
             setup_scaffer_config()?;
@@ -75,6 +502,42 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Open the files written by a generation run in the configured editor, else
+/// `$VISUAL` or `$EDITOR`
+fn open_in_editor(report: &GenerationReport, output_dir: &std::path::Path) -> Result<()> {
+    let files: Vec<PathBuf> = report
+        .actions
+        .iter()
+        .filter(|action| action.outcome != FileOutcome::Skipped)
+        .map(|action| output_dir.join(&action.destination))
+        .collect();
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let command = ScafferConfig::load_global()?
+        .editor
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|command| !command.trim().is_empty())
+        .context(
+            "No editor to open the files with (set \"editor\" in ~/.scaffer.json, or $VISUAL or $EDITOR)",
+        )?;
+
+    let mut args = command.split_whitespace();
+    let program = args.next().unwrap_or_default();
+    let status = std::process::Command::new(program)
+        .args(args)
+        .args(&files)
+        .status()
+        .with_context(|| format!("Failed to run editor '{command}'"))?;
+    if !status.success() {
+        bail!("Editor '{command}' failed: {status}");
+    }
+
+    Ok(())
+}
+
 fn add_current_directory_as_template() -> Result<()> {
     let current_dir = std::env::current_dir().context("Failed to get current directory")?;
 
@@ -86,7 +549,286 @@ fn add_current_directory_as_template() -> Result<()> {
     Ok(())
 }
 
-fn create_barrel_file() -> Result<()> {
+fn test_template(
+    template: String,
+    vars: Option<PathBuf>,
+    expect: PathBuf,
+    update: bool,
+) -> Result<()> {
+    let variables: HashMap<String, String> = match &vars {
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&content).with_context(|| {
+                format!(
+                    "Failed to parse {} (expected an object of string values)",
+                    path.display()
+                )
+            })?
+        }
+        None => HashMap::new(),
+    };
+
+    let output_dir = tempfile::TempDir::new().context("Failed to create temporary directory")?;
+    let options = GenerateOptions {
+        verbosity: Verbosity::Quiet,
+        output_dir: Some(output_dir.path().to_path_buf()),
+        no_input: true,
+        no_history: true,
+        ..Default::default()
+    };
+
+    let generator = TemplateGenerator::new()?;
+    generator.generate_with_variables(Some(template), variables, &options)?;
+
+    if update {
+        golden::update_golden(output_dir.path(), &expect)?;
+        println!("Updated expected output in {}", expect.display());
+        return Ok(());
+    }
+
+    let mismatches = golden::compare_dirs(output_dir.path(), &expect)?;
+    if mismatches.is_empty() {
+        println!("Template output matches {}", expect.display());
+        return Ok(());
+    }
+
+    for mismatch in &mismatches {
+        println!("{mismatch}");
+    }
+    bail!(
+        "{} file(s) differ from expected output in {}",
+        mismatches.len(),
+        expect.display()
+    );
+}
+
+fn move_template(old: &str, new: &str) -> Result<()> {
+    use config::TemplateSource;
+
+    let config = ScafferConfig::load()?;
+    let templates = config.list_templates()?;
+
+    if templates.iter().any(|info| info.name == new) {
+        bail!("Template '{new}' already exists");
+    }
+    // The first match is the template `generate` would use
+    let Some(template) = templates.iter().find(|info| info.name == old) else {
+        bail!("Template '{old}' not found");
+    };
+
+    match &template.source {
+        TemplateSource::Directory(path) => {
+            if config::manifest_name(path).is_some() {
+                bail!(
+                    "Template '{old}' is named in {}; change its `name` there instead",
+                    path.join(manifest::MANIFEST_FILE).display()
+                );
+            }
+            let target = path.with_file_name(new);
+            if target.exists() {
+                bail!("{} already exists", target.display());
+            }
+            std::fs::rename(path, &target).with_context(|| {
+                format!(
+                    "Failed to rename {} to {}",
+                    path.display(),
+                    target.display()
+                )
+            })?;
+            println!("Renamed {} to {}", path.display(), target.display());
+        }
+        TemplateSource::Url(_) => {
+            let mut files = Vec::new();
+            files.extend(ScafferConfig::find_local_file()?);
+            files.push(ScafferConfig::global_file()?);
+
+            let mut renamed = false;
+            for file in files.iter().filter(|file| file.exists()) {
+                if file.file_name() == Some(std::ffi::OsStr::new("package.json")) {
+                    if config
+                        .scaffer_template_urls
+                        .as_ref()
+                        .is_some_and(|urls| urls.contains_key(old))
+                    {
+                        bail!(
+                            "Template '{old}' is configured in {}; rename it there by hand",
+                            file.display()
+                        );
+                    }
+                    continue;
+                }
+                if config::rename_template_url(file, old, new)? {
+                    println!("Renamed template '{old}' to '{new}' in {}", file.display());
+                    renamed = true;
+                }
+            }
+            if !renamed {
+                bail!("Template '{old}' not found in a configuration file");
+            }
+        }
+        TemplateSource::Registry { registry, .. } => {
+            bail!("Template '{old}' comes from registry {registry} and can't be renamed");
+        }
+    }
+
+    let mut history = History::load();
+    history.rename_template(old, new);
+    // History is a convenience; failing to save it should not fail the rename
+    let _ = history.save();
+
+    Ok(())
+}
+
+fn pack_template(template: &str, output: Option<PathBuf>, include_manifest: bool) -> Result<()> {
+    let template_dir = TemplateGenerator::new()?.find_template(template)?;
+    if !template_dir.is_dir() {
+        bail!(
+            "{} is a single-file template; host the file itself",
+            template_dir.display()
+        );
+    }
+
+    let output = match output {
+        Some(output) => output,
+        None => {
+            let name = template_dir
+                .canonicalize()
+                .ok()
+                .and_then(|dir| {
+                    dir.file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                })
+                .context("Can't name the archive after the template; use --output")?;
+            PathBuf::from(format!("{name}.zip"))
+        }
+    };
+
+    let files = pack::pack(&template_dir, &output, include_manifest)?;
+    println!(
+        "Packed {files} file(s) from {} into {}",
+        template_dir.display(),
+        output.display()
+    );
+    Ok(())
+}
+
+fn list_templates() -> Result<()> {
+    let config = ScafferConfig::load()?;
+    let templates = config.list_templates()?;
+
+    if templates.is_empty() {
+        println!("No templates found. Run 'scaffer setup' to configure template directories.");
+        return Ok(());
+    }
+
+    let mut previous: Option<&str> = None;
+    for info in &templates {
+        // Later templates of the same name are never used
+        let shadowed = if previous == Some(info.name.as_str()) {
+            "  (shadowed)"
+        } else {
+            ""
+        };
+        match &info.description {
+            Some(description) => println!(
+                "{}  {}  [{}]{shadowed}",
+                info.name, description, info.source
+            ),
+            None => println!("{}  [{}]{shadowed}", info.name, info.source),
+        }
+        previous = Some(&info.name);
+    }
+
+    Ok(())
+}
+
+/// Fetch the indexes of all configured registries again, `jobs` at a time, replacing
+/// their cached copies; fails when any of them could not be fetched
+fn update_registries(jobs: usize) -> Result<()> {
+    let registries = ScafferConfig::load()?.get_registries();
+    if registries.is_empty() {
+        println!("No registries configured");
+        return Ok(());
+    }
+
+    let started = std::time::Instant::now();
+    let results = registry::update_all(&registries, jobs);
+    let mut failed = 0;
+    for (url, result) in registries.iter().zip(results) {
+        match result {
+            Ok(count) => println!("Updated {url}: {count} templates"),
+            Err(err) => {
+                println!("Failed {url}: {err:#}");
+                failed += 1;
+            }
+        }
+    }
+    println!(
+        "\nUpdated {} of {} registries in {:.1}s",
+        registries.len() - failed,
+        registries.len(),
+        started.elapsed().as_secs_f64()
+    );
+    if failed > 0 {
+        bail!("{failed} registries could not be updated; their cached indexes are kept");
+    }
+    Ok(())
+}
+
+/// Print the source a template name resolves to, noting the templates it shadows
+fn print_template_source(name: &str) -> Result<()> {
+    if std::path::Path::new(name).exists() {
+        println!("{name}");
+        return Ok(());
+    }
+
+    let config = ScafferConfig::load()?;
+    let candidates = config.template_candidates(name)?;
+    let Some((used, shadowed)) = candidates.split_first() else {
+        bail!("Template '{name}' not found");
+    };
+
+    println!("{}", used.source);
+    for info in shadowed {
+        eprintln!("shadows {}", info.source);
+    }
+    Ok(())
+}
+
+fn show_history(clear: bool, clear_values: bool) -> Result<()> {
+    if clear {
+        History::clear()?;
+        println!("Cleared template history");
+        return Ok(());
+    }
+
+    let mut history = History::load();
+    if clear_values {
+        history.values.clear();
+        history.save()?;
+        println!("Cleared remembered variable values");
+        return Ok(());
+    }
+
+    if history.templates.is_empty() {
+        println!("No recently used templates");
+    }
+    for name in history.recent_templates() {
+        println!("{name}");
+    }
+
+    if !history.values.is_empty() {
+        println!("\nRemembered variable values (most recent first):");
+        for (name, values) in &history.values {
+            println!("  {name}: {}", values.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+fn create_barrel_file(dry: bool) -> Result<()> {
     use std::fs;
     use walkdir::WalkDir;
 
@@ -112,46 +854,73 @@ fn create_barrel_file() -> Result<()> {
         }
     }
 
-    fs::write("index.ts", exports.join(""))?;
+    let content = exports.join("");
+    if dry {
+        let what = if std::path::Path::new("index.ts").exists() {
+            "Would replace index.ts with"
+        } else {
+            "Would create index.ts"
+        };
+        utils::print_dry_write(what, &content);
+        return Ok(());
+    }
+
+    fs::write("index.ts", content)?;
     println!("Created index.ts barrel file");
     Ok(())
 }
 
-fn create_gitignore_file() -> Result<()> {
+fn create_gitignore_file(stacks: &[String], append: bool, all: bool, dry: bool) -> Result<()> {
     use std::fs;
 
-    let gitignore_content = r#"# Dependencies
-node_modules/
-target/
-dist/
-build/
-
-# Environment variables
-.env
-.env.local
-.env.*.local
-
-# IDE
-.vscode/
-.idea/
-*.swp
-*.swo
-
-# OS
-.DS_Store
-Thumbs.db
-
-# Logs
-*.log
-logs/
-
-# Cache
-.cache/
-*.tmp
-*.temp
-"#;
-
-    fs::write(".gitignore", gitignore_content)?;
+    let content = if all {
+        gitignore::ALL_CONTENT.to_string()
+    } else {
+        let stacks = if stacks.is_empty() {
+            gitignore::detect_stacks(&std::env::current_dir()?)
+        } else {
+            gitignore::stacks_by_name(stacks)?
+        };
+
+        if stacks.is_empty() {
+            println!("No project type detected, using the generic .gitignore");
+            gitignore::ALL_CONTENT.to_string()
+        } else {
+            let names: Vec<&str> = stacks.iter().map(|stack| stack.name).collect();
+            println!("Writing rules for: {}", names.join(", "));
+            gitignore::render(&stacks)
+        }
+    };
+
+    let path = std::path::Path::new(".gitignore");
+    if append && path.exists() {
+        let existing = fs::read_to_string(path).context("Failed to read .gitignore")?;
+        let (merged, added) = gitignore::append(&existing, &content);
+        if added == 0 {
+            println!(".gitignore is already up to date");
+        } else if dry {
+            utils::print_dry_write(
+                &format!("Would add {added} entries to .gitignore, giving"),
+                &merged,
+            );
+        } else {
+            fs::write(path, merged).context("Failed to write .gitignore")?;
+            println!("Added {added} entries to .gitignore");
+        }
+        return Ok(());
+    }
+
+    if dry {
+        let what = if path.exists() {
+            "Would replace .gitignore with"
+        } else {
+            "Would create .gitignore"
+        };
+        utils::print_dry_write(what, &content);
+        return Ok(());
+    }
+
+    fs::write(path, content).context("Failed to write .gitignore")?;
     println!("Created .gitignore file");
     Ok(())
 }
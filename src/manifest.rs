@@ -0,0 +1,607 @@
+use crate::merge::ArrayStrategy;
+use crate::template::{self, FilenameCase, OutputEncoding};
+
+use crate::utils;
+
+use anyhow::{Context, Result, bail};
+use convert_case::{Case, Casing};
+use globset::GlobBuilder;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// File name of the optional manifest at a template root
+pub const MANIFEST_FILE: &str = "scaffer.toml";
+
+/// File names of the optional variables files at a template root, which hold the
+/// same per-variable settings as the manifest's `[variables]` table in other formats
+pub const VARS_FILES: &[&str] = &[
+    "scaffer.vars.json",
+    "scaffer.vars.toml",
+    "scaffer.vars.yaml",
+    "scaffer.vars.yml",
+];
+
+/// Whether a path relative to the template root is the manifest or a variables file,
+/// which configure the template and aren't part of it
+pub fn is_manifest_file(rel_path: &Path) -> bool {
+    rel_path == Path::new(MANIFEST_FILE)
+        || VARS_FILES.iter().any(|file| rel_path == Path::new(file))
+}
+
+/// Template-level settings read from `scaffer.toml`
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct TemplateManifest {
+    /// Name the template is listed and looked up by, instead of its directory name
+    pub name: Option<String>,
+    /// One-line summary shown by `scaffer list`
+    pub description: Option<String>,
+    /// Markdown file (relative to the template root) documenting the template, shown
+    /// by `scaffer readme` and never generated
+    pub readme: Option<String>,
+    /// Case normalization applied to generated file and directory names
+    pub filename_case: Option<FilenameCase>,
+    /// Encoding generated text files are written in (as rendered, i.e. UTF-8, unless
+    /// set)
+    pub output_encoding: Option<OutputEncoding>,
+    /// Globs of destination paths that are overwritten without prompting
+    pub auto_overwrite: Vec<String>,
+    /// Explicit destinations for template files, from source relative path to
+    /// destination relative path (both may contain template variables)
+    pub renames: HashMap<String, String>,
+    /// Generate the template's `.git` metadata instead of leaving it out
+    pub include_vcs: bool,
+    /// Unix permissions of generated files, from destination glob to an octal mode
+    /// such as `"0755"`; the first matching glob applies
+    pub modes: IndexMap<String, String>,
+    /// Existing JSON files that generated content is merged into instead of replacing
+    /// them, by destination glob; the first matching glob applies
+    pub merge: IndexMap<String, MergeRule>,
+    /// Globs of destination paths whose existing files only get their marked regions
+    /// (`scaffer:begin <name>` ... `scaffer:end <name>`) updated instead of replacing
+    /// them
+    pub update_regions: Vec<String>,
+    /// Template directories generated only when a variable is true, from relative
+    /// directory path to the variable (as a name or a template token)
+    pub conditional_dirs: HashMap<String, String>,
+    /// Per-variable settings, keyed by kebab-case variable name
+    pub variables: HashMap<String, VariableSpec>,
+    /// Variables prompted together under a label, in the order they are declared
+    pub groups: IndexMap<String, Vec<String>>,
+    /// Prefix of variable tokens, e.g. `tpl` for `TplName` and `tpl-name`
+    pub prefix: Option<String>,
+    /// Case styles variables are recognized in, e.g. `["pascal", "kebab"]`
+    pub cases: Option<Vec<String>>,
+    /// Template generated next, into the same output and with the same variables (a
+    /// template name, URL, or directory relative to this template)
+    pub after: Option<String>,
+    /// Variables always asked for, even when no file uses them directly (e.g. only
+    /// through an include)
+    pub required: Vec<String>,
+    /// Variables never asked for: they are substituted when given with `-v` and left
+    /// as they are otherwise
+    pub optional: Vec<String>,
+    /// The template deliberately has no variables, so finding none isn't pointed out
+    pub no_variables: bool,
+    /// Globs of variable names (kebab-case, without the prefix) that are never asked
+    /// for nor substituted, for tokens that only look like variables
+    pub ignore_vars: Vec<String>,
+    /// Whole tokens (e.g. `scfm`) that are never variables, matched ignoring case
+    pub substitution_denylist: Vec<String>,
+    /// Words kept in upper case where a case style capitalizes words, e.g. `["api"]`
+    /// turns the value `api-client` into `APIClient` rather than `ApiClient`
+    pub acronyms: Vec<String>,
+    /// Header (e.g. a license notice) put on top of generated source files as a
+    /// comment; may contain template variables
+    pub header: Option<String>,
+    /// Extensions of the files that get the header, e.g. `["rs", "py"]`
+    pub header_extensions: Vec<String>,
+    /// Stacks (as `scaffer gitignore --stack` takes them) whose ignore rules are added
+    /// to the generated project's `.gitignore`
+    pub gitignore_stacks: Vec<String>,
+    /// Globs of template files (e.g. `["docs/*.md"]`) whose variables are substituted
+    /// only in their `---` fenced frontmatter, leaving the body as it is
+    pub frontmatter_only: Vec<String>,
+    /// Directory (which may contain template variables, e.g. `scf-name`) the template's
+    /// contents are generated into, below the output directory
+    pub root_as: Option<String>,
+    /// Globs of static template files, whose contents are copied as they are without
+    /// being scanned for variables; their names are still substituted
+    #[serde(rename = "static")]
+    pub static_files: Vec<String>,
+    /// Globs of template files copied entirely as they are: neither their contents nor
+    /// their names are substituted
+    pub verbatim: Vec<String>,
+}
+
+/// How generated JSON is merged into an existing file
+#[derive(Debug, Clone, Copy, Deserialize, Default)]
+#[serde(default)]
+pub struct MergeRule {
+    /// `replace`, `concat` or `concat-dedup` (the default)
+    pub arrays: ArrayStrategy,
+}
+
+/// Settings for one template variable
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct VariableSpec {
+    /// Where to get the choices offered when prompting for the variable:
+    /// `dirs:<glob>`, `files:<glob>` (matched relative to the output directory)
+    /// or `options:<a>,<b>,...`
+    pub choices_from: Option<String>,
+    /// Only ask for the variable when this other variable, given as a name or as a
+    /// template token (e.g. `scf-use-db`), has a true value
+    pub required_if: Option<String>,
+    /// Explanation shown next to the variable in the `--interactive-vars` form
+    pub description: Option<String>,
+    /// `secret` for values that are prompted for without echo and never stored, `int`
+    /// for whole numbers and `enum` for one of `options`
+    #[serde(rename = "type")]
+    pub kind: VariableKind,
+    /// Smallest value of an `int` variable
+    pub min: Option<i64>,
+    /// Largest value of an `int` variable
+    pub max: Option<i64>,
+    /// The values an `enum` variable can have
+    pub options: Vec<String>,
+    /// Value used when none is given, instead of prompting (overrides the
+    /// configuration's `global_var_defaults`)
+    pub default: Option<String>,
+}
+
+/// What kind of value a variable holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableKind {
+    #[default]
+    Text,
+    /// Entered without echo, masked when shown, and left out of the history and
+    /// the run log
+    Secret,
+    /// A whole number, optionally between `min` and `max`
+    Int,
+    /// One of `options`, selected from a list when prompted
+    Enum,
+}
+
+/// Whether a variable value counts as true in a `required_if` condition: anything but
+/// an empty value, `false`, `no`, `n`, `off` or `0`
+pub fn is_truthy(value: &str) -> bool {
+    !matches!(
+        value.trim().to_lowercase().as_str(),
+        "" | "false" | "no" | "n" | "off" | "0"
+    )
+}
+
+impl VariableSpec {
+    /// Resolve the choices to offer for this variable, empty if there are none
+    ///
+    /// Filesystem choices are the names of the entries matching the glob under `base`.
+    /// An `enum` variable's choices are its `options`.
+    pub fn choices(&self, base: &Path) -> Result<Vec<String>> {
+        if self.kind == VariableKind::Enum {
+            return Ok(self.options.clone());
+        }
+        let Some(choices_from) = &self.choices_from else {
+            return Ok(Vec::new());
+        };
+
+        let (kind, value) = choices_from
+            .split_once(':')
+            .with_context(|| format!("Invalid choices_from '{choices_from}'"))?;
+
+        let want_dirs = match kind {
+            "options" => {
+                return Ok(value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|option| !option.is_empty())
+                    .map(String::from)
+                    .collect());
+            }
+            "dirs" => true,
+            "files" => false,
+            _ => bail!(
+                "Invalid choices_from '{choices_from}': expected 'dirs:', 'files:' or 'options:'"
+            ),
+        };
+
+        let glob = GlobBuilder::new(value)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob pattern: {value}"))?
+            .compile_matcher();
+
+        // Only descend as deep as the pattern can match
+        let max_depth = if value.contains("**") {
+            usize::MAX
+        } else {
+            value.split('/').count()
+        };
+
+        let mut choices = BTreeSet::new();
+        for entry in WalkDir::new(base)
+            .min_depth(1)
+            .max_depth(max_depth)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir() == want_dirs)
+        {
+            let Ok(rel_path) = entry.path().strip_prefix(base) else {
+                continue;
+            };
+            if glob.is_match(utils::glob_path(&rel_path.to_string_lossy())) {
+                choices.insert(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        Ok(choices.into_iter().collect())
+    }
+
+    /// Check a value against the variable's type, explaining what is wrong with it
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        match self.kind {
+            VariableKind::Text | VariableKind::Secret => Ok(()),
+            VariableKind::Int => {
+                let number: i64 = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("'{value}' is not a whole number"))?;
+                match (self.min, self.max) {
+                    (Some(min), _) if number < min => Err(format!("{number} is less than {min}")),
+                    (_, Some(max)) if number > max => {
+                        Err(format!("{number} is greater than {max}"))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            VariableKind::Enum if self.options.is_empty() => {
+                Err("the variable is an enum without options".to_string())
+            }
+            VariableKind::Enum if self.options.iter().any(|option| option == value) => Ok(()),
+            VariableKind::Enum => Err(format!(
+                "'{value}' is not one of {}",
+                self.options.join(", ")
+            )),
+        }
+    }
+}
+
+impl TemplateManifest {
+    /// Whether a variable is declared `type = "secret"`
+    pub fn is_secret(&self, var_name: &str) -> bool {
+        self.variables
+            .get(var_name)
+            .is_some_and(|spec| spec.kind == VariableKind::Secret)
+    }
+
+    /// Arrange the variables to prompt for into blocks: each group with its members
+    /// in declared order, followed by the ungrouped variables without a label
+    pub fn prompt_order(&self, variables: &[String]) -> Vec<(Option<&str>, Vec<String>)> {
+        let mut blocks = Vec::new();
+        let mut grouped = BTreeSet::new();
+
+        for (label, members) in &self.groups {
+            // Members may be named in any case, like variables elsewhere
+            let members: Vec<String> = members
+                .iter()
+                .map(|member| member.to_case(Case::Kebab))
+                .filter(|member| variables.contains(member) && grouped.insert(member.clone()))
+                .collect();
+            if !members.is_empty() {
+                blocks.push((Some(label.as_str()), members));
+            }
+        }
+
+        let rest: Vec<String> = variables
+            .iter()
+            .filter(|variable| !grouped.contains(variable.as_str()))
+            .cloned()
+            .collect();
+        if !rest.is_empty() {
+            blocks.push((None, rest));
+        }
+
+        blocks
+    }
+
+    /// Load the manifest from a template root, or return defaults if there is none,
+    /// adding the variables of the variables files
+    pub fn load(template_path: &Path) -> Result<Self> {
+        let manifest_path = template_path.join(MANIFEST_FILE);
+        let mut manifest: Self = if manifest_path.is_file() {
+            let content = fs::read_to_string(&manifest_path)
+                .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+            toml::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", manifest_path.display()))?
+        } else {
+            Self::default()
+        };
+
+        // Where each variable is defined, so that a second definition is caught
+        let mut defined_in: HashMap<String, &str> = manifest
+            .variables
+            .keys()
+            .map(|name| (name.clone(), MANIFEST_FILE))
+            .collect();
+        for file in VARS_FILES {
+            let path = template_path.join(file);
+            if !path.is_file() {
+                continue;
+            }
+            for (name, spec) in load_vars_file(&path)? {
+                if let Some(other) = defined_in.insert(name.clone(), file) {
+                    bail!(
+                        "Variable '{name}' is defined in both {other} and {file}; keep one of them"
+                    );
+                }
+                manifest.variables.insert(name, spec);
+            }
+        }
+
+        // Renamed files must still land in the output directory
+        let mut escaping: Vec<&String> = manifest
+            .renames
+            .values()
+            .filter(|destination| !template::is_contained_path(destination))
+            .collect();
+        escaping.sort();
+        if let Some(destination) = escaping.first() {
+            bail!(
+                "Invalid renames in {}: '{destination}' is not a relative path inside the output directory",
+                manifest_path.display()
+            );
+        }
+        Ok(manifest)
+    }
+}
+
+/// Read the per-variable settings of a variables file, in the format its extension
+/// names, keyed by kebab-case variable name
+fn load_vars_file(path: &Path) -> Result<HashMap<String, VariableSpec>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let variables: HashMap<String, VariableSpec> =
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&content).map_err(anyhow::Error::from),
+            Some("toml") => toml::from_str(&content).map_err(anyhow::Error::from),
+            Some("yaml" | "yml") => serde_norway::from_str(&content).map_err(anyhow::Error::from),
+            _ => bail!("Unsupported variables file {}", path.display()),
+        }
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let mut normalized = HashMap::new();
+    for (name, spec) in variables {
+        let kebab = name.to_case(Case::Kebab);
+        if normalized.insert(kebab.clone(), spec).is_some() {
+            bail!(
+                "Variable '{kebab}' is defined twice in {}, under different names",
+                path.display()
+            );
+        }
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(choices_from: &str) -> VariableSpec {
+        VariableSpec {
+            choices_from: Some(choices_from.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_truthy() {
+        for value in ["true", "yes", "1", "Y", "postgres"] {
+            assert!(is_truthy(value), "{value}");
+        }
+        for value in ["", "false", "No", "n", "OFF", "0", " "] {
+            assert!(!is_truthy(value), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn test_renames_stay_in_the_output() {
+        let dir = tempfile::tempdir().unwrap();
+        for destination in ["/tmp/victim/evil.txt", "../evil.txt", "docs/../../evil.txt"] {
+            fs::write(
+                dir.path().join(MANIFEST_FILE),
+                format!("[renames]\n\"a.txt\" = \"{destination}\"\n"),
+            )
+            .unwrap();
+            let err = TemplateManifest::load(dir.path()).unwrap_err().to_string();
+            assert!(err.contains("inside the output directory"), "{err}");
+        }
+
+        fs::write(
+            dir.path().join(MANIFEST_FILE),
+            "[renames]\n\"a.txt\" = \"docs/scf-name.txt\"\n",
+        )
+        .unwrap();
+        assert!(TemplateManifest::load(dir.path()).is_ok());
+    }
+
+    #[test]
+    fn test_vars_files() {
+        // Names are given in any case, and used in kebab-case
+        let formats = [
+            (
+                "scaffer.vars.json",
+                r#"{"api-key": {"type": "secret", "description": "Key"}, "db": {"required_if": "scf-use-db"}}"#,
+            ),
+            (
+                "scaffer.vars.toml",
+                "[api_key]\ntype = \"secret\"\ndescription = \"Key\"\n[db]\nrequired_if = \"scf-use-db\"\n",
+            ),
+            (
+                "scaffer.vars.yaml",
+                "ApiKey:\n  type: secret\n  description: Key\ndb:\n  required_if: scf-use-db\n",
+            ),
+            (
+                "scaffer.vars.yml",
+                "apiKey: {type: secret, description: Key}\nDB: {required_if: scf-use-db}\n",
+            ),
+        ];
+        for (file, content) in formats {
+            let dir = tempfile::tempdir().unwrap();
+            fs::write(dir.path().join(MANIFEST_FILE), "[variables.name]\n").unwrap();
+            fs::write(dir.path().join(file), content).unwrap();
+
+            let manifest = TemplateManifest::load(dir.path()).unwrap();
+            let mut names: Vec<&String> = manifest.variables.keys().collect();
+            names.sort();
+            assert_eq!(names, ["api-key", "db", "name"], "{file}");
+            assert!(manifest.is_secret("api-key"), "{file}");
+            assert_eq!(
+                manifest.variables["api-key"].description.as_deref(),
+                Some("Key")
+            );
+            assert_eq!(
+                manifest.variables["db"].required_if.as_deref(),
+                Some("scf-use-db")
+            );
+            assert!(is_manifest_file(Path::new(file)));
+        }
+
+        // A variable can only be defined once
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("scaffer.vars.json"), r#"{"name": {}}"#).unwrap();
+        fs::write(dir.path().join("scaffer.vars.toml"), "[name]\n").unwrap();
+        let err = TemplateManifest::load(dir.path()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("'name' is defined in both scaffer.vars.json and scaffer.vars.toml")
+        );
+        fs::remove_file(dir.path().join("scaffer.vars.toml")).unwrap();
+        fs::write(dir.path().join("scaffer.vars.yml"), "Name: {}\n").unwrap();
+        let err = TemplateManifest::load(dir.path()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("'name' is defined in both scaffer.vars.json and scaffer.vars.yml")
+        );
+        fs::write(
+            dir.path().join("scaffer.vars.json"),
+            r#"{"db-name": {}, "dbName": {}}"#,
+        )
+        .unwrap();
+        let err = TemplateManifest::load(dir.path()).unwrap_err();
+        assert!(
+            err.to_string().contains("'db-name' is defined twice"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_secret_variables() {
+        let manifest: TemplateManifest =
+            toml::from_str("[variables.api-key]\ntype = \"secret\"\n[variables.name]\n").unwrap();
+        assert!(manifest.is_secret("api-key"));
+        assert!(!manifest.is_secret("name"));
+        assert!(!manifest.is_secret("other"));
+    }
+
+    #[test]
+    fn test_prompt_order() {
+        let manifest: TemplateManifest = toml::from_str(
+            r#"
+            [groups]
+            Server = ["scheme", "host", "port"]
+            Auth = ["user", "unused"]
+            Database = ["db_name", "DbUser"]
+            "#,
+        )
+        .unwrap();
+
+        let variables: Vec<String> = [
+            "db-name", "db-user", "host", "name", "port", "scheme", "user",
+        ]
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+        let to_strings = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            manifest.prompt_order(&variables),
+            vec![
+                (Some("Server"), to_strings(&["scheme", "host", "port"])),
+                (Some("Auth"), to_strings(&["user"])),
+                (Some("Database"), to_strings(&["db-name", "db-user"])),
+                (None, to_strings(&["name"])),
+            ]
+        );
+        assert_eq!(
+            TemplateManifest::default().prompt_order(&variables),
+            vec![(None, variables.clone())]
+        );
+    }
+
+    #[test]
+    fn test_typed_variables() {
+        let port: VariableSpec = toml::from_str("type = \"int\"\nmin = 1\nmax = 65535").unwrap();
+        assert!(port.validate("8080").is_ok());
+        assert!(port.validate(" 1 ").is_ok());
+        assert_eq!(port.validate("0").unwrap_err(), "0 is less than 1");
+        assert_eq!(
+            port.validate("70000").unwrap_err(),
+            "70000 is greater than 65535"
+        );
+        assert_eq!(
+            port.validate("80a").unwrap_err(),
+            "'80a' is not a whole number"
+        );
+        let count: VariableSpec = toml::from_str("type = \"int\"").unwrap();
+        assert!(count.validate("-3").is_ok());
+
+        let db: VariableSpec =
+            toml::from_str("type = \"enum\"\noptions = [\"postgres\", \"sqlite\"]").unwrap();
+        assert!(db.validate("sqlite").is_ok());
+        assert_eq!(
+            db.validate("mysql").unwrap_err(),
+            "'mysql' is not one of postgres, sqlite"
+        );
+        assert_eq!(
+            db.choices(Path::new(".")).unwrap(),
+            vec!["postgres", "sqlite"]
+        );
+
+        assert!(spec("options:a,b").validate("anything").is_ok());
+    }
+
+    #[test]
+    fn test_variable_choices() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/auth/nested")).unwrap();
+        fs::create_dir_all(dir.path().join("src/billing")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "").unwrap();
+
+        assert_eq!(
+            spec("dirs:src/*").choices(dir.path()).unwrap(),
+            vec!["auth", "billing"]
+        );
+        assert_eq!(
+            spec("files:src/*.rs").choices(dir.path()).unwrap(),
+            vec!["main.rs"]
+        );
+        assert_eq!(
+            spec("options: small, large").choices(dir.path()).unwrap(),
+            vec!["small", "large"]
+        );
+        assert!(spec("dirs:lib/*").choices(dir.path()).unwrap().is_empty());
+        assert!(spec("paths:src").choices(dir.path()).is_err());
+        assert!(
+            VariableSpec::default()
+                .choices(dir.path())
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
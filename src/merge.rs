@@ -0,0 +1,355 @@
+use anyhow::{Context, Result, bail};
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::template::OutputEncoding;
+
+/// How an array of the generated JSON is merged into the existing one at the same place
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArrayStrategy {
+    /// The generated array takes the existing one's place
+    Replace,
+    /// The generated items are appended to the existing ones
+    Concat,
+    /// The generated items the existing array doesn't have yet are appended
+    #[default]
+    ConcatDedup,
+}
+
+/// A JSON value whose objects keep their keys in the order they were read, so that
+/// merging doesn't reorder an existing file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(serde_json::Number),
+    String(String),
+    Array(Vec<Json>),
+    Object(IndexMap<String, Json>),
+}
+
+/// Merge generated JSON into existing JSON: objects are merged key by key, arrays by
+/// `arrays`, and any other value is replaced by the generated one
+///
+/// Keys keep their existing order, with new keys after them.
+pub fn merge_json(existing: Json, generated: Json, arrays: ArrayStrategy) -> Json {
+    match (existing, generated) {
+        (Json::Object(mut existing), Json::Object(generated)) => {
+            for (key, value) in generated {
+                match existing.get_mut(&key) {
+                    Some(old) => {
+                        *old = merge_json(std::mem::replace(old, Json::Null), value, arrays)
+                    }
+                    None => {
+                        existing.insert(key, value);
+                    }
+                }
+            }
+            Json::Object(existing)
+        }
+        (Json::Array(mut existing), Json::Array(generated)) => match arrays {
+            ArrayStrategy::Replace => Json::Array(generated),
+            ArrayStrategy::Concat => {
+                existing.extend(generated);
+                Json::Array(existing)
+            }
+            ArrayStrategy::ConcatDedup => {
+                for item in generated {
+                    if !existing.contains(&item) {
+                        existing.push(item);
+                    }
+                }
+                Json::Array(existing)
+            }
+        },
+        (_, generated) => generated,
+    }
+}
+
+/// The content of an existing JSON file with generated JSON content merged into it,
+/// pretty-printed
+pub fn merge_into_file(path: &Path, generated: &[u8], arrays: ArrayStrategy) -> Result<Vec<u8>> {
+    let existing = read_decoded(path)?;
+    let existing: Json = serde_json::from_slice(&existing)
+        .with_context(|| format!("Can't merge into {}: not valid JSON", path.display()))?;
+    let generated: Json = serde_json::from_slice(generated).with_context(|| {
+        format!(
+            "Can't merge into {}: the generated content is not valid JSON",
+            path.display()
+        )
+    })?;
+
+    let mut merged = serde_json::to_string_pretty(&merge_json(existing, generated, arrays))?;
+    merged.push('\n');
+    Ok(merged.into_bytes())
+}
+
+/// The regions of a text delimited by `scaffer:begin <name>` and `scaffer:end <name>`
+/// lines, in any comment syntax: the byte range of the lines between the markers, by name
+fn marked_regions(text: &str) -> Result<HashMap<String, Range<usize>>> {
+    let marker = Regex::new(r"scaffer:(begin|end)\s+(\S+)").unwrap();
+    let mut regions = HashMap::new();
+    let mut open: Option<(&str, usize)> = None;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let Some(caps) = marker.captures(line) else {
+            continue;
+        };
+        let name = caps.get(2).unwrap().as_str();
+        match (&caps[1], open) {
+            ("begin", None) => open = Some((name, offset)),
+            ("begin", Some((outer, _))) => {
+                bail!("scaffer:begin {name} is inside the region {outer}; regions can't nest")
+            }
+            ("end", Some((begun, start))) if begun == name => {
+                if regions
+                    .insert(name.to_string(), start..line_start)
+                    .is_some()
+                {
+                    bail!("The region {name} is marked more than once");
+                }
+                open = None;
+            }
+            (_, Some((begun, _))) => bail!("scaffer:end {name} doesn't close the region {begun}"),
+            (_, None) => bail!("scaffer:end {name} has no scaffer:begin {name}"),
+        }
+    }
+    if let Some((name, _)) = open {
+        bail!("scaffer:begin {name} has no scaffer:end {name}");
+    }
+
+    Ok(regions)
+}
+
+/// Replace the marked regions of an existing text with the regions of the same name in
+/// generated text, keeping everything outside them (markers included) as it is
+///
+/// Returns `None` when the texts have no region in common. Regions only one of them
+/// has are left as they are.
+pub fn merge_marked_regions(existing: &str, generated: &str) -> Result<Option<String>> {
+    let generated_regions =
+        marked_regions(generated).context("Invalid markers in the generated content")?;
+    let mut regions: Vec<(Range<usize>, &str)> = marked_regions(existing)?
+        .into_iter()
+        .filter_map(|(name, range)| {
+            let replacement = generated_regions.get(&name)?;
+            Some((range, &generated[replacement.clone()]))
+        })
+        .collect();
+    if regions.is_empty() {
+        return Ok(None);
+    }
+    regions.sort_by_key(|(range, _)| range.start);
+
+    let mut merged = String::with_capacity(existing.len());
+    let mut end = 0;
+    for (range, replacement) in regions {
+        merged.push_str(&existing[end..range.start]);
+        merged.push_str(replacement);
+        end = range.end;
+    }
+    merged.push_str(&existing[end..]);
+    Ok(Some(merged))
+}
+
+/// The content of an existing file with the marked regions it shares with the
+/// generated content updated, or `None` when either isn't text or they share none
+pub fn merge_marked_file(path: &Path, generated: &[u8]) -> Result<Option<Vec<u8>>> {
+    let existing = read_decoded(path)?;
+    let (Ok(existing), Ok(generated)) = (String::from_utf8(existing), str::from_utf8(generated))
+    else {
+        return Ok(None);
+    };
+    let merged = merge_marked_regions(&existing, generated)
+        .with_context(|| format!("Can't update the marked regions of {}", path.display()))?;
+    Ok(merged.map(String::into_bytes))
+}
+
+/// Read an existing file as UTF-8, decoding it from the output encoding it may have
+/// been written in, so that the merged content can be encoded again
+fn read_decoded(path: &Path) -> Result<Vec<u8>> {
+    let content = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(OutputEncoding::decode(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(text: &str) -> Json {
+        serde_json::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_nested_merge() {
+        let existing = json(
+            r#"{
+                "compilerOptions": {"strict": false, "paths": {"@app/*": ["src/*"]}},
+                "include": ["src"]
+            }"#,
+        );
+        let generated = json(
+            r#"{
+                "compilerOptions": {"strict": true, "paths": {"@lib/*": ["lib/*"]}, "outDir": "dist"},
+                "include": ["src", "tests"]
+            }"#,
+        );
+
+        let merged = merge_json(existing, generated, ArrayStrategy::ConcatDedup);
+        assert_eq!(
+            merged,
+            json(
+                r#"{
+                    "compilerOptions": {
+                        "strict": true,
+                        "paths": {"@app/*": ["src/*"], "@lib/*": ["lib/*"]},
+                        "outDir": "dist"
+                    },
+                    "include": ["src", "tests"]
+                }"#
+            )
+        );
+        // Existing keys stay first
+        let Json::Object(merged) = merged else {
+            panic!("not an object");
+        };
+        let Some(Json::Object(options)) = merged.get("compilerOptions") else {
+            panic!("no compilerOptions");
+        };
+        let keys: Vec<&String> = options.keys().collect();
+        assert_eq!(keys, ["strict", "paths", "outDir"]);
+    }
+
+    #[test]
+    fn test_array_strategies() {
+        let merge = |arrays| merge_json(json(r#"{"a": [1, 2]}"#), json(r#"{"a": [2, 3]}"#), arrays);
+        assert_eq!(merge(ArrayStrategy::Replace), json(r#"{"a": [2, 3]}"#));
+        assert_eq!(merge(ArrayStrategy::Concat), json(r#"{"a": [1, 2, 2, 3]}"#));
+        assert_eq!(
+            merge(ArrayStrategy::ConcatDedup),
+            json(r#"{"a": [1, 2, 3]}"#)
+        );
+
+        // Values of different types are replaced whatever the strategy
+        assert_eq!(
+            merge_json(
+                json(r#"{"a": [1]}"#),
+                json(r#"{"a": "x", "b": null}"#),
+                ArrayStrategy::Concat
+            ),
+            json(r#"{"a": "x", "b": null}"#)
+        );
+    }
+
+    #[test]
+    fn test_merge_into_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("package.json");
+        fs::write(&path, r#"{"name": "app", "files": ["dist"]}"#).unwrap();
+
+        let merged =
+            merge_into_file(&path, br#"{"files": ["types"]}"#, ArrayStrategy::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(merged).unwrap(),
+            "{\n  \"name\": \"app\",\n  \"files\": [\n    \"dist\",\n    \"types\"\n  ]\n}\n"
+        );
+
+        // Keys aren't sorted, and numbers and nulls are written as they were
+        fs::write(&path, r#"{"version": 2, "main": null}"#).unwrap();
+        let merged = merge_into_file(&path, br#"{"id": 1.5}"#, ArrayStrategy::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(merged).unwrap(),
+            "{\n  \"version\": 2,\n  \"main\": null,\n  \"id\": 1.5\n}\n"
+        );
+
+        fs::write(&path, "not json").unwrap();
+        assert!(merge_into_file(&path, b"{}", ArrayStrategy::default()).is_err());
+    }
+
+    #[test]
+    fn test_merge_marked_regions() {
+        let existing = "\
+use crate::handlers;
+
+// scaffer:begin routes
+route(\"/old\");
+// scaffer:end routes
+
+fn custom() {}
+# scaffer:begin hand-kept
+kept
+# scaffer:end hand-kept
+";
+        let generated = "\
+// scaffer:begin routes
+route(\"/users\");
+route(\"/posts\");
+// scaffer:end routes
+<!-- scaffer:begin new -->
+not in the existing file
+<!-- scaffer:end new -->
+";
+        assert_eq!(
+            merge_marked_regions(existing, generated).unwrap().unwrap(),
+            "\
+use crate::handlers;
+
+// scaffer:begin routes
+route(\"/users\");
+route(\"/posts\");
+// scaffer:end routes
+
+fn custom() {}
+# scaffer:begin hand-kept
+kept
+# scaffer:end hand-kept
+"
+        );
+
+        // Without markers in common, the file is left to the usual overwrite handling
+        assert_eq!(
+            merge_marked_regions("plain text\n", generated).unwrap(),
+            None
+        );
+        assert_eq!(
+            merge_marked_regions(existing, "plain text\n").unwrap(),
+            None
+        );
+
+        // Broken markers are errors
+        for broken in [
+            "// scaffer:begin a\n",
+            "// scaffer:end a\n",
+            "// scaffer:begin a\n// scaffer:end b\n",
+            "// scaffer:begin a\n// scaffer:begin b\n// scaffer:end b\n// scaffer:end a\n",
+            "// scaffer:begin a\n// scaffer:end a\n// scaffer:begin a\n// scaffer:end a\n",
+        ] {
+            assert!(merge_marked_regions(broken, generated).is_err(), "{broken}");
+        }
+    }
+
+    #[test]
+    fn test_merge_marked_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mod.rs");
+        fs::write(&path, "mine\n// scaffer:begin x\nold\n// scaffer:end x\n").unwrap();
+
+        let merged =
+            merge_marked_file(&path, b"// scaffer:begin x\nnew\n// scaffer:end x\n").unwrap();
+        assert_eq!(
+            merged.as_deref(),
+            Some(&b"mine\n// scaffer:begin x\nnew\n// scaffer:end x\n"[..])
+        );
+        assert_eq!(merge_marked_file(&path, b"\xff\x00").unwrap(), None);
+    }
+}
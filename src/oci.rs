@@ -0,0 +1,357 @@
+use anyhow::{Context, Result, bail};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{http, utils};
+
+/// URL scheme of templates distributed as OCI artifacts
+pub const SCHEME: &str = "oci://";
+
+/// Environment variable holding a bearer token for private registries
+pub const TOKEN_ENV: &str = "SCAFFER_REGISTRY_TOKEN";
+
+/// Manifest media types requested from the registry
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.manifest.v1+json, application/vnd.docker.distribution.manifest.v2+json";
+
+/// An artifact parsed from an `oci://registry/repository[:tag|@digest]` URL
+#[derive(Debug, PartialEq)]
+pub struct Reference {
+    pub registry: String,
+    pub repository: String,
+    /// Tag or digest; `latest` when the URL names neither
+    pub reference: String,
+}
+
+impl Reference {
+    pub fn parse(url: &str) -> Result<Self> {
+        let invalid =
+            || format!("Invalid OCI reference '{url}' (expected oci://registry/repository[:tag])");
+
+        let rest = url.strip_prefix(SCHEME).with_context(invalid)?;
+        let (registry, path) = rest
+            .split_once('/')
+            .filter(|(registry, path)| !registry.is_empty() && !path.is_empty())
+            .with_context(invalid)?;
+
+        let (repository, reference) = match path.split_once('@') {
+            Some((repository, digest)) => (repository, digest),
+            // A tag can only follow a colon in the last path segment
+            None => match path.rsplit_once(':') {
+                Some((repository, tag)) if !tag.contains('/') => (repository, tag),
+                _ => (path, "latest"),
+            },
+        };
+        if repository.is_empty() || reference.is_empty() {
+            bail!(invalid());
+        }
+
+        Ok(Reference {
+            registry: registry.to_string(),
+            repository: repository.to_string(),
+            reference: reference.to_string(),
+        })
+    }
+
+    /// Base URL of the repository in the registry API
+    fn api_url(&self) -> String {
+        // Local registries are usually served without TLS
+        let scheme =
+            if self.registry.starts_with("localhost") || self.registry.starts_with("127.0.0.1") {
+                "http"
+            } else {
+                "https"
+            };
+        format!("{scheme}://{}/v2/{}", self.registry, self.repository)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    media_type: String,
+    digest: String,
+}
+
+/// How the content of a layer is unpacked
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LayerFormat {
+    Tar,
+    TarGzip,
+    Zip,
+}
+
+impl LayerFormat {
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/vnd.oci.image.layer.v1.tar" | "application/x-tar" => Some(Self::Tar),
+            "application/vnd.oci.image.layer.v1.tar+gzip"
+            | "application/vnd.docker.image.rootfs.diff.tar.gzip"
+            | "application/gzip"
+            | "application/x-gzip" => Some(Self::TarGzip),
+            "application/zip" | "application/x-zip-compressed" => Some(Self::Zip),
+            _ => None,
+        }
+    }
+}
+
+/// Pull the template layer of an OCI artifact and extract it below `work_dir`
+///
+/// The first layer that is a tar, gzipped tar or zip archive is used. Returns the
+/// directory the layer was extracted to, with the SHA-256 of the layer.
+pub fn pull(url: &str, work_dir: &Path) -> Result<(PathBuf, String)> {
+    let reference = Reference::parse(url)?;
+    let mut client = Client {
+        token: std::env::var(TOKEN_ENV)
+            .ok()
+            .filter(|token| !token.is_empty()),
+    };
+
+    let response = client.get(
+        &format!("{}/manifests/{}", reference.api_url(), reference.reference),
+        Some(MANIFEST_MEDIA_TYPES),
+    )?;
+    let manifest: Manifest = serde_json::from_slice(response.as_bytes())
+        .with_context(|| format!("Failed to parse the OCI manifest of {url}"))?;
+
+    let Some((layer, format)) = manifest.layers.iter().find_map(|layer| {
+        LayerFormat::from_media_type(&layer.media_type).map(|format| (layer, format))
+    }) else {
+        let media_types: Vec<&str> = manifest
+            .layers
+            .iter()
+            .map(|layer| layer.media_type.as_str())
+            .collect();
+        bail!(
+            "OCI artifact {url} has no tar or zip layer (layer media types: {})",
+            if media_types.is_empty() {
+                "none".to_string()
+            } else {
+                media_types.join(", ")
+            }
+        );
+    };
+
+    let blob = client.get(
+        &format!("{}/blobs/{}", reference.api_url(), layer.digest),
+        None,
+    )?;
+    let sha256 = utils::sha256_hex(blob.as_bytes());
+    verify_digest(&layer.digest, &sha256).with_context(|| format!("Not extracting {url}"))?;
+
+    let extract_dir = work_dir.join("extracted");
+    fs::create_dir_all(&extract_dir).context("Failed to create extraction directory")?;
+    match format {
+        LayerFormat::Tar => tar::Archive::new(blob.as_bytes())
+            .unpack(&extract_dir)
+            .context("Failed to extract template tar")?,
+        LayerFormat::TarGzip => tar::Archive::new(GzDecoder::new(blob.as_bytes()))
+            .unpack(&extract_dir)
+            .context("Failed to extract template tar")?,
+        LayerFormat::Zip => {
+            let zip_path = work_dir.join("template.zip");
+            fs::write(&zip_path, blob.as_bytes()).context("Failed to write template zip file")?;
+            utils::extract_zip(&zip_path, &extract_dir)
+                .context("Failed to extract template zip")?;
+        }
+    }
+
+    Ok((extract_dir, sha256))
+}
+
+/// Check that a layer's `sha256:<hex>` digest matches the SHA-256 of what was
+/// downloaded for it
+fn verify_digest(digest: &str, sha256: &str) -> Result<()> {
+    let Some(expected) = digest.strip_prefix("sha256:") else {
+        bail!("Unsupported layer digest {digest} (only sha256 can be verified)");
+    };
+    if !expected.eq_ignore_ascii_case(sha256) {
+        bail!("The downloaded layer doesn't match its digest {digest} (got sha256:{sha256})");
+    }
+    Ok(())
+}
+
+/// Registry requests, authenticated with a bearer token once one is known
+struct Client {
+    token: Option<String>,
+}
+
+impl Client {
+    fn get(&mut self, url: &str, accept: Option<&str>) -> Result<minreq::Response> {
+        let mut response = self.send(url, accept)?;
+
+        // Without a configured token, public registries hand out anonymous pull tokens
+        if response.status_code == 401 && self.token.is_none() {
+            if let Some(challenge) = response.headers.get("www-authenticate") {
+                self.token = Some(fetch_anonymous_token(challenge)?);
+                response = self.send(url, accept)?;
+            }
+        }
+
+        match response.status_code {
+            200 => Ok(response),
+            401 | 403 => bail!(
+                "Access denied to {url}: HTTP {} (set {TOKEN_ENV} for private registries)",
+                response.status_code
+            ),
+            status => bail!("Failed to fetch {url}: HTTP {status}"),
+        }
+    }
+
+    fn send(&self, url: &str, accept: Option<&str>) -> Result<minreq::Response> {
+        let mut request = http::get(url)?;
+        if let Some(accept) = accept {
+            request = request.with_header("Accept", accept);
+        }
+        if let Some(token) = &self.token {
+            request = request.with_header("Authorization", format!("Bearer {token}"));
+        }
+        request
+            .send()
+            .with_context(|| format!("Failed to fetch {url}"))
+    }
+}
+
+/// Request an anonymous token from the auth service named by a `WWW-Authenticate` challenge
+fn fetch_anonymous_token(challenge: &str) -> Result<String> {
+    #[derive(Deserialize)]
+    struct TokenResponse {
+        token: Option<String>,
+        access_token: Option<String>,
+    }
+
+    let Some(params) = parse_bearer_challenge(challenge) else {
+        bail!("Unsupported registry authentication: {challenge}");
+    };
+    let Some(realm) = params.get("realm") else {
+        bail!("Registry authentication challenge has no realm: {challenge}");
+    };
+
+    let query: Vec<String> = ["service", "scope"]
+        .iter()
+        .filter_map(|key| params.get(*key).map(|value| format!("{key}={value}")))
+        .collect();
+    let token_url = if query.is_empty() {
+        realm.clone()
+    } else {
+        format!("{realm}?{}", query.join("&"))
+    };
+
+    let response = http::get(&token_url)?
+        .send()
+        .with_context(|| format!("Failed to fetch registry token from {realm}"))?;
+    if response.status_code != 200 {
+        bail!(
+            "Failed to fetch registry token from {realm}: HTTP {}",
+            response.status_code
+        );
+    }
+
+    let tokens: TokenResponse = serde_json::from_slice(response.as_bytes())
+        .with_context(|| format!("Failed to parse registry token from {realm}"))?;
+    tokens
+        .token
+        .or(tokens.access_token)
+        .with_context(|| format!("No token in response from {realm}"))
+}
+
+/// Parse the parameters of a `Bearer realm="...",service="...",scope="..."` challenge
+fn parse_bearer_challenge(challenge: &str) -> Option<HashMap<String, String>> {
+    let (scheme, params) = challenge.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+
+    let mut parsed = HashMap::new();
+    let mut rest = params.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_lowercase();
+        let value = value.trim_start();
+        // Values are quoted strings, which may contain commas (e.g. a scope with
+        // several actions), or bare tokens
+        let (value, remainder) = match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        parsed.insert(key, value.to_string());
+        rest = remainder;
+    }
+
+    Some(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reference() {
+        assert_eq!(
+            Reference::parse("oci://ghcr.io/acme/templates/rust-api:1.2").unwrap(),
+            Reference {
+                registry: "ghcr.io".to_string(),
+                repository: "acme/templates/rust-api".to_string(),
+                reference: "1.2".to_string(),
+            }
+        );
+
+        let reference = Reference::parse("oci://localhost:5000/rust-api").unwrap();
+        assert_eq!(reference.registry, "localhost:5000");
+        assert_eq!(reference.reference, "latest");
+        assert_eq!(reference.api_url(), "http://localhost:5000/v2/rust-api");
+
+        let reference = Reference::parse("oci://ghcr.io/acme/app@sha256:abc").unwrap();
+        assert_eq!(reference.repository, "acme/app");
+        assert_eq!(reference.reference, "sha256:abc");
+
+        assert!(Reference::parse("oci://ghcr.io").is_err());
+        assert!(Reference::parse("oci://ghcr.io/app:").is_err());
+    }
+
+    #[test]
+    fn test_layer_formats() {
+        assert_eq!(
+            LayerFormat::from_media_type("application/vnd.oci.image.layer.v1.tar+gzip"),
+            Some(LayerFormat::TarGzip)
+        );
+        assert_eq!(
+            LayerFormat::from_media_type("application/zip"),
+            Some(LayerFormat::Zip)
+        );
+        assert_eq!(
+            LayerFormat::from_media_type("application/vnd.oci.image.config.v1+json"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_verify_digest() {
+        let sha256 = utils::sha256_hex(b"layer");
+        assert!(verify_digest(&format!("sha256:{sha256}"), &sha256).is_ok());
+
+        let err = verify_digest(&format!("sha256:{}", "0".repeat(64)), &sha256).unwrap_err();
+        assert!(err.to_string().contains("doesn't match"), "{err}");
+        assert!(verify_digest(&format!("sha512:{sha256}"), &sha256).is_err());
+    }
+
+    #[test]
+    fn test_parse_bearer_challenge() {
+        let params = parse_bearer_challenge(
+            r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:acme/app:pull,push""#,
+        )
+        .unwrap();
+        assert_eq!(params["realm"], "https://ghcr.io/token");
+        assert_eq!(params["service"], "ghcr.io");
+        assert_eq!(params["scope"], "repository:acme/app:pull,push");
+
+        assert!(parse_bearer_challenge(r#"Basic realm="registry""#).is_none());
+    }
+}
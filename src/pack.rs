@@ -0,0 +1,171 @@
+use anyhow::{Context, Result, bail};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::manifest;
+use crate::utils;
+
+/// File at a template root listing what `scaffer pack` leaves out, one glob per line
+pub const IGNORE_FILE: &str = ".scafferignore";
+
+/// Metadata that is never packed
+const METADATA: &[&str] = &[".git", ".DS_Store", "Thumbs.db", IGNORE_FILE];
+
+/// Zip a template directory into `archive`, with the template's files at the root of
+/// the archive
+///
+/// Metadata and files matched by the template's `.scafferignore` are left out, as are
+/// the manifest and variables files unless `include_manifest` is set. Returns the
+/// number of files packed.
+pub fn pack(template_dir: &Path, archive: &Path, include_manifest: bool) -> Result<usize> {
+    let ignored = ignore_patterns(template_dir)?;
+    let archive_path = fs::canonicalize(archive.parent().unwrap_or(Path::new(".")))
+        .ok()
+        .zip(archive.file_name())
+        .map(|(dir, name)| dir.join(name));
+
+    let mut walked = Vec::new();
+    let walker = WalkDir::new(template_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            let Ok(rel_path) = e.path().strip_prefix(template_dir) else {
+                return false;
+            };
+            e.depth() == 0
+                || !(METADATA.contains(&e.file_name().to_string_lossy().as_ref())
+                    || ignored.is_match(rel_path)
+                    || (!include_manifest && manifest::is_manifest_file(rel_path)))
+        });
+    for entry in walker {
+        let entry = entry.context("Failed to read template directory")?;
+        // The archive itself, when written into the template
+        if entry.depth() == 0 || fs::canonicalize(entry.path()).ok() == archive_path {
+            continue;
+        }
+        walked.push(entry);
+    }
+
+    // Unix permissions are kept so that scripts stay executable
+    let mut entries = Vec::new();
+    let mut files = 0;
+    for entry in walked {
+        let rel_path = entry.path().strip_prefix(template_dir)?;
+        let name = utils::glob_path(&rel_path.to_string_lossy());
+        let mode = utils::unix_mode(entry.path());
+
+        if entry.file_type().is_dir() {
+            entries.push(utils::ArchiveEntry {
+                name: format!("{name}/"),
+                content: Vec::new(),
+                mode,
+            });
+        } else if entry.file_type().is_file() {
+            let content = fs::read(entry.path())
+                .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+            entries.push(utils::ArchiveEntry {
+                name,
+                content,
+                mode,
+            });
+            files += 1;
+        }
+    }
+    utils::write_zip(archive, &entries)?;
+
+    Ok(files)
+}
+
+/// Read a template's `.scafferignore`
+///
+/// Each line is a glob; `#` starts a comment. As in `.gitignore`, a pattern without a
+/// slash matches at any depth, a leading slash anchors it to the template root, and
+/// a matched directory is left out with everything in it.
+fn ignore_patterns(template_dir: &Path) -> Result<GlobSet> {
+    let path = template_dir.join(IGNORE_FILE);
+    let mut builder = GlobSetBuilder::new();
+    if !path.is_file() {
+        return Ok(builder.build()?);
+    }
+
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('!') {
+            bail!("Negated pattern '{line}' in {IGNORE_FILE} is not supported");
+        }
+
+        let pattern = line.trim_end_matches('/');
+        let pattern = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{pattern}"),
+        };
+        let glob = GlobBuilder::new(&pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid pattern '{line}' in {IGNORE_FILE}"))?;
+        builder.add(glob);
+    }
+
+    builder
+        .build()
+        .with_context(|| format!("Invalid patterns in {IGNORE_FILE}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_pack_leaves_out_ignored_files_and_metadata() {
+        let template = tempfile::tempdir().unwrap();
+        for (path, content) in [
+            ("src/ScfName.rs", "struct ScfName;"),
+            ("src/notes.tmp", ""),
+            ("docs/build/out.html", ""),
+            ("build/cache.bin", ""),
+            (".git/HEAD", "ref: refs/heads/main"),
+            (manifest::MANIFEST_FILE, "prefix = \"scf\""),
+            (IGNORE_FILE, "# scratch files\n*.tmp\n/build/\n"),
+        ] {
+            let path = template.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        let names = |include_manifest: bool| {
+            let out = tempfile::tempdir().unwrap();
+            let archive = out.path().join("template.zip");
+            let files = pack(template.path(), &archive, include_manifest).unwrap();
+
+            let zip = ZipArchive::new(fs::File::open(&archive).unwrap()).unwrap();
+            let mut names: Vec<String> = zip.file_names().map(str::to_string).collect();
+            names.sort();
+            (files, names)
+        };
+
+        assert_eq!(
+            names(false),
+            (
+                2,
+                vec![
+                    "docs/".to_string(),
+                    "docs/build/".to_string(),
+                    "docs/build/out.html".to_string(),
+                    "src/".to_string(),
+                    "src/ScfName.rs".to_string(),
+                ]
+            )
+        );
+        let (files, names) = names(true);
+        assert_eq!(files, 3);
+        assert!(names.contains(&manifest::MANIFEST_FILE.to_string()));
+    }
+}
@@ -0,0 +1,68 @@
+use similar::TextDiff;
+
+/// Render the change of one file as a git-apply compatible diff
+///
+/// `old` is `None` for a file that does not exist yet. Returns an empty string when
+/// the content is unchanged.
+pub fn file_diff(path: &str, old: Option<&str>, new: &str) -> String {
+    if old == Some(new) {
+        return String::new();
+    }
+
+    let mut diff = format!("diff --git a/{path} b/{path}\n");
+    let (old_name, old_text) = match old {
+        Some(old) => (format!("a/{path}"), old),
+        None => {
+            diff.push_str("new file mode 100644\n");
+            // An empty new file has no hunks, only the header
+            if new.is_empty() {
+                return diff;
+            }
+            ("/dev/null".to_string(), "")
+        }
+    };
+
+    diff.push_str(
+        &TextDiff::from_lines(old_text, new)
+            .unified_diff()
+            .header(&old_name, &format!("b/{path}"))
+            .to_string(),
+    );
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_diff() {
+        assert_eq!(
+            file_diff("src/lib.rs", None, "one\ntwo\n"),
+            "diff --git a/src/lib.rs b/src/lib.rs\n\
+             new file mode 100644\n\
+             --- /dev/null\n\
+             +++ b/src/lib.rs\n\
+             @@ -0,0 +1,2 @@\n\
+             +one\n\
+             +two\n"
+        );
+
+        assert_eq!(
+            file_diff("a.txt", Some("one\ntwo\n"), "one\n2\n"),
+            "diff --git a/a.txt b/a.txt\n\
+             --- a/a.txt\n\
+             +++ b/a.txt\n\
+             @@ -1,2 +1,2 @@\n \
+             one\n\
+             -two\n\
+             +2\n"
+        );
+
+        assert_eq!(file_diff("a.txt", Some("same"), "same"), "");
+        assert_eq!(
+            file_diff("empty", None, ""),
+            "diff --git a/empty b/empty\nnew file mode 100644\n"
+        );
+    }
+}
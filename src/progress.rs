@@ -0,0 +1,82 @@
+use std::io::Write;
+
+/// Width of the bar, in characters
+const BAR_WIDTH: usize = 30;
+
+/// A progress bar on stderr, redrawn in place after each processed file
+///
+/// The bar is cleared before anything else is printed, so it is only ever visible
+/// while a file is being worked on, and it clears itself when dropped.
+pub struct Progress {
+    total: usize,
+    done: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    /// A bar counting up to `total`, drawn only when `enabled`
+    pub fn new(total: usize, enabled: bool) -> Self {
+        Progress {
+            total,
+            done: 0,
+            enabled: enabled && total > 0,
+        }
+    }
+
+    /// Count one more item done and redraw the bar
+    pub fn advance(&mut self) {
+        self.done = (self.done + 1).min(self.total);
+        if self.enabled {
+            eprint!("\r{}", self.line());
+            let _ = std::io::stderr().flush();
+        }
+    }
+
+    /// Remove the bar from the terminal, until the next `advance`
+    pub fn clear(&self) {
+        if self.enabled {
+            eprint!("\r\x1b[K");
+            let _ = std::io::stderr().flush();
+        }
+    }
+
+    fn line(&self) -> String {
+        let filled = BAR_WIDTH * self.done / self.total;
+        format!(
+            "[{}{}] {}/{} files",
+            "=".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            self.done,
+            self.total
+        )
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_line() {
+        let mut progress = Progress::new(4, false);
+        assert_eq!(progress.line(), format!("[{}] 0/4 files", " ".repeat(30)));
+
+        progress.advance();
+        progress.advance();
+        assert_eq!(
+            progress.line(),
+            format!("[{}{}] 2/4 files", "=".repeat(15), " ".repeat(15))
+        );
+
+        for _ in 0..5 {
+            progress.advance();
+        }
+        assert_eq!(progress.line(), format!("[{}] 4/4 files", "=".repeat(30)));
+    }
+}
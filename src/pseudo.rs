@@ -0,0 +1,212 @@
+use convert_case::{Case, Casing};
+use std::collections::HashMap;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The time `timestamp` gives with a seed, unless `SOURCE_DATE_EPOCH` is set:
+/// 2000-01-01 00:00:00 UTC
+const SEEDED_TIME: u64 = 946_684_800;
+
+/// Built-in variables whose values are generated instead of prompted for
+///
+/// Random values come from a generator that can be seeded, so that regenerating
+/// with the same seed and variables produces byte-identical output.
+pub struct PseudoVariables {
+    rng: fastrand::Rng,
+    /// The values produced so far, by variable name
+    values: HashMap<String, String>,
+    /// Seconds since the Unix epoch that `timestamp` is taken from
+    time: u64,
+    template_name: Option<String>,
+    template_source: Option<String>,
+}
+
+impl PseudoVariables {
+    /// Create pseudo-variables seeded with `seed`, or from entropy when `None`
+    pub fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => fastrand::Rng::with_seed(seed),
+            None => fastrand::Rng::new(),
+        };
+
+        // SOURCE_DATE_EPOCH is the usual way to pin timestamps for reproducible output
+        let time = match env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            Some(time) => time,
+            None if seed.is_some() => SEEDED_TIME,
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        };
+
+        Self {
+            rng,
+            values: HashMap::new(),
+            time,
+            template_name: None,
+            template_source: None,
+        }
+    }
+
+    /// Record the template being generated, for `template-name` and `template-source`
+    pub fn set_template(&mut self, name: &str, source: &str) {
+        self.template_name = Some(name.to_string());
+        self.template_source = Some(source.to_string());
+    }
+
+    /// Produce a value for a pseudo-variable, or `None` if `name` is not one; a
+    /// variable asked for again gets the same value
+    ///
+    /// - `uuid` - a random (version 4) UUID
+    /// - `random` - eight random lowercase hex digits
+    /// - `timestamp` - the current UTC time as `YYYYMMDDhhmmss`, fixed with a seed
+    /// - `template-name` - the template as it was named when generating
+    /// - `template-source` - the resolved template directory or URL
+    /// - `os` - the host operating system: `windows`, `linux`, `macos`, ...
+    pub fn value(&mut self, name: &str) -> Option<String> {
+        if let Some(value) = self.values.get(name) {
+            return Some(value.clone());
+        }
+
+        let value = match name {
+            "uuid" => self.uuid(),
+            "random" => format!("{:08x}", self.rng.u32(..)),
+            "timestamp" => compact_utc(self.time),
+            "template-name" => self.template_name.clone()?,
+            "template-source" => self.template_source.clone()?,
+            "os" => env::consts::OS.to_string(),
+            _ => return None,
+        };
+        self.values.insert(name.to_string(), value.clone());
+        Some(value)
+    }
+
+    /// The values produced so far, by variable name
+    pub fn values(&self) -> &HashMap<String, String> {
+        &self.values
+    }
+
+    fn uuid(&mut self) -> String {
+        let mut bytes = [0u8; 16];
+        self.rng.fill(&mut bytes);
+
+        // Set version 4 and the RFC 4122 variant
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+        let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+}
+
+/// The value conditions see for `env-<name>`: the environment variable `<NAME>`
+/// (`env-my-var` reads `MY_VAR`), empty when it is unset; `None` for other names
+///
+/// These are only for conditions, so environment values never end up in files, logs
+/// or the variables handed on to chained templates.
+pub fn env_value(name: &str) -> Option<String> {
+    let var = name.strip_prefix("env-")?;
+    Some(env::var(var.to_case(Case::UpperSnake)).unwrap_or_default())
+}
+
+/// A time given in seconds since the Unix epoch as `YYYYMMDDhhmmss` in UTC
+fn compact_utc(secs: u64) -> String {
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01, in eras of 400 years starting in March
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}{:02}{:02}{:02}",
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_values_are_reproducible() {
+        let mut first = PseudoVariables::new(Some(42));
+        let mut second = PseudoVariables::new(Some(42));
+
+        let uuid = first.value("uuid").unwrap();
+        assert_eq!(uuid, second.value("uuid").unwrap());
+        assert_eq!(first.value("uuid").unwrap(), uuid);
+        assert_eq!(first.value("random"), second.value("random"));
+        if env::var_os("SOURCE_DATE_EPOCH").is_none() {
+            assert_eq!(first.value("timestamp").unwrap(), "20000101000000");
+        }
+
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert!(first.value("name").is_none());
+    }
+
+    #[test]
+    fn test_compact_utc() {
+        assert_eq!(compact_utc(0), "19700101000000");
+        assert_eq!(compact_utc(1_704_110_400), "20240101120000");
+        // Leap day and the last second of a year
+        assert_eq!(compact_utc(951_827_696), "20000229123456");
+        assert_eq!(compact_utc(1_735_689_599), "20241231235959");
+
+        let timestamp = PseudoVariables::new(None).value("timestamp").unwrap();
+        assert_eq!(timestamp.len(), 14);
+        assert!(timestamp.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn test_host_values() {
+        let mut pseudo = PseudoVariables::new(None);
+        assert_eq!(pseudo.value("os").unwrap(), env::consts::OS);
+
+        assert!(pseudo.value("env-path").is_none());
+
+        assert_eq!(
+            env_value("env-path").unwrap(),
+            env::var("PATH").unwrap_or_default()
+        );
+        assert_eq!(env_value("env-scaffer-test-unset").as_deref(), Some(""));
+        assert!(env_value("os").is_none());
+    }
+
+    #[test]
+    fn test_template_values() {
+        let mut pseudo = PseudoVariables::new(None);
+        assert!(pseudo.value("template-name").is_none());
+
+        pseudo.set_template("rust-api", "https://example.com/rust-api.zip");
+        assert_eq!(pseudo.value("template-name").unwrap(), "rust-api");
+        assert_eq!(
+            pseudo.value("template-source").unwrap(),
+            "https://example.com/rust-api.zip"
+        );
+    }
+}
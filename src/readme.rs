@@ -0,0 +1,95 @@
+use crate::generator::TemplateGenerator;
+use crate::manifest::{self, TemplateManifest};
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use std::fs;
+use std::io::IsTerminal;
+
+const BOLD: &str = "\x1b[1m";
+const UNDERLINE: &str = "\x1b[4m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Print the readme a template's manifest points at, rendered for the terminal unless
+/// `raw` or stdout isn't one
+pub fn show(template: &str, raw: bool) -> Result<()> {
+    let template_path = TemplateGenerator::new()?.find_template(template)?;
+    let manifest = TemplateManifest::load(&template_path)?;
+    let Some(readme) = &manifest.readme else {
+        bail!(
+            "Template '{template}' has no readme (set `readme` in its {})",
+            manifest::MANIFEST_FILE
+        );
+    };
+
+    let readme_path = template_path.join(readme);
+    let content = fs::read_to_string(&readme_path)
+        .with_context(|| format!("Failed to read readme {}", readme_path.display()))?;
+
+    if raw || !std::io::stdout().is_terminal() {
+        print!("{content}");
+    } else {
+        print!("{}", render(&content));
+    }
+    Ok(())
+}
+
+/// Style markdown with terminal escapes: headings, bold and code stand out, list
+/// bullets become dots and code blocks lose their fences
+fn render(markdown: &str) -> String {
+    let code = Regex::new(r"`([^`]+)`").unwrap();
+    let bold = Regex::new(r"\*\*([^*]+)\*\*").unwrap();
+
+    let mut rendered = String::new();
+    let mut in_code_block = false;
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            rendered.push_str(&format!("    {CYAN}{line}{RESET}\n"));
+            continue;
+        }
+
+        let heading_level = line.chars().take_while(|c| *c == '#').count();
+        let line = if heading_level > 0 && line[heading_level..].starts_with(' ') {
+            let text = line[heading_level..].trim();
+            if heading_level == 1 {
+                format!("{BOLD}{UNDERLINE}{text}{RESET}")
+            } else {
+                format!("{BOLD}{text}{RESET}")
+            }
+        } else {
+            let indent = line.len() - line.trim_start().len();
+            let line = match line.trim_start().strip_prefix(['-', '*']) {
+                Some(item) if item.starts_with(' ') => format!("{}•{item}", &line[..indent]),
+                _ => line.to_string(),
+            };
+            let line = bold.replace_all(&line, format!("{BOLD}$1{RESET}"));
+            code.replace_all(&line, format!("{CYAN}$1{RESET}"))
+                .into_owned()
+        };
+        rendered.push_str(&line);
+        rendered.push('\n');
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let markdown = "# Service\n\nRun `make` **first**.\n\n## Files\n  - one\n```sh\nls\n```\n";
+        assert_eq!(
+            render(markdown),
+            format!(
+                "{BOLD}{UNDERLINE}Service{RESET}\n\nRun {CYAN}make{RESET} {BOLD}first{RESET}.\n\n\
+                 {BOLD}Files{RESET}\n  • one\n    {CYAN}ls{RESET}\n"
+            )
+        );
+    }
+}
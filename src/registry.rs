@@ -0,0 +1,233 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::http;
+use crate::utils;
+
+/// How long a fetched registry index is reused before fetching again
+const REGISTRY_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A remote index of templates, e.g. `{"templates": {"name": {"url": ..., "description": ...}}}`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RegistryIndex {
+    #[serde(default)]
+    pub templates: HashMap<String, RegistryEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Load a registry index, using the cached copy while it is fresh
+///
+/// If fetching fails, a stale cached copy is used when available.
+pub fn load_index(registry_url: &str) -> Result<RegistryIndex> {
+    load_index_with(
+        registry_url,
+        cache_path(registry_url).as_deref(),
+        fetch_index,
+    )
+}
+
+/// [`load_index`] with the index cached at `cache_path` and fetched by `fetch`
+fn load_index_with(
+    registry_url: &str,
+    cache_path: Option<&Path>,
+    fetch: impl Fn(&str) -> Result<RegistryIndex>,
+) -> Result<RegistryIndex> {
+    if let Some(path) = cache_path {
+        if is_fresh(path) {
+            if let Ok(index) = read_cached(path) {
+                return Ok(index);
+            }
+        }
+    }
+
+    match fetch(registry_url) {
+        Ok(index) => {
+            if let Some(path) = cache_path {
+                // Caching is best-effort
+                let _ = write_cached(path, &index);
+            }
+            Ok(index)
+        }
+        Err(err) => match cache_path.map(read_cached) {
+            Some(Ok(index)) => Ok(index),
+            _ => Err(err),
+        },
+    }
+}
+
+/// Fetch the index of every registry again, on at most `jobs` threads at once,
+/// returning for each registry the number of templates it lists or why it failed
+pub fn update_all(registry_urls: &[String], jobs: usize) -> Vec<Result<usize>> {
+    utils::parallel_map(registry_urls, jobs, |registry_url| {
+        let index = refresh_index(
+            registry_url,
+            cache_path(registry_url).as_deref(),
+            fetch_index,
+        )?;
+        Ok(index.templates.len())
+    })
+}
+
+/// Fetch a registry's index by `fetch` and replace its copy at `cache_path`, however
+/// fresh that is; a failed fetch leaves the cached copy as it was
+fn refresh_index(
+    registry_url: &str,
+    cache_path: Option<&Path>,
+    fetch: impl Fn(&str) -> Result<RegistryIndex>,
+) -> Result<RegistryIndex> {
+    let index = fetch(registry_url)?;
+    if let Some(path) = cache_path {
+        write_cached(path, &index)?;
+    }
+    Ok(index)
+}
+
+/// Whether a registry's index comes from the cache, as `--explain` reports it
+pub fn cache_state(registry_url: &str) -> &'static str {
+    match cache_path(registry_url) {
+        Some(path) if is_fresh(&path) => "cached index is fresh and used",
+        Some(path) if path.is_file() => "cached index is stale and fetched again",
+        _ => "not cached, fetched",
+    }
+}
+
+fn fetch_index(registry_url: &str) -> Result<RegistryIndex> {
+    let response = http::get(registry_url)?
+        .send()
+        .with_context(|| format!("Failed to fetch registry {registry_url}"))?;
+
+    if response.status_code != 200 {
+        bail!(
+            "Failed to fetch registry {registry_url}: HTTP {}",
+            response.status_code
+        );
+    }
+
+    serde_json::from_slice(response.as_bytes())
+        .with_context(|| format!("Failed to parse registry {registry_url}"))
+}
+
+/// Location of the cached index for a registry URL
+fn cache_path(registry_url: &str) -> Option<PathBuf> {
+    let file_name = format!("{}.json", utils::stable_key([registry_url.as_bytes()]));
+
+    dirs::cache_dir().map(|dir| dir.join("scaffer").join("registries").join(file_name))
+}
+
+fn is_fresh(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < REGISTRY_CACHE_TTL)
+}
+
+fn read_cached(path: &Path) -> Result<RegistryIndex> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn write_cached(path: &Path, index: &RegistryIndex) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    let content = serde_json::to_string_pretty(index).context("Failed to serialize registry")?;
+
+    // Written aside and moved into place, so that concurrent runs never read a
+    // partly written index
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to create a file in {}", dir.display()))?;
+    file.write_all(content.as_bytes())
+        .and_then(|()| file.flush())
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    file.persist(path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_registry_index() {
+        let json = r#"{
+            "templates": {
+                "react": {"url": "https://example.com/react.zip", "description": "React app"},
+                "rust-lib": {"url": "https://example.com/rust-lib.zip"}
+            }
+        }"#;
+
+        let index: RegistryIndex = serde_json::from_str(json).unwrap();
+        assert_eq!(index.templates.len(), 2);
+        assert_eq!(
+            index.templates["react"].description.as_deref(),
+            Some("React app")
+        );
+        assert!(index.templates["rust-lib"].description.is_none());
+    }
+
+    #[test]
+    fn test_refresh_replaces_the_cached_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("registry.json");
+        let url = "https://registry.example.com/index.json";
+        let serving = |name: &'static str| {
+            move |_: &str| {
+                Ok(RegistryIndex {
+                    templates: HashMap::from([(
+                        name.to_string(),
+                        RegistryEntry {
+                            url: format!("https://example.com/{name}.zip"),
+                            description: None,
+                        },
+                    )]),
+                })
+            }
+        };
+        let offline = |_: &str| -> Result<RegistryIndex> { bail!("offline") };
+        let names = |index: RegistryIndex| index.templates.into_keys().collect::<Vec<_>>();
+
+        // Fetched once, then used from the cache while it is fresh
+        let index = load_index_with(url, Some(&cache), serving("old")).unwrap();
+        assert_eq!(names(index), ["old"]);
+        let index = load_index_with(url, Some(&cache), serving("new")).unwrap();
+        assert_eq!(names(index), ["old"]);
+
+        // A refresh fetches the fresh index again and replaces the cached copy
+        refresh_index(url, Some(&cache), serving("new")).unwrap();
+        assert_eq!(names(read_cached(&cache).unwrap()), ["new"]);
+        let index = load_index_with(url, Some(&cache), offline).unwrap();
+        assert_eq!(names(index), ["new"]);
+
+        // So does one of an expired index, which would also be fetched without it
+        fs::File::options()
+            .write(true)
+            .open(&cache)
+            .unwrap()
+            .set_modified(SystemTime::now() - 2 * REGISTRY_CACHE_TTL)
+            .unwrap();
+        assert!(!is_fresh(&cache));
+        refresh_index(url, Some(&cache), serving("newer")).unwrap();
+        assert!(is_fresh(&cache));
+        assert_eq!(names(read_cached(&cache).unwrap()), ["newer"]);
+
+        // A refresh that fails keeps the cached copy
+        assert!(refresh_index(url, Some(&cache), offline).is_err());
+        assert_eq!(names(read_cached(&cache).unwrap()), ["newer"]);
+    }
+}
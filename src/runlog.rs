@@ -0,0 +1,108 @@
+use crate::generator::{GenerateOptions, GenerationReport};
+
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Placeholder written instead of variable values when redacting
+pub const REDACTED: &str = "<redacted>";
+
+/// Append one JSON line describing a generation run to the log file
+///
+/// The file is flushed to disk before returning so the record survives
+/// even when the run itself failed.
+pub fn append(
+    log_file: &Path,
+    report: &GenerationReport,
+    options: &GenerateOptions,
+    error: Option<&anyhow::Error>,
+) -> Result<()> {
+    let record = record(report, options, error);
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .with_context(|| format!("Failed to open log file: {}", log_file.display()))?;
+
+    writeln!(file, "{record}")
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("Failed to write log file: {}", log_file.display()))
+}
+
+fn record(
+    report: &GenerationReport,
+    options: &GenerateOptions,
+    error: Option<&anyhow::Error>,
+) -> serde_json::Value {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let variables: serde_json::Map<String, serde_json::Value> = report
+        .variables
+        .iter()
+        .map(|(name, value)| {
+            let redacted = options.log_redact || report.secrets.contains(name);
+            let value = if redacted { REDACTED } else { value };
+            (name.clone(), json!(value))
+        })
+        .collect();
+
+    json!({
+        "timestamp": timestamp,
+        "dry_run": options.dry_run,
+        "template": report.template,
+        "template_source": report.template_source,
+        "variables": variables,
+        "output_dir": options.output_dir().ok().and_then(|dir| fs::canonicalize(dir).ok()),
+        "actions": report.actions,
+        "hashes": report.hashes,
+        "status": if error.is_some() { "error" } else { "ok" },
+        "error": error.map(|err| format!("{err:#}")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_redacts_variables() {
+        let mut report = GenerationReport::default();
+        report
+            .variables
+            .insert("token".to_string(), "s3cret".to_string());
+
+        let options = GenerateOptions {
+            dry_run: true,
+            log_redact: true,
+            ..Default::default()
+        };
+        let error = anyhow::anyhow!("boom");
+        let record = record(&report, &options, Some(&error));
+
+        assert_eq!(record["variables"]["token"], REDACTED);
+        assert_eq!(record["dry_run"], true);
+        assert_eq!(record["status"], "error");
+        assert_eq!(record["error"], "boom");
+    }
+
+    #[test]
+    fn test_record_leaves_out_secrets() {
+        let mut report = GenerationReport::default();
+        for (name, value) in [("name", "app"), ("api-key", "s3cret")] {
+            report.variables.insert(name.to_string(), value.to_string());
+        }
+        report.secrets.insert("api-key".to_string());
+
+        let record = record(&report, &GenerateOptions::default(), None);
+        assert_eq!(record["variables"]["name"], "app");
+        assert_eq!(record["variables"]["api-key"], REDACTED);
+        assert!(!record.to_string().contains("s3cret"));
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::DirEntry;
+
+use crate::utils;
+
+/// Variables found in a local template, valid while its fingerprint is unchanged
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedScan {
+    fingerprint: String,
+    variables: Vec<String>,
+}
+
+/// Fingerprint a template from the paths, sizes and modification times of its entries
+///
+/// Any added, removed or modified file changes the fingerprint. Entries outside the
+/// template, such as shared partials, are fingerprinted by their full path. The
+/// scaffer version and the `token_forms` of the variable syntax in effect are included
+/// so that a change in variable detection invalidates old scans.
+pub fn fingerprint(
+    template_path: &Path,
+    entries: impl Iterator<Item = DirEntry>,
+    token_forms: &[String],
+) -> String {
+    let mut files: Vec<(PathBuf, u64, u128)> = entries
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |age| age.as_nanos());
+            let rel_path = entry
+                .path()
+                .strip_prefix(template_path)
+                .unwrap_or(entry.path());
+            Some((rel_path.to_path_buf(), metadata.len(), modified))
+        })
+        .collect();
+    files.sort();
+
+    let files: Vec<Vec<u8>> = files
+        .into_iter()
+        .map(|(rel_path, len, modified)| {
+            let mut part = rel_path.into_os_string().into_encoded_bytes();
+            part.push(0);
+            part.extend(len.to_le_bytes());
+            part.extend(modified.to_le_bytes());
+            part
+        })
+        .collect();
+    // An empty part ends the token forms, so that they can't run into the files
+    let parts = [env!("CARGO_PKG_VERSION").as_bytes()]
+        .into_iter()
+        .chain(token_forms.iter().map(|form| form.as_bytes()))
+        .chain([b"".as_slice()])
+        .chain(files.iter().map(Vec::as_slice));
+    utils::stable_key(parts)
+}
+
+/// Load the cached variables of a template if they match the fingerprint
+pub fn load(template_path: &Path, fingerprint: &str) -> Option<HashSet<String>> {
+    let content = fs::read_to_string(cache_path(template_path)?).ok()?;
+    let cached: CachedScan = serde_json::from_str(&content).ok()?;
+    (cached.fingerprint == fingerprint).then(|| cached.variables.into_iter().collect())
+}
+
+/// Remember the variables of a template (best-effort)
+pub fn store(template_path: &Path, fingerprint: &str, variables: &HashSet<String>) {
+    let Some(path) = cache_path(template_path) else {
+        return;
+    };
+
+    let mut variables: Vec<String> = variables.iter().cloned().collect();
+    variables.sort();
+    let cached = CachedScan {
+        fingerprint: fingerprint.to_string(),
+        variables,
+    };
+
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(content) = serde_json::to_string(&cached) {
+        let _ = fs::write(path, content);
+    }
+}
+
+/// Location of the cached scan for a template directory
+fn cache_path(template_path: &Path) -> Option<PathBuf> {
+    let template_path = fs::canonicalize(template_path).ok()?;
+    let file_name = format!(
+        "{}.json",
+        utils::stable_key([template_path.as_os_str().as_encoded_bytes()])
+    );
+
+    dirs::cache_dir().map(|dir| dir.join("scaffer").join("scan").join(file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use walkdir::WalkDir;
+
+    fn fingerprint_of(dir: &Path) -> String {
+        fingerprint(
+            dir,
+            WalkDir::new(dir).into_iter().filter_map(|e| e.ok()),
+            &["ScfName".to_string()],
+        )
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "ScfName").unwrap();
+
+        let original = fingerprint_of(dir.path());
+        assert_eq!(fingerprint_of(dir.path()), original);
+
+        fs::write(dir.path().join("a.txt"), "ScfName ScfOther").unwrap();
+        let modified = fingerprint_of(dir.path());
+        assert_ne!(modified, original);
+
+        fs::write(dir.path().join("b.txt"), "").unwrap();
+        assert_ne!(fingerprint_of(dir.path()), modified);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::generator::{FileOutcome, GenerateOptions, GenerationReport};
+
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+/// Write a compact JSON summary of a generation run's counts to `stats_file`
+///
+/// Failed runs are summarized too, counting the files handled before the failure.
+pub fn write(
+    stats_file: &Path,
+    report: &GenerationReport,
+    options: &GenerateOptions,
+    error: Option<&anyhow::Error>,
+) -> Result<()> {
+    let stats = stats(report, options, error);
+    let content = serde_json::to_string_pretty(&stats).context("Failed to serialize stats")?;
+
+    fs::write(stats_file, content + "\n")
+        .with_context(|| format!("Failed to write stats file: {}", stats_file.display()))
+}
+
+fn stats(
+    report: &GenerationReport,
+    options: &GenerateOptions,
+    error: Option<&anyhow::Error>,
+) -> serde_json::Value {
+    json!({
+        "status": if error.is_some() { "error" } else { "ok" },
+        "dry_run": options.dry_run,
+        "created": report.count(FileOutcome::Created),
+        "overwritten": report.count(FileOutcome::Overwritten)
+            + report.count(FileOutcome::AutoOverwritten),
+        "skipped": report.count(FileOutcome::Skipped),
+        "unchanged": report.count(FileOutcome::Unchanged),
+        "errors": usize::from(error.is_some()),
+        "bytes_written": report.bytes_written,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::FileAction;
+
+    #[test]
+    fn test_stats_counts_outcomes() {
+        let mut report = GenerationReport {
+            bytes_written: 42,
+            ..Default::default()
+        };
+        for outcome in [
+            FileOutcome::Created,
+            FileOutcome::Created,
+            FileOutcome::Overwritten,
+            FileOutcome::AutoOverwritten,
+            FileOutcome::Unchanged,
+        ] {
+            report.actions.push(FileAction {
+                source: "src".into(),
+                destination: "dest".to_string(),
+                outcome,
+            });
+        }
+
+        assert_eq!(
+            stats(&report, &GenerateOptions::default(), None),
+            json!({
+                "status": "ok",
+                "dry_run": false,
+                "created": 2,
+                "overwritten": 2,
+                "skipped": 0,
+                "unchanged": 1,
+                "errors": 0,
+                "bytes_written": 42,
+            })
+        );
+
+        let error = anyhow::anyhow!("boom");
+        let failed = stats(&report, &GenerateOptions::default(), Some(&error));
+        assert_eq!(failed["status"], "error");
+        assert_eq!(failed["errors"], 1);
+    }
+}
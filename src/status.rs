@@ -0,0 +1,273 @@
+use crate::generator::{GenerateOptions, TemplateGenerator, Verbosity};
+use crate::golden;
+use crate::utils;
+
+use anyhow::{Context, Result, bail};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// How a generated file differs, locally and in the current template, from what was
+/// generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Unchanged,
+    /// Locally: edited since generation; in the template: regenerating changes it
+    Modified,
+    /// Locally: deleted since generation; in the template: no longer generated
+    Deleted,
+    /// Generated by the current template but not by the recorded run
+    Added,
+}
+
+impl Change {
+    fn code(self) -> char {
+        match self {
+            Change::Unchanged => ' ',
+            Change::Modified => 'M',
+            Change::Deleted => 'D',
+            Change::Added => 'A',
+        }
+    }
+}
+
+/// The status of one file: its local change and its change in the template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStatus {
+    pub path: String,
+    pub local: Change,
+    pub template: Change,
+}
+
+impl fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{} {}",
+            self.local.code(),
+            self.template.code(),
+            self.path
+        )
+    }
+}
+
+/// The last successful generation into `output_dir` recorded in a run log
+struct RecordedRun {
+    template: String,
+    template_source: String,
+    variables: HashMap<String, String>,
+    hashes: BTreeMap<String, String>,
+}
+
+/// Report how the files generated into `output_dir`, as recorded in `log_file`, have
+/// changed locally and how regenerating them from the current template would change them
+pub fn status(log_file: &Path, output_dir: &Path) -> Result<()> {
+    let run = last_run(log_file, output_dir)?;
+    println!("Template: {} ({})", run.template, run.template_source);
+
+    // Regenerate with the recorded variables into a scratch directory
+    let fresh_dir = tempfile::TempDir::new().context("Failed to create temporary directory")?;
+    let options = GenerateOptions {
+        verbosity: Verbosity::Quiet,
+        output_dir: Some(fresh_dir.path().to_path_buf()),
+        no_input: true,
+        no_history: true,
+        ..Default::default()
+    };
+    TemplateGenerator::new()?
+        .generate_with_variables(Some(run.template_source.clone()), run.variables, &options)
+        .context("Failed to regenerate the template with the recorded variables")?;
+
+    let statuses = compare(&run.hashes, output_dir, fresh_dir.path())?;
+    if statuses.is_empty() {
+        println!("Up to date: no local changes and nothing new in the template");
+        return Ok(());
+    }
+
+    println!("(first column: local changes; second column: changes in the template)");
+    for status in &statuses {
+        println!("{status}");
+    }
+    Ok(())
+}
+
+/// Find the last successful, non-dry run into `output_dir` in a run log
+fn last_run(log_file: &Path, output_dir: &Path) -> Result<RecordedRun> {
+    let content = fs::read_to_string(log_file)
+        .with_context(|| format!("Failed to read log file: {}", log_file.display()))?;
+    let output_dir = fs::canonicalize(output_dir)
+        .with_context(|| format!("Failed to resolve {}", output_dir.display()))?;
+
+    let record = content
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .find(|record| {
+            record["status"] == "ok"
+                && record["dry_run"] == false
+                && record["output_dir"]
+                    .as_str()
+                    .is_some_and(|dir| Path::new(dir) == output_dir)
+        })
+        .with_context(|| {
+            format!(
+                "No successful run into {} recorded in {}",
+                output_dir.display(),
+                log_file.display()
+            )
+        })?;
+
+    let Some(hashes) = record["hashes"].as_object() else {
+        bail!("The recorded run has no file hashes; generate again with --log-file");
+    };
+    let variables: HashMap<String, String> = serde_json::from_value(record["variables"].clone())
+        .context("Invalid variables in the recorded run")?;
+    if variables
+        .values()
+        .any(|value| value == crate::runlog::REDACTED)
+    {
+        bail!("The recorded run's variables were redacted (--log-redact or secret variables)");
+    }
+
+    Ok(RecordedRun {
+        template: record["template"].as_str().unwrap_or_default().to_string(),
+        template_source: record["template_source"]
+            .as_str()
+            .context("The recorded run has no template source")?
+            .to_string(),
+        variables,
+        hashes: hashes
+            .iter()
+            .filter_map(|(path, hash)| Some((path.clone(), hash.as_str()?.to_string())))
+            .collect(),
+    })
+}
+
+/// Compare the recorded hashes of generated files with the files in `output_dir` and
+/// with a fresh generation in `fresh_dir`, returning the files that changed
+fn compare(
+    recorded: &BTreeMap<String, String>,
+    output_dir: &Path,
+    fresh_dir: &Path,
+) -> Result<Vec<FileStatus>> {
+    let hash_of = |path: &Path| -> Result<Option<String>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content =
+            fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Some(utils::content_hash(&content)))
+    };
+    let change = |hash: Option<String>, recorded: &str| match hash {
+        None => Change::Deleted,
+        Some(hash) if hash == recorded => Change::Unchanged,
+        Some(_) => Change::Modified,
+    };
+
+    let mut statuses = Vec::new();
+    for (path, hash) in recorded {
+        let local = change(hash_of(&output_dir.join(path))?, hash);
+        let template = change(hash_of(&fresh_dir.join(path))?, hash);
+        if local != Change::Unchanged || template != Change::Unchanged {
+            statuses.push(FileStatus {
+                path: path.clone(),
+                local,
+                template,
+            });
+        }
+    }
+
+    for rel_path in golden::relative_files(fresh_dir)? {
+        let path = utils::glob_path(&rel_path.to_string_lossy());
+        if recorded.contains_key(&path) {
+            continue;
+        }
+        // A file of the same name may already exist in the project
+        let local = match hash_of(&output_dir.join(&rel_path))? {
+            Some(hash) if Some(hash.clone()) != hash_of(&fresh_dir.join(&rel_path))? => {
+                Change::Modified
+            }
+            _ => Change::Unchanged,
+        };
+        statuses.push(FileStatus {
+            path,
+            local,
+            template: Change::Added,
+        });
+    }
+
+    statuses.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_reports_local_and_template_changes() {
+        let output = tempfile::tempdir().unwrap();
+        let fresh = tempfile::tempdir().unwrap();
+        let hash = |content: &str| utils::content_hash(content.as_bytes());
+
+        let recorded: BTreeMap<String, String> = [
+            ("same.txt", "same"),
+            ("edited.txt", "original"),
+            ("upstream.txt", "v1"),
+            ("both.txt", "v1"),
+            ("deleted.txt", "x"),
+            ("dropped.txt", "x"),
+        ]
+        .into_iter()
+        .map(|(path, content)| (path.to_string(), hash(content)))
+        .collect();
+
+        for (dir, files) in [
+            (
+                output.path(),
+                vec![
+                    ("same.txt", "same"),
+                    ("edited.txt", "edited"),
+                    ("upstream.txt", "v1"),
+                    ("both.txt", "mine"),
+                    ("dropped.txt", "x"),
+                ],
+            ),
+            (
+                fresh.path(),
+                vec![
+                    ("same.txt", "same"),
+                    ("edited.txt", "original"),
+                    ("upstream.txt", "v2"),
+                    ("both.txt", "v2"),
+                    ("deleted.txt", "x"),
+                    ("src/new.txt", "new"),
+                ],
+            ),
+        ] {
+            for (path, content) in files {
+                let path = dir.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, content).unwrap();
+            }
+        }
+
+        let statuses: Vec<String> = compare(&recorded, output.path(), fresh.path())
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                "MM both.txt",
+                "D  deleted.txt",
+                " D dropped.txt",
+                "M  edited.txt",
+                " A src/new.txt",
+                " M upstream.txt",
+            ]
+        );
+    }
+}
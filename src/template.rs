@@ -1,40 +1,594 @@
-use convert_case::{Case, Casing};
+use anyhow::{Context, Result, bail};
+use convert_case::{Case, Casing, Converter};
 use regex::Regex;
+use serde::Deserialize;
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::filters;
+
+/// Maximum nesting of `{{include "..."}}` directives before giving up
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Directive that, as a comment on the first line of a file, turns off substitution
+/// for the rest of the file
+const VERBATIM_DIRECTIVE: &str = "scaffer:verbatim";
+
+/// Comment delimiters the verbatim directive may be written in, as (start, end)
+const COMMENT_STYLES: &[(&str, &str)] = &[
+    ("//", ""),
+    ("#", ""),
+    ("--", ""),
+    (";", ""),
+    ("%", ""),
+    ("'", ""),
+    ("/*", "*/"),
+    ("<!--", "-->"),
+    ("{#", "#}"),
+    ("{{!--", "--}}"),
+    ("(*", "*)"),
+];
+
+/// The text after a first-line `scaffer:verbatim` directive comment (e.g.
+/// `// scaffer:verbatim` or `<!-- scaffer:verbatim -->`), if the text starts with one
+pub fn verbatim_body(text: &str) -> Option<&str> {
+    let (first_line, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let first_line = first_line.trim();
+    let is_directive = COMMENT_STYLES.iter().any(|(start, end)| {
+        first_line
+            .strip_prefix(start)
+            .and_then(|comment| comment.strip_suffix(end))
+            .is_some_and(|comment| comment.trim() == VERBATIM_DIRECTIVE)
+    });
+    is_directive.then_some(rest)
+}
+
+/// Split text into its `---` fenced frontmatter, fences included, and the body after
+/// it; `None` when the text doesn't start with a frontmatter block
+pub fn split_frontmatter(text: &str) -> Option<(&str, &str)> {
+    let mut lines = text.split_inclusive('\n');
+    if lines.next()?.trim_end() != "---" {
+        return None;
+    }
+    let mut end = text.find('\n')? + 1;
+    for line in lines {
+        end += line.len();
+        if matches!(line.trim_end(), "---" | "...") {
+            return Some(text.split_at(end));
+        }
+    }
+    None
+}
+
+/// Case normalization applied to generated path components after substitution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilenameCase {
+    /// Keep names exactly as substituted
+    #[default]
+    Preserve,
+    /// Lowercase every path component
+    Lower,
+    /// Convert every dot-separated part of a path component to kebab-case
+    Kebab,
+}
+
+impl FromStr for FilenameCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(FilenameCase::Preserve),
+            "lower" => Ok(FilenameCase::Lower),
+            "kebab" => Ok(FilenameCase::Kebab),
+            _ => Err(format!(
+                "invalid filename case '{s}' (expected preserve, lower or kebab)"
+            )),
+        }
+    }
+}
+
+impl FilenameCase {
+    /// Apply the normalization to a single path component
+    fn apply(self, component: &str) -> String {
+        match self {
+            FilenameCase::Preserve => component.to_string(),
+            FilenameCase::Lower => component.to_lowercase(),
+            FilenameCase::Kebab => component
+                .split('.')
+                .map(|part| part.to_case(Case::Kebab))
+                .collect::<Vec<_>>()
+                .join("."),
+        }
+    }
+}
+
+/// Encoding generated text files are written in, whatever the template file's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputEncoding {
+    /// UTF-8 without a byte order mark
+    #[default]
+    Utf8,
+    /// UTF-8 with a byte order mark
+    Utf8Bom,
+    /// UTF-16 little-endian with a byte order mark
+    Utf16le,
+}
+
+impl FromStr for OutputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "utf8" => Ok(OutputEncoding::Utf8),
+            "utf8-bom" => Ok(OutputEncoding::Utf8Bom),
+            "utf16le" => Ok(OutputEncoding::Utf16le),
+            _ => Err(format!(
+                "invalid output encoding '{s}' (expected utf8, utf8-bom or utf16le)"
+            )),
+        }
+    }
+}
+
+impl OutputEncoding {
+    /// Encode generated content, replacing any byte order mark it has. Binary content
+    /// (not valid UTF-8, or containing NUL bytes) is returned as it is.
+    pub fn encode(self, content: Vec<u8>) -> Vec<u8> {
+        if content.contains(&0) {
+            return content;
+        }
+        let Ok(text) = str::from_utf8(&content) else {
+            return content;
+        };
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        match self {
+            OutputEncoding::Utf8 => text.as_bytes().to_vec(),
+            OutputEncoding::Utf8Bom => ["\u{feff}", text].concat().into_bytes(),
+            OutputEncoding::Utf16le => [0xfeff]
+                .into_iter()
+                .chain(text.encode_utf16())
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+        }
+    }
+
+    /// Decode content written in any of the encodings, as told by its byte order
+    /// mark, back to UTF-8 without one. Other content is returned as it is.
+    pub fn decode(content: Vec<u8>) -> Vec<u8> {
+        if let Some(text) = content.strip_prefix(b"\xef\xbb\xbf") {
+            return text.to_vec();
+        }
+        match content.strip_prefix(b"\xff\xfe") {
+            Some(text) if text.len() % 2 == 0 => {
+                let units = text
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+                char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .map_or(content, String::into_bytes)
+            }
+            _ => content,
+        }
+    }
+}
+
+/// Which parts of a template variables are substituted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubstitutionScope {
+    /// File and directory names as well as file contents
+    #[default]
+    All,
+    /// Only names; contents are copied verbatim
+    NamesOnly,
+    /// Only contents; files keep their template names
+    ContentOnly,
+}
+
+/// Directory at a template root holding partials: files that are only included,
+/// never generated
+pub const PARTIALS_DIR: &str = "_partials";
+
+/// Prefix of template variable tokens unless configured otherwise
+pub const DEFAULT_PREFIX: &str = "scf";
+
+/// Words that read as tokens with the default prefix but are never variables: the
+/// units standard cubic feet per minute, hour and day
+const DEFAULT_DENIED_TOKENS: &[&str] = &["scfm", "scfh", "scfd"];
+
+/// A case style template variables can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseStyle {
+    /// `ScfMyvar`
+    Pascal,
+    /// `SCF_MYVAR`
+    UpperSnake,
+    /// `SCF-MYVAR`
+    UpperKebab,
+    /// `SCF.MYVAR`
+    UpperDot,
+    /// `scf_myvar`
+    Snake,
+    /// `scf-myvar`
+    Kebab,
+    /// `scf.myvar`
+    Dot,
+    /// `scfmyvar`
+    Flat,
+    /// `SCFMYVAR`
+    UpperFlat,
+}
+
+impl CaseStyle {
+    /// All styles, in the order substitution tries them
+    pub const ALL: [CaseStyle; 9] = [
+        CaseStyle::Pascal,
+        CaseStyle::UpperSnake,
+        CaseStyle::UpperKebab,
+        CaseStyle::UpperDot,
+        CaseStyle::Snake,
+        CaseStyle::Kebab,
+        CaseStyle::Dot,
+        CaseStyle::Flat,
+        CaseStyle::UpperFlat,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            CaseStyle::Pascal => "pascal",
+            CaseStyle::UpperSnake => "upper-snake",
+            CaseStyle::UpperKebab => "upper-kebab",
+            CaseStyle::UpperDot => "upper-dot",
+            CaseStyle::Snake => "snake",
+            CaseStyle::Kebab => "kebab",
+            CaseStyle::Dot => "dot",
+            CaseStyle::Flat => "flat",
+            CaseStyle::UpperFlat => "upper-flat",
+        }
+    }
+}
+
+impl FromStr for CaseStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match CaseStyle::ALL.into_iter().find(|style| style.name() == s) {
+            Some(style) => Ok(style),
+            None => bail!(
+                "unknown case style '{s}' (expected one of: {})",
+                CaseStyle::ALL.map(CaseStyle::name).join(", ")
+            ),
+        }
+    }
+}
+
+/// Check a variable token prefix, returning it in lowercase
+///
+/// The prefix must be ASCII letters and digits starting with a letter, so that it
+/// can be written in every case style.
+pub fn parse_prefix(prefix: &str) -> Result<String> {
+    let prefix = prefix.trim();
+    if !prefix.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !prefix.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        bail!(
+            "invalid prefix '{prefix}': expected ASCII letters and digits starting with a letter"
+        );
+    }
+    Ok(prefix.to_lowercase())
+}
+
+/// Parse a list of case style names, which must not be empty
+pub fn parse_cases<S: AsRef<str>>(names: &[S]) -> Result<Vec<CaseStyle>> {
+    let cases = names
+        .iter()
+        .map(|name| name.as_ref().trim().parse())
+        .collect::<Result<Vec<CaseStyle>>>()?;
+    if cases.is_empty() {
+        bail!("at least one case style is required");
+    }
+    Ok(cases)
+}
+
+/// One of the case styles a template variable can be written in, e.g. `ScfMyvar` or `scf-myvar`
+#[derive(Debug, Clone)]
+struct VariableStyle {
+    prefix: String,
+    case: Case,
+    /// Words are separated by dots instead of the case's dashes
+    dotted: bool,
+    /// Matches a whole token in this style, capturing everything after the prefix
+    pattern: Regex,
+    /// Matches a token followed by a filter (`scf-name|ident:rust`), capturing the
+    /// name, the filter and its argument
+    filter_pattern: Regex,
+    /// Matches the start of a further token concatenated onto the first one
+    /// (e.g. the `ScfS` in `ScfModuleScfSubmodule`); the match ends with the
+    /// token's first character. Flat styles have none, as their tokens can't
+    /// be told apart.
+    joiner: Option<Regex>,
+}
+
+impl VariableStyle {
+    /// Build the matchers for a case style with the given (lowercase) token prefix
+    fn new(style: CaseStyle, prefix: &str) -> Self {
+        let upper = prefix.to_uppercase();
+        let pascal = format!("{}{}", &upper[..1], &prefix[1..]);
+
+        // (token prefix, case, dotted, characters of the name after its first one)
+        let (prefix, case, dotted, name_chars) = match style {
+            CaseStyle::Pascal => (pascal, Case::Pascal, false, "a-zA-Z0-9"),
+            CaseStyle::UpperSnake => (format!("{upper}_"), Case::UpperSnake, false, "A-Z0-9_"),
+            CaseStyle::UpperKebab => (format!("{upper}-"), Case::UpperKebab, false, "A-Z0-9-"),
+            CaseStyle::UpperDot => (format!("{upper}."), Case::UpperKebab, true, r"A-Z0-9\."),
+            CaseStyle::Snake => (format!("{prefix}_"), Case::Snake, false, "a-z0-9_"),
+            CaseStyle::Kebab => (format!("{prefix}-"), Case::Kebab, false, "a-z0-9-"),
+            CaseStyle::Dot => (format!("{prefix}."), Case::Kebab, true, r"a-z0-9\."),
+            CaseStyle::Flat => (prefix.to_string(), Case::Flat, false, "a-z0-9"),
+            CaseStyle::UpperFlat => (upper.clone(), Case::UpperFlat, false, "A-Z0-9"),
+        };
+        let first_char = if matches!(case, Case::Snake | Case::Kebab | Case::Flat) {
+            "a-z"
+        } else {
+            "A-Z"
+        };
+
+        let escaped = regex::escape(&prefix);
+        let pattern = format!(r"\b{escaped}([{first_char}][{name_chars}]*)\b");
+        let filter_pattern =
+            format!(r"\b{escaped}([{first_char}][{name_chars}]*)\|([a-z]+)(?::([a-z]+))?\b");
+        // A further token starts with the separator, if any, and the prefix again
+        let joiner = match style {
+            CaseStyle::Flat | CaseStyle::UpperFlat => None,
+            CaseStyle::Pascal => Some(format!("{escaped}[{first_char}]")),
+            _ => {
+                let separator = regex::escape(&prefix[prefix.len() - 1..]);
+                Some(format!("{separator}{escaped}[{first_char}]"))
+            }
+        };
+
+        Self {
+            prefix,
+            case,
+            dotted,
+            pattern: Regex::new(&pattern).unwrap(),
+            filter_pattern: Regex::new(&filter_pattern).unwrap(),
+            joiner: joiner.map(|joiner| Regex::new(&joiner).unwrap()),
+        }
+    }
+
+    /// Convert a variable name or value to this style, without the prefix
+    fn convert(&self, text: &str) -> String {
+        let converted = text.to_case(self.case);
+        if self.dotted {
+            converted.replace('-', ".")
+        } else {
+            converted
+        }
+    }
+
+    /// Convert a variable value to this style, writing the words that are `acronyms`
+    /// (lowercase) in upper case wherever the style capitalizes words
+    fn convert_value(&self, value: &str, acronyms: &[String]) -> String {
+        if acronyms.is_empty() {
+            return self.convert(value);
+        }
+
+        // Split with the same boundaries as `to_case`, keeping the original words
+        // next to their converted forms
+        let words = Converter::new().set_delim(" ").convert(value);
+        let cased = Converter::new()
+            .to_case(self.case)
+            .set_delim(" ")
+            .convert(value);
+        let converted: Vec<String> = words
+            .split(' ')
+            .zip(cased.split(' '))
+            .map(|(word, cased)| {
+                let is_acronym = acronyms.contains(&word.to_lowercase());
+                if is_acronym && cased.starts_with(|c: char| c.is_uppercase()) {
+                    word.to_uppercase()
+                } else {
+                    cased.to_string()
+                }
+            })
+            .collect();
+
+        let converted = converted.join(self.case.delim());
+        if self.dotted {
+            converted.replace('-', ".")
+        } else {
+            converted
+        }
+    }
+
+    /// Split the text captured after the prefix into the ranges of the names of the
+    /// concatenated tokens
+    fn split(&self, body: &str) -> Vec<Range<usize>> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        if let Some(joiner) = &self.joiner {
+            for joint in joiner.find_iter(body).filter(|joint| joint.start() > 0) {
+                parts.push(start..joint.start());
+                start = joint.end() - 1;
+            }
+        }
+        parts.push(start..body.len());
+        parts
+    }
+
+    /// For styles whose words are separated by non-word characters (`scf-name-logo`),
+    /// a token also matches at the start of a longer name, as the word boundary allows;
+    /// returns the remainder of `part` after `var` in that case
+    fn after_leading_word<'a>(&self, part: &'a str, var: &str) -> Option<&'a str> {
+        if !matches!(self.case, Case::Kebab | Case::UpperKebab) {
+            return None;
+        }
+        part.strip_prefix(var)
+            .filter(|rest| rest.starts_with(['-', '.']))
+    }
+
+    /// Replace the token for `var` with the token for `value`, which is already in
+    /// this style, wherever it appears, except in `denied` tokens
+    fn replace(&self, text: &str, var: &str, value: &str, denied: &HashSet<String>) -> String {
+        let var = self.convert(var);
+
+        self.pattern
+            .replace_all(text, |caps: &regex::Captures| {
+                if denied.contains(&caps[0].to_lowercase()) {
+                    return caps[0].to_string();
+                }
+                let body = caps.get(1).map_or("", |m| m.as_str());
+                let mut result = self.prefix.clone();
+                let mut end = 0;
+                for range in self.split(body) {
+                    // Keep the joiner between the previous part and this one
+                    result.push_str(&body[end..range.start]);
+                    let part = &body[range.clone()];
+                    if part == var {
+                        result.push_str(value);
+                    } else if let Some(rest) = self.after_leading_word(part, &var) {
+                        result.push_str(value);
+                        result.push_str(rest);
+                    } else {
+                        result.push_str(part);
+                    }
+                    end = range.end;
+                }
+                result
+            })
+            .to_string()
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct TemplateProcessor {
     variables: HashMap<String, String>,
-    variable_patterns: Vec<Regex>,
+    /// Lowercase prefix of variable tokens
+    prefix: String,
+    variable_styles: Vec<VariableStyle>,
+    include_pattern: Regex,
+    /// Matches a token with an inline default (`{{scf-name:default}}`), capturing the
+    /// token and the default
+    default_pattern: Regex,
+    filename_case: FilenameCase,
+    scope: SubstitutionScope,
+    /// Lowercase words kept in upper case in capitalized values
+    acronyms: Vec<String>,
+    /// Text put in place of the whole token of each variable left without a value
+    placeholders: HashMap<String, String>,
+    /// Values substituted as they are, without being converted to each token's case
+    /// style
+    literals: HashMap<String, String>,
+    /// Lowercase tokens that match a variable pattern but are never variables
+    denied_tokens: HashSet<String>,
+    /// Directories of partials shared by templates, searched by includes last
+    shared_partials: Vec<PathBuf>,
 }
 
 impl TemplateProcessor {
     pub fn new() -> Self {
-        let variable_patterns = vec![
-            // ScfMyvar - PascalCase with Scf prefix
-            Regex::new(r"\bScf([A-Z][a-zA-Z0-9]*)\b").unwrap(),
-            // SCF_MYVAR - UPPER_SNAKE_CASE with SCF prefix
-            Regex::new(r"\bSCF_([A-Z][A-Z0-9_]*)\b").unwrap(),
-            // SCF-MYVAR - UPPER-KEBAB-CASE with SCF prefix
-            Regex::new(r"\bSCF-([A-Z][A-Z0-9-]*)\b").unwrap(),
-            // SCF.MYVAR - UPPER.DOT.CASE with SCF prefix
-            Regex::new(r"\bSCF\.([A-Z][A-Z0-9\.]*)\b").unwrap(),
-            // scf_myvar - snake_case with scf prefix
-            Regex::new(r"\bscf_([a-z][a-z0-9_]*)\b").unwrap(),
-            // scf-myvar - kebab-case with scf prefix
-            Regex::new(r"\bscf-([a-z][a-z0-9-]*)\b").unwrap(),
-            // scf.myvar - dot.case with scf prefix
-            Regex::new(r"\bscf\.([a-z][a-z0-9\.]*)\b").unwrap(),
-            // scfmyvar - lowercase flat with scf prefix
-            Regex::new(r"\bscf([a-z][a-z0-9]*)\b").unwrap(),
-            // SCFMYVAR - uppercase flat with SCF prefix
-            Regex::new(r"\bSCF([A-Z][A-Z0-9]*)\b").unwrap(),
-        ];
-
-        Self {
+        let mut processor = Self {
             variables: HashMap::new(),
-            variable_patterns,
-        }
+            prefix: DEFAULT_PREFIX.to_string(),
+            variable_styles: Vec::new(),
+            include_pattern: Regex::new(r#"\{\{\s*include\s+"([^"]+)"\s*\}\}"#).unwrap(),
+            default_pattern: Regex::new(r"\{\{\s*([A-Za-z][A-Za-z0-9_.-]*)\s*:([^{}]*)\}\}")
+                .unwrap(),
+            filename_case: FilenameCase::default(),
+            scope: SubstitutionScope::default(),
+            acronyms: Vec::new(),
+            placeholders: HashMap::new(),
+            literals: HashMap::new(),
+            denied_tokens: HashSet::new(),
+            shared_partials: Vec::new(),
+        };
+        processor.deny_tokens(DEFAULT_DENIED_TOKENS);
+        processor.set_syntax(DEFAULT_PREFIX, &CaseStyle::ALL);
+        processor
+    }
+
+    /// Recognize variables written with the given prefix (as checked by
+    /// [`parse_prefix`]) in the given case styles
+    pub fn set_syntax(&mut self, prefix: &str, cases: &[CaseStyle]) {
+        self.prefix = prefix.to_string();
+        // Styles are tried in a fixed order regardless of how they were listed
+        self.variable_styles = CaseStyle::ALL
+            .iter()
+            .filter(|style| cases.contains(style))
+            .map(|style| VariableStyle::new(*style, prefix))
+            .collect();
+    }
+
+    /// Never treat these whole tokens (in any letter case) as variables: they are
+    /// neither asked for nor substituted
+    pub fn deny_tokens<S: AsRef<str>>(&mut self, tokens: &[S]) {
+        self.denied_tokens.extend(
+            tokens
+                .iter()
+                .map(|token| token.as_ref().trim().to_lowercase()),
+        );
+    }
+
+    fn is_denied(&self, token: &str) -> bool {
+        self.denied_tokens.contains(&token.to_lowercase())
+    }
+
+    /// Let includes find partials in these directories when the template has none
+    /// by the included name
+    pub fn set_shared_partials(&mut self, dirs: Vec<PathBuf>) {
+        self.shared_partials = dirs;
+    }
+
+    /// The shared partial directories set with `set_shared_partials`
+    pub fn shared_partials(&self) -> &[PathBuf] {
+        &self.shared_partials
+    }
+
+    pub fn set_filename_case(&mut self, filename_case: FilenameCase) {
+        self.filename_case = filename_case;
+    }
+
+    /// Keep these words in upper case (`APIClient`) in the case styles that capitalize
+    /// words, instead of capitalizing them (`ApiClient`)
+    pub fn set_acronyms(&mut self, acronyms: &[String]) {
+        self.acronyms = acronyms
+            .iter()
+            .map(|acronym| acronym.to_lowercase())
+            .collect();
+    }
+
+    /// Replace the tokens of a variable that has no value with `placeholder`, taken
+    /// literally in every case style, instead of leaving the tokens in place
+    pub fn set_placeholder(&mut self, name: &str, placeholder: &str) {
+        self.placeholders
+            .insert(name.to_case(Case::Kebab), placeholder.to_string());
+    }
+
+    /// Give a variable a value that is substituted as it is in every case style, for
+    /// values such as UUIDs or paths that case conversion would break
+    pub fn set_literal(&mut self, name: &str, value: &str) {
+        self.literals
+            .insert(name.to_case(Case::Kebab), value.to_string());
+    }
+
+    /// Limit substitution to names or to contents
+    pub fn set_scope(&mut self, scope: SubstitutionScope) {
+        self.scope = scope;
+    }
+
+    /// Whether variables are substituted in file and directory names
+    pub fn substitutes_names(&self) -> bool {
+        self.scope != SubstitutionScope::ContentOnly
+    }
+
+    /// Whether variables are substituted in file contents
+    pub fn substitutes_content(&self) -> bool {
+        self.scope != SubstitutionScope::NamesOnly
     }
 
     pub fn set_variable(&mut self, name: String, value: String) {
@@ -49,17 +603,64 @@ impl TemplateProcessor {
         }
     }
 
+    /// The value of a variable, literal or not, by name in any case style
+    pub fn variable(&self, name: &str) -> Option<&str> {
+        let name = name.to_case(Case::Kebab);
+        self.variables
+            .get(&name)
+            .or_else(|| self.literals.get(&name))
+            .map(String::as_str)
+    }
+
     /// Extract all template variables from the given text
     pub fn extract_variables(&self, text: &str) -> HashSet<String> {
+        self.extract_variables_reporting(text, |_| {})
+    }
+
+    /// Extract all template variables from the given text, passing the tokens that
+    /// can't form a usable variable to `skipped`
+    ///
+    /// A token is usable when its kebab-case name, written in the token's style again,
+    /// gives back the token; otherwise (e.g. `ScfHTTPServer` or `SCF_A__`) no value
+    /// could ever be substituted for it.
+    pub fn extract_variables_reporting(
+        &self,
+        text: &str,
+        mut skipped: impl FnMut(&str),
+    ) -> HashSet<String> {
         let mut variables = HashSet::new();
+        if verbatim_body(text).is_some() {
+            return variables;
+        }
 
-        for pattern in &self.variable_patterns {
-            for caps in pattern.captures_iter(text) {
+        // Tokens with an inline default never need a value
+        let text = self
+            .default_pattern
+            .replace_all(text, |caps: &regex::Captures| {
+                match self.defaulted_token(&caps[1]) {
+                    Some(_) => String::new(),
+                    None => caps[0].to_string(),
+                }
+            });
+        for style in &self.variable_styles {
+            for caps in style.pattern.captures_iter(&text) {
+                if self.is_denied(&caps[0]) {
+                    continue;
+                }
                 if let Some(var_match) = caps.get(1) {
-                    let var_name = var_match.as_str();
-                    // Convert to kebab-case for consistency
-                    let normalized = var_name.to_case(Case::Kebab);
-                    variables.insert(normalized);
+                    let body = var_match.as_str();
+                    for range in style.split(body) {
+                        // Convert to kebab-case for consistency
+                        let part = &body[range];
+                        let name = part.to_case(Case::Kebab);
+                        if name.starts_with(|c: char| c.is_ascii_alphabetic())
+                            && style.convert(&name) == part
+                        {
+                            variables.insert(name);
+                        } else {
+                            skipped(&format!("{}{part}", style.prefix));
+                        }
+                    }
                 }
             }
         }
@@ -67,85 +668,308 @@ impl TemplateProcessor {
         variables
     }
 
+    /// The variables of the tokens with an inline default (`{{scf-name:default}}`) in
+    /// the given text, which [`Self::extract_variables`] leaves out
+    pub fn defaulted_variables(&self, text: &str) -> HashSet<String> {
+        if verbatim_body(text).is_some() {
+            return HashSet::new();
+        }
+        self.default_pattern
+            .captures_iter(text)
+            .filter_map(|caps| self.defaulted_token(&caps[1]).map(|(_, name)| name))
+            .collect()
+    }
+
+    /// The style and kebab-case variable name of a single usable token, as written
+    /// before the default of `{{token:default}}`
+    fn defaulted_token(&self, token: &str) -> Option<(&VariableStyle, String)> {
+        if self.is_denied(token) {
+            return None;
+        }
+        self.variable_styles.iter().find_map(|style| {
+            let caps = style.pattern.captures(token)?;
+            let name = (&caps[1]).to_case(Case::Kebab);
+            let single = caps[0].len() == token.len() && style.split(&caps[1]).len() == 1;
+            (single && style.convert(&name) == caps[1]).then_some((style, name))
+        })
+    }
+
+    /// Words that start with the prefix (in any letter case) but are no token of the
+    /// recognized case styles, e.g. `scfName` or a lone `SCF`, and tokens no value can
+    /// be substituted for, each once in order of appearance
+    pub fn stray_tokens(&self, text: &str) -> Vec<String> {
+        let mut stray = Vec::new();
+        if verbatim_body(text).is_some() {
+            return stray;
+        }
+
+        let word = Regex::new(&format!(
+            r"(?i)\b{}[a-z0-9_.-]*",
+            regex::escape(&self.prefix)
+        ))
+        .unwrap();
+        let token_starts: HashSet<usize> = self
+            .variable_styles
+            .iter()
+            .flat_map(|style| style.pattern.find_iter(text).map(|token| token.start()))
+            .collect();
+        for found in word.find_iter(text) {
+            let token = found.as_str().trim_end_matches(['.', '-', '_']);
+            if !token_starts.contains(&found.start())
+                && !self.is_denied(token)
+                && !stray.iter().any(|t| t == token)
+            {
+                stray.push(token.to_string());
+            }
+        }
+
+        self.extract_variables_reporting(text, |token| {
+            if !stray.iter().any(|t| t == token) {
+                stray.push(token.to_string());
+            }
+        });
+        stray
+    }
+
+    /// Every concrete token a variable is searched for in templates, one per case style
+    /// (e.g. `ScfMyVar`, `SCF_MY_VAR`, `scf-my-var`, ...)
+    pub fn token_forms(&self, var_name: &str) -> Vec<String> {
+        self.variable_styles
+            .iter()
+            .map(|style| format!("{}{}", style.prefix, style.convert(var_name)))
+            .collect()
+    }
+
+    /// Find the targets of all `{{include "..."}}` directives in the given text
+    pub fn find_includes(&self, text: &str) -> Vec<String> {
+        if verbatim_body(text).is_some() {
+            return Vec::new();
+        }
+        self.include_pattern
+            .captures_iter(text)
+            .map(|caps| caps[1].to_string())
+            .collect()
+    }
+
+    /// Find the file an include names: relative to the template root, else in the
+    /// template's `_partials` directory, else in the shared partial directories
+    ///
+    /// Only files inside the directory searched are found, so absolute targets, `..`
+    /// and symlinks leading elsewhere never resolve.
+    pub fn resolve_include(&self, target: &str, template_root: &Path) -> Option<PathBuf> {
+        if !is_contained_path(target) {
+            return None;
+        }
+        [
+            template_root.to_path_buf(),
+            template_root.join(PARTIALS_DIR),
+        ]
+        .iter()
+        .chain(&self.shared_partials)
+        .find_map(|dir| {
+            let path = dir.join(target);
+            let inside = path
+                .canonicalize()
+                .ok()?
+                .starts_with(dir.canonicalize().ok()?);
+            (inside && path.is_file()).then_some(path)
+        })
+    }
+
+    /// Expand `{{include "..."}}` directives, resolving targets with [`Self::resolve_include`]
+    pub fn expand_includes(&self, text: &str, template_root: &Path) -> Result<String> {
+        let mut stack = Vec::new();
+        self.expand_includes_inner(text, template_root, &mut stack)
+    }
+
+    fn expand_includes_inner(
+        &self,
+        text: &str,
+        template_root: &Path,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<String> {
+        if stack.len() > MAX_INCLUDE_DEPTH {
+            bail!(
+                "Include depth exceeds {} (while including {})",
+                MAX_INCLUDE_DEPTH,
+                stack.last().unwrap().display()
+            );
+        }
+
+        let mut result = String::with_capacity(text.len());
+        let mut last_end = 0;
+
+        for caps in self.include_pattern.captures_iter(text) {
+            let directive = caps.get(0).unwrap();
+            if !is_contained_path(&caps[1]) {
+                bail!(
+                    "Include target must be a relative path inside the template: {}",
+                    &caps[1]
+                );
+            }
+            let Some(include_path) = self.resolve_include(&caps[1], template_root) else {
+                bail!(
+                    "Include target not found: {}",
+                    template_root.join(&caps[1]).display()
+                );
+            };
+            if stack.contains(&include_path) {
+                bail!("Include cycle detected at {}", include_path.display());
+            }
+
+            let content = fs::read_to_string(&include_path).with_context(|| {
+                format!("Failed to read included file: {}", include_path.display())
+            })?;
+
+            stack.push(include_path);
+            let expanded = self.expand_includes_inner(&content, template_root, stack)?;
+            stack.pop();
+
+            result.push_str(&text[last_end..directive.start()]);
+            result.push_str(&expanded);
+            last_end = directive.end();
+        }
+
+        result.push_str(&text[last_end..]);
+        Ok(result)
+    }
+
     /// Process template text by replacing all variable placeholders
     pub fn process_text(&self, text: &str) -> String {
-        let mut result = text.to_string();
+        if !self.substitutes_content() {
+            return text.to_string();
+        }
+        if let Some(body) = verbatim_body(text) {
+            return body.to_string();
+        }
+        self.substitute(text)
+    }
+
+    /// Replace all variable placeholders, regardless of the scope
+    fn substitute(&self, text: &str) -> String {
+        let mut result = self.apply_filters(&self.resolve_defaults(text));
+
+        // Longer names first, so that `scf-name-suffix` is replaced as the variable
+        // `name-suffix` rather than as `name` followed by `-suffix`
+        let mut variables: Vec<(&String, &String, bool)> = self
+            .variables
+            .iter()
+            .map(|(name, value)| (name, value, false))
+            .chain(
+                self.literals
+                    .iter()
+                    .map(|(name, value)| (name, value, true)),
+            )
+            .collect();
+        variables.sort_by(|(a, ..), (b, ..)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        for (var_name, var_value, literal) in variables {
+            result = self.replace_variable_in_text(&result, var_name, var_value, literal);
+        }
 
-        for (var_name, var_value) in &self.variables {
-            result = self.replace_variable_in_text(&result, var_name, var_value);
+        if !self.placeholders.is_empty() {
+            result = self.replace_placeholders(&result);
         }
 
         result
     }
 
-    /// Replace all occurrences of a variable in different case formats
-    fn replace_variable_in_text(&self, text: &str, var_name: &str, var_value: &str) -> String {
+    /// Unwrap the tokens with an inline default (`{{scf-name:default}}`): the token is
+    /// left for the substitution when its variable has a value, else the default is
+    /// substituted for the variable in it
+    fn resolve_defaults(&self, text: &str) -> String {
+        self.default_pattern
+            .replace_all(text, |caps: &regex::Captures| {
+                let token = &caps[1];
+                match self.defaulted_token(token) {
+                    Some((_, name))
+                        if self.variables.contains_key(&name)
+                            || self.literals.contains_key(&name) =>
+                    {
+                        token.to_string()
+                    }
+                    Some((style, name)) => style.replace(
+                        token,
+                        &name,
+                        &style.convert_value(caps[2].trim(), &self.acronyms),
+                        &self.denied_tokens,
+                    ),
+                    None => caps[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+
+    /// Replace the whole tokens of variables without a value by their placeholders
+    fn replace_placeholders(&self, text: &str) -> String {
+        let mut result = text.to_string();
+        for style in &self.variable_styles {
+            result = style
+                .pattern
+                .replace_all(&result, |caps: &regex::Captures| {
+                    let name = (&caps[1]).to_case(Case::Kebab);
+                    match self.placeholders.get(&name) {
+                        Some(placeholder)
+                            if style.convert(&name) == caps[1] && !self.is_denied(&caps[0]) =>
+                        {
+                            placeholder.clone()
+                        }
+                        _ => caps[0].to_string(),
+                    }
+                })
+                .into_owned();
+        }
+        result
+    }
+
+    /// Replace filtered tokens (e.g. `scf-name|ident`) with the filtered value of their
+    /// variable, converted to the token's case style
+    ///
+    /// Tokens of unknown variables or with unknown filters are left for the plain
+    /// substitution.
+    fn apply_filters(&self, text: &str) -> String {
         let mut result = text.to_string();
 
-        // Convert variable name and value to different cases
-        let pascal_var = var_name.to_case(Case::Pascal);
-        let upper_snake_var = var_name.to_case(Case::UpperSnake);
-        let upper_kebab_var = var_name.to_case(Case::UpperKebab);
-        let upper_flat_var = var_name.to_case(Case::UpperFlat);
-        let snake_var = var_name.to_case(Case::Snake);
-        let kebab_var = var_name.to_case(Case::Kebab);
-        let flat_var = var_name.to_case(Case::Flat);
-
-        let pascal_val = var_value.to_case(Case::Pascal);
-        let upper_snake_val = var_value.to_case(Case::UpperSnake);
-        let upper_kebab_val = var_value.to_case(Case::UpperKebab);
-        let upper_flat_val = var_value.to_case(Case::UpperFlat);
-        let snake_val = var_value.to_case(Case::Snake);
-        let kebab_val = var_value.to_case(Case::Kebab);
-        let flat_val = var_value.to_case(Case::Flat);
-
-        // Create dot-separated versions
-        let upper_dot_var = upper_kebab_var.replace('-', ".");
-        let lower_dot_var = kebab_var.replace('-', ".");
-        let upper_dot_val = upper_kebab_val.replace('-', ".");
-        let lower_dot_val = kebab_val.replace('-', ".");
-
-        // Replace patterns (order matters - more specific patterns first)
-        let replacements = vec![
-            // PascalCase with Scf prefix
-            (format!(r"\bScf{pascal_var}\b"), format!("Scf{pascal_val}")),
-            // UPPER_SNAKE_CASE with SCF prefix
-            (
-                format!(r"\bSCF_{upper_snake_var}\b"),
-                format!("SCF_{upper_snake_val}"),
-            ),
-            // UPPER-KEBAB-CASE with SCF prefix
-            (
-                format!(r"\bSCF-{upper_kebab_var}\b"),
-                format!("SCF-{upper_kebab_val}"),
-            ),
-            // UPPER.DOT.CASE with SCF prefix
-            (
-                format!(r"\bSCF\.{upper_dot_var}\b"),
-                format!("SCF.{upper_dot_val}"),
-            ),
-            // snake_case with scf prefix
-            (format!(r"\bscf_{snake_var}\b"), format!("scf_{snake_val}")),
-            // kebab-case with scf prefix
-            (format!(r"\bscf-{kebab_var}\b"), format!("scf-{kebab_val}")),
-            // dot.case with scf prefix
-            (
-                format!(r"\bscf\.{lower_dot_var}\b"),
-                format!("scf.{lower_dot_val}"),
-            ),
-            // lowercase flat with scf prefix
-            (format!(r"\bscf{flat_var}\b"), format!("scf{flat_val}")),
-            // uppercase flat with SCF prefix
-            (
-                format!(r"\bSCF{upper_flat_var}\b"),
-                format!("SCF{upper_flat_val}"),
-            ),
-        ];
+        for style in &self.variable_styles {
+            result = style
+                .filter_pattern
+                .replace_all(&result, |caps: &regex::Captures| {
+                    if self.is_denied(&format!("{}{}", style.prefix, &caps[1])) {
+                        return caps[0].to_string();
+                    }
+                    self.variables
+                        .iter()
+                        .find(|(name, _)| style.convert(name) == caps[1])
+                        .and_then(|(_, value)| {
+                            let arg = caps.get(3).map(|arg| arg.as_str());
+                            let value = style.convert_value(value, &self.acronyms);
+                            filters::apply(&caps[2], arg, &value)
+                        })
+                        .unwrap_or_else(|| caps[0].to_string())
+                })
+                .to_string();
+        }
 
-        for (pattern, replacement) in replacements {
-            if let Ok(re) = Regex::new(&pattern) {
-                result = re.replace_all(&result, replacement).to_string();
-            }
+        result
+    }
+
+    /// Replace all occurrences of a variable in different case formats, with its value
+    /// converted to each format unless it is `literal`
+    fn replace_variable_in_text(
+        &self,
+        text: &str,
+        var_name: &str,
+        var_value: &str,
+        literal: bool,
+    ) -> String {
+        let mut result = text.to_string();
+
+        for style in &self.variable_styles {
+            let value = if literal {
+                Cow::Borrowed(var_value)
+            } else {
+                Cow::Owned(style.convert_value(var_value, &self.acronyms))
+            };
+            result = style.replace(&result, var_name, &value, &self.denied_tokens);
         }
 
         result
@@ -153,19 +977,79 @@ impl TemplateProcessor {
 
     /// Process a file path by replacing variables in the path components
     pub fn process_path(&self, path: &str) -> String {
-        let processed = self.process_text(path);
+        if !self.substitutes_names() {
+            return path.to_string();
+        }
+        let processed = self.substitute(path);
 
         // Clean up any invalid path characters that might result from replacement
-        processed
+        let cleaned: String = processed
             .chars()
             .map(|c| match c {
                 '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
                 _ => c,
             })
-            .collect()
+            .collect();
+
+        // Normalize each component, keeping the separators as they are
+        let mut result = String::with_capacity(cleaned.len());
+        let mut component = String::new();
+        for c in cleaned.chars() {
+            if c == '/' || c == '\\' {
+                result.push_str(&platform_safe_component(
+                    self.filename_case.apply(&component),
+                ));
+                result.push(c);
+                component.clear();
+            } else {
+                component.push(c);
+            }
+        }
+        result.push_str(&platform_safe_component(
+            self.filename_case.apply(&component),
+        ));
+        result
+    }
+}
+
+/// Whether a relative path stays below the directory it is joined to: it is not
+/// absolute and has no `..` components
+pub fn is_contained_path(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// Rename path components that Windows reserves for devices (`CON`, `NUL`, `COM1`, ...)
+///
+/// The reserved part is suffixed with `_`, so `con.txt` becomes `con_.txt`.
+#[cfg(windows)]
+fn platform_safe_component(component: String) -> String {
+    const RESERVED: &[&str] = &[
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+
+    let (stem, rest) = match component.find('.') {
+        Some(index) => component.split_at(index),
+        None => (component.as_str(), ""),
+    };
+
+    if RESERVED
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(stem.trim_end()))
+    {
+        format!("{stem}_{rest}")
+    } else {
+        component
     }
 }
 
+#[cfg(not(windows))]
+fn platform_safe_component(component: String) -> String {
+    component
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -215,6 +1099,218 @@ mod tests {
         assert!(result.contains("SCF_HELLO_WORLD"));
     }
 
+    #[test]
+    fn test_longer_variable_names_are_substituted_first() {
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "app".to_string());
+        processor.set_variable("name-suffix".to_string(), "beta".to_string());
+        processor.set_variable("name-suffix-long".to_string(), "x".to_string());
+
+        // Each token is one variable, never a shorter name followed by the rest
+        assert_eq!(
+            processor.process_text(
+                "scf-name-suffix scf-name SCF_NAME_SUFFIX scf.name.suffix scf-name-suffix-long"
+            ),
+            "scf-beta scf-app SCF_BETA scf.beta scf-x"
+        );
+        assert_eq!(
+            processor.process_path("scf_name_suffix/scf-name.rs"),
+            "scf_beta/scf-app.rs"
+        );
+    }
+
+    #[test]
+    fn test_custom_syntax() {
+        assert_eq!(parse_prefix(" Tpl ").unwrap(), "tpl");
+        assert!(parse_prefix("1x").is_err());
+        assert!(parse_prefix("my-prefix").is_err());
+        assert!(parse_prefix("").is_err());
+        assert_eq!(
+            parse_cases(&["kebab", "pascal"]).unwrap(),
+            vec![CaseStyle::Kebab, CaseStyle::Pascal]
+        );
+        assert!(parse_cases(&["camel"]).is_err());
+        assert!(parse_cases::<&str>(&[]).is_err());
+
+        let mut processor = TemplateProcessor::new();
+        processor.set_syntax("tpl", &[CaseStyle::Kebab, CaseStyle::Pascal]);
+        assert_eq!(
+            processor.token_forms("my-var"),
+            vec!["TplMyVar", "tpl-my-var"]
+        );
+
+        processor.set_variable("name".to_string(), "my-app".to_string());
+        assert_eq!(
+            processor.process_text("TplName tpl-name tpl_name ScfName TplNameTplName"),
+            "TplMyApp tpl-my-app tpl_name ScfName TplMyAppTplMyApp"
+        );
+    }
+
+    #[test]
+    fn test_token_forms() {
+        let mut processor = TemplateProcessor::new();
+        let forms = processor.token_forms("my-var");
+        assert_eq!(
+            forms,
+            vec![
+                "ScfMyVar",
+                "SCF_MY_VAR",
+                "SCF-MY-VAR",
+                "SCF.MY.VAR",
+                "scf_my_var",
+                "scf-my-var",
+                "scf.my.var",
+                "scfmyvar",
+                "SCFMYVAR",
+            ]
+        );
+
+        // Each form is exactly what substitution replaces
+        processor.set_variable("my-var".to_string(), "new-name".to_string());
+        let replaced: Vec<String> = forms
+            .iter()
+            .map(|form| processor.process_text(form))
+            .collect();
+        assert_eq!(replaced, processor.token_forms("new-name"));
+    }
+
+    #[test]
+    fn test_denied_tokens() {
+        let mut processor = TemplateProcessor::new();
+        processor.deny_tokens(&["scfx"]);
+        let text = "Fan: 40 SCFM, scfh; scfx scfName ScfName scfm|upper";
+
+        let mut vars: Vec<_> = processor.extract_variables(text).into_iter().collect();
+        vars.sort();
+        assert_eq!(vars, vec!["name"]);
+
+        for var in ["m", "h", "x", "name"] {
+            processor.set_variable(var.to_string(), "app".to_string());
+        }
+        assert_eq!(
+            processor.process_text(text),
+            "Fan: 40 SCFM, scfh; scfx scfName ScfApp scfm|upper"
+        );
+
+        // Without the denylist the same tokens are variables
+        let mut processor = TemplateProcessor::new();
+        processor.denied_tokens.clear();
+        processor.set_variable("m".to_string(), "app".to_string());
+        assert_eq!(processor.process_text("40 SCFM"), "40 SCFAPP");
+    }
+
+    #[test]
+    fn test_stray_tokens() {
+        let processor = TemplateProcessor::new();
+        assert_eq!(
+            processor.stray_tokens("ScfName scfName SCF, 40 SCFM, SCF_A__ scf-name.rs ScfName"),
+            vec!["scfName", "SCF", "SCF_A__"]
+        );
+        assert!(
+            processor
+                .stray_tokens("ScfModuleScfSubmodule scf.my.var")
+                .is_empty()
+        );
+
+        // Tokens of case styles that aren't recognized are stray too
+        let mut processor = TemplateProcessor::new();
+        processor.set_syntax("tpl", &[CaseStyle::Pascal]);
+        assert_eq!(processor.stray_tokens("TplName tpl-name"), vec!["tpl-name"]);
+    }
+
+    #[test]
+    fn test_concatenated_tokens() {
+        let processor = TemplateProcessor::new();
+        let mut vars: Vec<_> = processor
+            .extract_variables("ScfModuleScfSubmodule SCF_MODULE_SCF_SUB_MODULE scf-module-scf-x")
+            .into_iter()
+            .collect();
+        vars.sort();
+        assert_eq!(vars, vec!["module", "sub-module", "submodule", "x"]);
+
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("module".to_string(), "core".to_string());
+        processor.set_variable("submodule".to_string(), "http-client".to_string());
+
+        assert_eq!(
+            processor.process_path("src/ScfModuleScfSubmodule.ts"),
+            "src/ScfCoreScfHttpClient.ts"
+        );
+        assert_eq!(
+            processor.process_text("SCF_MODULE_SCF_SUBMODULE scf_module_scf_submodule"),
+            "SCF_CORE_SCF_HTTP_CLIENT scf_core_scf_http_client"
+        );
+        // PascalCase tokens are only substituted whole, not as a prefix of another name
+        assert_eq!(processor.process_text("ScfModuleName"), "ScfModuleName");
+    }
+
+    #[test]
+    fn test_unusable_tokens_are_skipped() {
+        let processor = TemplateProcessor::new();
+        let mut skipped = Vec::new();
+        let mut vars: Vec<_> = processor
+            .extract_variables_reporting(
+                "scf1 SCF. scf- SCF_1 SCF_A__ ScfHTTPServer scf_a__b ScfA1 scf-ok.",
+                |token| skipped.push(token.to_string()),
+            )
+            .into_iter()
+            .collect();
+        vars.sort();
+        skipped.sort();
+
+        assert_eq!(vars, vec!["a-1", "ok"]);
+        assert_eq!(skipped, vec!["SCF_A__", "ScfHTTPServer", "scf_a__b"]);
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "123 my-thing!".to_string());
+
+        assert!(
+            processor
+                .extract_variables("let scf_name|ident:rust = 1;")
+                .contains("name")
+        );
+        assert_eq!(
+            processor.process_text("let scf_name|ident:rust = ScfName|ident:ts;"),
+            "let _123_my_thing = _123MyThing;"
+        );
+        assert_eq!(processor.process_text("scf-name|ident"), "_123_my_thing");
+
+        // Unknown filters and variables are left to the plain substitution
+        assert_eq!(
+            processor.process_text("scf-name|shout scf-other|ident"),
+            "scf-123-my-thing!|shout scf-other|ident"
+        );
+    }
+
+    #[test]
+    fn test_acronyms() {
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "api-client".to_string());
+        processor.set_variable("proto".to_string(), "HTTPServer".to_string());
+        processor.set_acronyms(&["API".to_string(), "http".to_string()]);
+
+        assert_eq!(
+            processor.process_text("ScfName SCF_NAME scf-name scf_name scf.name scfname"),
+            "ScfAPIClient SCF_API_CLIENT scf-api-client scf_api_client scf.api.client scfapiclient"
+        );
+        assert_eq!(
+            processor.process_text("ScfProto scf-proto SCFPROTO"),
+            "ScfHTTPServer scf-http-server SCFHTTPSERVER"
+        );
+        assert_eq!(processor.process_text("ScfName|ident"), "APIClient");
+
+        // Only whole words are acronyms
+        processor.set_variable("name".to_string(), "rapid-apis".to_string());
+        assert_eq!(processor.process_text("ScfName"), "ScfRapidApis");
+
+        processor.set_acronyms(&[]);
+        processor.set_variable("name".to_string(), "api-client".to_string());
+        assert_eq!(processor.process_text("ScfName"), "ScfApiClient");
+    }
+
     #[test]
     fn test_path_processing() {
         let mut processor = TemplateProcessor::new();
@@ -225,4 +1321,296 @@ mod tests {
 
         assert_eq!(result, "src/ScfMyApp/scf-my-app.rs");
     }
+
+    #[test]
+    fn test_inline_defaults() {
+        let mut processor = TemplateProcessor::new();
+        let path = "src/{{ScfModule:core}}/{{scf-name:my-widget}}.rs";
+
+        // Unset variables get their default, converted to the token's style
+        assert_eq!(processor.process_path(path), "src/ScfCore/scf-my-widget.rs");
+        // Defaults are still sanitized like substituted values
+        assert_eq!(
+            processor.process_path("{{scf-name:what?}}/{{scf_kind: a:b }}.txt"),
+            "scf-what_/scf_a_b.txt"
+        );
+        // Defaulted variables are never asked for
+        assert!(processor.extract_variables(path).is_empty());
+        let mut defaulted: Vec<_> = processor.defaulted_variables(path).into_iter().collect();
+        defaulted.sort();
+        assert_eq!(defaulted, vec!["module", "name"]);
+
+        processor.set_variable("name".to_string(), "user-card".to_string());
+        assert_eq!(processor.process_path(path), "src/ScfCore/scf-user-card.rs");
+        assert_eq!(
+            processor.process_text("let x = {{ ScfName : Widget }}; // scf-name"),
+            "let x = ScfUserCard; // scf-user-card"
+        );
+
+        // Braces around something other than a single token are left alone
+        assert_eq!(
+            processor.process_text("{{scf-x-scf-y:z}} {{text:here}}"),
+            "{{scf-x-scf-y:z}} {{text:here}}"
+        );
+    }
+
+    #[test]
+    fn test_filename_case() {
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "MyWidget".to_string());
+
+        let path = "src/ScfName/ScfName.test.ts";
+        assert_eq!(
+            processor.process_path(path),
+            "src/ScfMyWidget/ScfMyWidget.test.ts"
+        );
+
+        processor.set_filename_case(FilenameCase::Kebab);
+        assert_eq!(
+            processor.process_path(path),
+            "src/scf-my-widget/scf-my-widget.test.ts"
+        );
+
+        processor.set_filename_case(FilenameCase::Lower);
+        assert_eq!(
+            processor.process_path(path),
+            "src/scfmywidget/scfmywidget.test.ts"
+        );
+    }
+
+    #[test]
+    fn test_output_encoding() {
+        assert_eq!("utf8-bom".parse(), Ok(OutputEncoding::Utf8Bom));
+        assert!("latin1".parse::<OutputEncoding>().is_err());
+
+        let text = "h\u{e9}\n".as_bytes().to_vec();
+        let with_bom = b"\xef\xbb\xbfh\xc3\xa9\n".to_vec();
+        assert_eq!(OutputEncoding::Utf8.encode(text.clone()), text);
+        assert_eq!(OutputEncoding::Utf8.encode(with_bom.clone()), text);
+        assert_eq!(OutputEncoding::Utf8Bom.encode(text.clone()), with_bom);
+        // A source BOM is not doubled
+        assert_eq!(OutputEncoding::Utf8Bom.encode(with_bom.clone()), with_bom);
+        assert_eq!(
+            OutputEncoding::Utf16le.encode(with_bom),
+            b"\xff\xfeh\x00\xe9\x00\n\x00"
+        );
+
+        // Binary content is left as it is
+        let binary = b"\x89PNG\x00\xff".to_vec();
+        assert_eq!(OutputEncoding::Utf16le.encode(binary.clone()), binary);
+        assert_eq!(OutputEncoding::decode(binary.clone()), binary);
+
+        // Whatever it was encoded in, content decodes to plain UTF-8
+        for encoding in [
+            OutputEncoding::Utf8,
+            OutputEncoding::Utf8Bom,
+            OutputEncoding::Utf16le,
+        ] {
+            assert_eq!(OutputEncoding::decode(encoding.encode(text.clone())), text);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_reserved_device_names_are_renamed() {
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "con".to_string());
+        processor.set_filename_case(FilenameCase::Lower);
+
+        assert_eq!(processor.process_path("src/scfname.rs"), "src/scfcon.rs");
+        assert_eq!(processor.process_path("nul.txt"), "nul_.txt");
+        assert_eq!(processor.process_path("Com1/readme.md"), "com1_/readme.md");
+    }
+
+    #[test]
+    fn test_missing_placeholder() {
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "app".to_string());
+        processor.set_placeholder("api-url", "TODO(api-url)");
+
+        assert_eq!(
+            processor.process_text("ScfName uses SCF_API_URL, scf-api-url and ScfApiUrl"),
+            "ScfApp uses TODO(api-url), TODO(api-url) and TODO(api-url)"
+        );
+        // Tokens of other unknown variables are left alone
+        assert_eq!(
+            processor.process_text("ScfOther scf-api-url-base"),
+            "ScfOther scf-api-url-base"
+        );
+    }
+
+    #[test]
+    fn test_literal_values() {
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("template".to_string(), "web-app".to_string());
+        processor.set_literal("id", "7d470993-7775-4859-ab9b-0c5d3a3f1e2a");
+        processor.set_literal("template-name", "api-v2");
+
+        // Every case style gets the value as it is
+        assert_eq!(
+            processor.process_text("scf-id ScfId SCF_ID {{scf-id:none}}"),
+            "scf-7d470993-7775-4859-ab9b-0c5d3a3f1e2a Scf7d470993-7775-4859-ab9b-0c5d3a3f1e2a \
+             SCF_7d470993-7775-4859-ab9b-0c5d3a3f1e2a scf-7d470993-7775-4859-ab9b-0c5d3a3f1e2a"
+        );
+        // Shorter variable names don't take a literal's token apart
+        assert_eq!(
+            processor.process_text("ScfTemplateName, ScfTemplate"),
+            "Scfapi-v2, ScfWebApp"
+        );
+        assert_eq!(processor.variable("TemplateName"), Some("api-v2"));
+    }
+
+    #[test]
+    fn test_split_frontmatter() {
+        let text = "---\ntitle: ScfName\n---\n# ScfName\n";
+        assert_eq!(
+            split_frontmatter(text),
+            Some(("---\ntitle: ScfName\n---\n", "# ScfName\n"))
+        );
+        assert_eq!(
+            split_frontmatter("---\r\na: 1\r\n...\r\nbody"),
+            Some(("---\r\na: 1\r\n...\r\n", "body"))
+        );
+        assert_eq!(
+            split_frontmatter("---\na: 1\n---"),
+            Some(("---\na: 1\n---", ""))
+        );
+
+        // No block at the very start, or one that is never closed
+        assert_eq!(split_frontmatter("# Title\n---\na: 1\n---\n"), None);
+        assert_eq!(split_frontmatter("---\na: 1\n"), None);
+        assert_eq!(split_frontmatter("----\na: 1\n----\n"), None);
+    }
+
+    #[test]
+    fn test_verbatim_directive() {
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("name".to_string(), "app".to_string());
+
+        for directive in [
+            "// scaffer:verbatim",
+            "# scaffer:verbatim",
+            "-- scaffer:verbatim",
+            "  /* scaffer:verbatim */",
+            "<!-- scaffer:verbatim -->",
+            "{# scaffer:verbatim #}",
+            "//scaffer:verbatim\r",
+        ] {
+            let text = format!("{directive}\nUse ScfName {{{{include \"x.md\"}}}}\n");
+            assert_eq!(
+                processor.process_text(&text),
+                "Use ScfName {{include \"x.md\"}}\n",
+                "{directive}"
+            );
+            assert!(processor.extract_variables(&text).is_empty());
+            assert!(processor.find_includes(&text).is_empty());
+        }
+
+        // Only a whole first-line comment counts
+        for text in [
+            "ScfName\n// scaffer:verbatim\n",
+            "// scaffer:verbatim ScfName\n",
+            "// see scaffer:verbatim\n",
+        ] {
+            assert_eq!(verbatim_body(text), None, "{text}");
+        }
+        assert_eq!(verbatim_body("# scaffer:verbatim"), Some(""));
+    }
+
+    #[test]
+    fn test_include_expansion() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("header.txt"), "// ScfProject\n").unwrap();
+        fs::write(
+            dir.path().join("outer.txt"),
+            "{{include \"header.txt\"}}body",
+        )
+        .unwrap();
+
+        let mut processor = TemplateProcessor::new();
+        processor.set_variable("project".to_string(), "my-app".to_string());
+
+        let text = "{{ include \"outer.txt\" }}\nrest";
+        let expanded = processor.expand_includes(text, dir.path()).unwrap();
+        assert_eq!(expanded, "// ScfProject\nbody\nrest");
+        assert_eq!(processor.process_text(&expanded), "// ScfMyApp\nbody\nrest");
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "{{include \"b.txt\"}}").unwrap();
+        fs::write(dir.path().join("b.txt"), "{{include \"a.txt\"}}").unwrap();
+
+        let processor = TemplateProcessor::new();
+        let err = processor
+            .expand_includes("{{include \"a.txt\"}}", dir.path())
+            .unwrap_err();
+        assert!(err.to_string().contains("cycle"));
+
+        let err = processor
+            .expand_includes("{{include \"missing.txt\"}}", dir.path())
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[test]
+    fn test_includes_stay_inside_the_template() {
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        let template = outside.path().join("template");
+        fs::create_dir(&template).unwrap();
+        let secret = outside.path().join("secret.txt");
+
+        let processor = TemplateProcessor::new();
+        for target in [
+            secret.to_string_lossy().to_string(),
+            "../secret.txt".to_string(),
+        ] {
+            let directive = format!("{{{{include \"{target}\"}}}}");
+            let err = processor
+                .expand_includes(&directive, &template)
+                .unwrap_err();
+            assert!(err.to_string().contains("inside the template"), "{err}");
+            assert_eq!(processor.resolve_include(&target, &template), None);
+        }
+
+        // Nor can a symlink inside the template lead out of it
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&secret, template.join("link.txt")).unwrap();
+            assert_eq!(processor.resolve_include("link.txt", &template), None);
+            assert!(
+                processor
+                    .expand_includes("{{include \"link.txt\"}}", &template)
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_include_search_order() {
+        let template = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        fs::create_dir(template.path().join(PARTIALS_DIR)).unwrap();
+        for (dir, file, content) in [
+            (template.path().to_path_buf(), "a.txt", "root"),
+            (template.path().join(PARTIALS_DIR), "a.txt", "partials"),
+            (template.path().join(PARTIALS_DIR), "b.txt", "partials"),
+            (shared.path().to_path_buf(), "b.txt", "shared"),
+            (shared.path().to_path_buf(), "c.txt", "shared"),
+        ] {
+            fs::write(dir.join(file), content).unwrap();
+        }
+
+        let mut processor = TemplateProcessor::new();
+        processor.set_shared_partials(vec![shared.path().to_path_buf()]);
+        let expanded = processor
+            .expand_includes(
+                "{{include \"a.txt\"}} {{include \"b.txt\"}} {{include \"c.txt\"}}",
+                template.path(),
+            )
+            .unwrap();
+        assert_eq!(expanded, "root partials shared");
+    }
 }
@@ -1,17 +1,124 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use zip::ZipArchive;
 
+use crate::template::TemplateProcessor;
+
+/// Progress of a ZIP extraction, reported after each entry is extracted
+#[derive(Debug, Clone)]
+pub struct ExtractProgress {
+    /// Zero-based index of the entry
+    pub index: usize,
+    /// Number of entries in the archive
+    pub total: usize,
+    /// Name of the entry within the archive; directories end with `/`
+    pub name: String,
+    /// Uncompressed size of the entry
+    pub bytes: u64,
+}
+
 /// Extract a ZIP file to a destination directory
 pub fn extract_zip(zip_path: &Path, dest_dir: &Path) -> Result<()> {
+    extract_zip_with_progress(zip_path, dest_dir, &mut |_| {})
+}
+
+/// A file to be written into a zip archive
+pub struct ArchiveEntry {
+    /// Path inside the archive, with `/` separators; a name ending in `/` is a
+    /// directory, without content
+    pub name: String,
+    pub content: Vec<u8>,
+    /// Unix permissions to keep, such as the executable bit of scripts
+    pub mode: Option<u32>,
+}
+
+/// Write files into a new ZIP archive, returning the size of the archive
+///
+/// The archive is written to a temporary file next to `zip_path` first, so a failed
+/// run leaves no partial archive behind.
+pub fn write_zip(zip_path: &Path, entries: &[ArchiveEntry]) -> Result<u64> {
+    let dir = match zip_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut file = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to create {}", zip_path.display()))?;
+
+    let mut writer = zip::ZipWriter::new(file.as_file_mut());
+    for entry in entries {
+        let mut options = zip::write::FileOptions::default();
+        if let Some(mode) = entry.mode {
+            options = options.unix_permissions(mode);
+        }
+        if entry.name.ends_with('/') {
+            writer.add_directory(entry.name.as_str(), options)?;
+        } else {
+            writer.start_file(entry.name.as_str(), options)?;
+            writer.write_all(&entry.content)?;
+        }
+    }
+    writer.finish()?;
+    drop(writer);
+
+    let size = file.as_file().metadata()?.len();
+    file.persist(zip_path)
+        .with_context(|| format!("Failed to write {}", zip_path.display()))?;
+    Ok(size)
+}
+
+/// Set the Unix permissions of a file; does nothing on other platforms
+pub fn set_unix_mode(path: &Path, mode: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set the mode of {}", path.display()))?;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+    }
+    Ok(())
+}
+
+/// Unix permissions of a file, where there are any
+pub fn unix_mode(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path)
+            .ok()
+            .map(|metadata| metadata.permissions().mode())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Extract a ZIP file to a destination directory, calling `progress` after each entry
+pub fn extract_zip_with_progress(
+    zip_path: &Path,
+    dest_dir: &Path,
+    progress: &mut dyn FnMut(ExtractProgress),
+) -> Result<()> {
     let file = fs::File::open(zip_path)
         .with_context(|| format!("Failed to open zip file: {}", zip_path.display()))?;
 
     let mut archive = ZipArchive::new(file).context("Failed to read zip archive")?;
+    let total = archive.len();
 
-    for i in 0..archive.len() {
+    for i in 0..total {
         let mut file = archive
             .by_index(i)
             .with_context(|| format!("Failed to read file at index {i}"))?;
@@ -36,16 +143,24 @@ pub fn extract_zip(zip_path: &Path, dest_dir: &Path) -> Result<()> {
             std::io::copy(&mut file, &mut outfile)
                 .with_context(|| format!("Failed to extract file: {}", outpath.display()))?;
         }
+
+        progress(ExtractProgress {
+            index: i,
+            total,
+            name: file.name().to_string(),
+            bytes: file.size(),
+        });
     }
 
     Ok(())
 }
 
 /// Find the root directory of a template within an extracted archive
-/// This handles cases where the template might be nested within subdirectories
-pub fn find_template_root(extract_dir: &Path) -> Result<PathBuf> {
+/// This handles cases where the template might be nested within subdirectories;
+/// template variables are recognized by `processor`, with its configured prefix
+pub fn find_template_root(extract_dir: &Path, processor: &TemplateProcessor) -> Result<PathBuf> {
     // First check if the extract directory itself looks like a template
-    if is_template_directory(extract_dir)? {
+    if is_template_directory(extract_dir, processor)? {
         return Ok(extract_dir.to_path_buf());
     }
 
@@ -56,7 +171,7 @@ pub fn find_template_root(extract_dir: &Path) -> Result<PathBuf> {
         let entry = entry?;
         let path = entry.path();
 
-        if path.is_dir() && is_template_directory(&path)? {
+        if path.is_dir() && is_template_directory(&path, processor)? {
             return Ok(path);
         }
     }
@@ -79,7 +194,7 @@ pub fn find_template_root(extract_dir: &Path) -> Result<PathBuf> {
 
 /// Check if a directory looks like a template directory
 /// A template directory should contain files or have template variables in names
-fn is_template_directory(dir: &Path) -> Result<bool> {
+fn is_template_directory(dir: &Path, processor: &TemplateProcessor) -> Result<bool> {
     let entries: Vec<_> = fs::read_dir(dir)
         .with_context(|| format!("Failed to read directory: {}", dir.display()))?
         .collect::<Result<Vec<_>, _>>()?;
@@ -99,7 +214,7 @@ fn is_template_directory(dir: &Path) -> Result<bool> {
     // Check if any file or directory names contain template variables
     for entry in &entries {
         if let Some(name) = entry.file_name().to_str() {
-            if contains_template_variables(name) {
+            if contains_template_variables(name, processor) {
                 return Ok(true);
             }
         }
@@ -110,7 +225,7 @@ fn is_template_directory(dir: &Path) -> Result<bool> {
         let path = entry.path();
         if path.is_file() {
             if let Ok(content) = fs::read_to_string(&path) {
-                if contains_template_variables(&content) {
+                if contains_template_variables(&content, processor) {
                     return Ok(true);
                 }
             }
@@ -121,44 +236,453 @@ fn is_template_directory(dir: &Path) -> Result<bool> {
     Ok(entries.len() > 1)
 }
 
-/// Check if text contains scaffer template variables
-fn contains_template_variables(text: &str) -> bool {
-    use regex::Regex;
-
-    let patterns = [
-        r"\bScf[A-Z][a-zA-Z0-9]*\b",  // ScfMyvar
-        r"\bSCF_[A-Z][A-Z0-9_]*\b",   // SCF_MYVAR
-        r"\bSCF-[A-Z][A-Z0-9-]*\b",   // SCF-MYVAR
-        r"\bSCF\.[A-Z][A-Z0-9\.]*\b", // SCF.MYVAR
-        r"\bscf_[a-z][a-z0-9_]*\b",   // scf_myvar
-        r"\bscf-[a-z][a-z0-9-]*\b",   // scf-myvar
-        r"\bscf\.[a-z][a-z0-9\.]*\b", // scf.myvar
-        r"\bscf[a-z][a-z0-9]*\b",     // scfmyvar
-        r"\bSCF[A-Z][A-Z0-9]*\b",     // SCFMYVAR
-    ];
-
-    for pattern in &patterns {
-        if let Ok(re) = Regex::new(pattern) {
-            if re.is_match(text) {
-                return true;
-            }
+/// Make a destination path writable on Windows even beyond `MAX_PATH`
+///
+/// Paths longer than 260 characters get the `\\?\` extended-length prefix,
+/// which requires an absolute path with backslash separators.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> PathBuf {
+    const MAX_PATH: usize = 260;
+
+    let as_str = path.to_string_lossy();
+    if as_str.len() < MAX_PATH || as_str.starts_with(r"\\?\") {
+        return path.to_path_buf();
+    }
+
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let absolute = absolute.to_string_lossy().replace('/', "\\");
+    match absolute.strip_prefix(r"\\") {
+        // UNC paths use the \\?\UNC\server\share form
+        Some(unc) => PathBuf::from(format!(r"\\?\UNC\{unc}")),
+        None => PathBuf::from(format!(r"\\?\{absolute}")),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// Compile a list of glob patterns into a set matched against relative paths
+pub fn build_globset(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder
+            .add(Glob::new(pattern).with_context(|| format!("Invalid glob pattern: {pattern}"))?);
+    }
+    builder.build().context("Failed to build glob set")
+}
+
+/// Move a file, falling back to copy and delete when the destination is on another device
+pub fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(from, to)
+        .with_context(|| format!("Failed to move {} to {}", from.display(), to.display()))?;
+    fs::remove_file(from).with_context(|| format!("Failed to remove {}", from.display()))
+}
+
+/// Normalize a relative path to forward slashes for glob matching
+pub fn glob_path(rel_path: &str) -> String {
+    rel_path.replace('\\', "/")
+}
+
+/// Pattern matching `{var}` placeholders in template URLs
+fn url_placeholder_pattern() -> Regex {
+    Regex::new(r"\{([A-Za-z0-9_-]+)\}").unwrap()
+}
+
+/// Find the names of all `{var}` placeholders in a URL, in order of first appearance
+pub fn url_placeholders(url: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for caps in url_placeholder_pattern().captures_iter(url) {
+        if !names.iter().any(|n| n == &caps[1]) {
+            names.push(caps[1].to_string());
         }
     }
+    names
+}
+
+/// Substitute `{var}` placeholders in a URL and validate the result
+pub fn expand_url_placeholders(url: &str, variables: &HashMap<String, String>) -> Result<String> {
+    let expanded = url_placeholder_pattern().replace_all(url, |caps: &regex::Captures| {
+        variables
+            .get(&caps[1])
+            .cloned()
+            .unwrap_or_else(|| caps[0].to_string())
+    });
+
+    if expanded.contains('{') || expanded.contains('}') {
+        bail!("Unresolved placeholders in template URL: {expanded}");
+    }
+    if !(expanded.starts_with("http://")
+        || expanded.starts_with("https://")
+        || expanded.starts_with(crate::oci::SCHEME))
+    {
+        bail!("Invalid template URL: {expanded}");
+    }
+
+    Ok(expanded.into_owned())
+}
+
+/// Expand a leading `~` to the home directory and `${VAR}` / `$VAR` references to the
+/// values of environment variables in a path given by the user
+///
+/// Fails on references to unset variables.
+pub fn expand_path(path: &Path) -> Result<PathBuf> {
+    expand_path_with(path, dirs::home_dir(), |name| std::env::var(name).ok())
+}
+
+fn expand_path_with(
+    path: &Path,
+    home: Option<PathBuf>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<PathBuf> {
+    // Paths that aren't valid UTF-8 can't hold anything to expand
+    let Some(text) = path.to_str() else {
+        return Ok(path.to_path_buf());
+    };
+    let expanded = expand_env_with(text, true, env)?;
 
-    false
+    // Only `~` on its own or followed by a separator means the home directory
+    let rest = match expanded.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => rest,
+        _ => return Ok(PathBuf::from(expanded)),
+    };
+    let home = home.context("Failed to get home directory")?;
+    Ok(home.join(rest.trim_start_matches(['/', '\\'])))
+}
+
+/// Expand `${VAR}` references to the values of environment variables, and
+/// `${VAR:-default}` to the value of `VAR`, or `default` when it is unset or empty
+///
+/// Fails on `${VAR}` references to unset variables. A `$` without braces is left as
+/// it is, since URLs can contain one.
+pub fn expand_env(text: &str) -> Result<String> {
+    expand_env_with(text, false, |name| std::env::var(name).ok())
+}
+
+/// Expand environment variable references as `expand_env` does, and with `bare` also
+/// `$VAR` ones
+fn expand_env_with(text: &str, bare: bool, env: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let reference = if bare {
+        Regex::new(r"\$(?:\{([^}]*)\}|([A-Za-z_][A-Za-z0-9_]*))").unwrap()
+    } else {
+        Regex::new(r"\$\{([^}]*)\}").unwrap()
+    };
+    let mut expanded = String::with_capacity(text.len());
+    let mut last_end = 0;
+    for caps in reference.captures_iter(text) {
+        let body = caps.get(1).or(caps.get(2)).map_or("", |body| body.as_str());
+        let (name, default) = match body.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (body, None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            bail!("Invalid variable reference '{}' in {text}", &caps[0]);
+        }
+        let value = match (env(name), default) {
+            (Some(value), Some(default)) if value.is_empty() => default.to_string(),
+            (Some(value), _) => value,
+            (None, Some(default)) => default.to_string(),
+            (None, None) => bail!("Environment variable {name} used in {text} is not set"),
+        };
+
+        let whole = caps.get(0).unwrap();
+        expanded.push_str(&text[last_end..whole.start()]);
+        expanded.push_str(&value);
+        last_end = whole.end();
+    }
+    expanded.push_str(&text[last_end..]);
+    Ok(expanded)
+}
+
+/// Printed at the end of a dry run, after what would have been written
+pub const DRY_RUN_NOTICE: &str = "This was a dry run - no files were actually written.";
+
+/// On a dry run, print what a file would get instead of writing it: `what` (e.g.
+/// `Would create index.ts`) and then the content
+pub fn print_dry_write(what: &str, content: &str) {
+    println!("{what}:");
+    print!("{content}");
+    if !content.is_empty() && !content.ends_with('\n') {
+        println!();
+    }
+    println!("{DRY_RUN_NOTICE}");
+}
+
+/// A stable fingerprint of file content (64-bit FNV-1a, in hex), for telling later
+/// whether a generated file changed
+pub fn content_hash(content: &[u8]) -> String {
+    let hash = content
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{hash:016x}")
+}
+
+/// The SHA-256 digest of content, in lowercase hex as `sha256sum` prints it
+pub fn sha256_hex(content: &[u8]) -> String {
+    hex(&Sha256::digest(content))
+}
+
+/// A key for data kept on disk between runs: the SHA-256 of `parts` in hex, with
+/// each part ended by a NUL byte so that where one ends and the next starts counts
+///
+/// Unlike `DefaultHasher`, whose output may change with any Rust release, the key
+/// stays the same for the same parts.
+pub fn stable_key<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+        hasher.update([0]);
+    }
+    hex(&hasher.finalize())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Apply `f` to every item on at most `jobs` threads at once, returning the results
+/// in the order of the items
+pub fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    let result = f(item);
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every item is processed"))
+        .collect()
+}
+
+/// Check if text contains template variables in the syntax of `processor`
+fn contains_template_variables(text: &str, processor: &TemplateProcessor) -> bool {
+    !processor.extract_variables(text).is_empty()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parallel_map() {
+        let running = AtomicUsize::new(0);
+        let most_running = AtomicUsize::new(0);
+        let items: Vec<u64> = (0..12).collect();
+        let results = parallel_map(&items, 3, |item| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            most_running.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            running.fetch_sub(1, Ordering::SeqCst);
+            item * 2
+        });
+
+        assert_eq!(results, (0..12).map(|item| item * 2).collect::<Vec<_>>());
+        assert!(most_running.load(Ordering::SeqCst) <= 3);
+        assert!(parallel_map(&Vec::<u64>::new(), 0, |item| *item).is_empty());
+    }
+
+    #[test]
+    fn test_expand_path() {
+        let home = Some(PathBuf::from("/home/me"));
+        let env = |name: &str| (name == "NAME").then(|| "app".to_string());
+        let expand = |path: &str| expand_path_with(Path::new(path), home.clone(), env);
+
+        assert_eq!(
+            expand("~/projects/$NAME").unwrap(),
+            PathBuf::from("/home/me/projects/app")
+        );
+        assert_eq!(expand("~").unwrap(), PathBuf::from("/home/me"));
+        assert_eq!(
+            expand("out/${NAME}-v2").unwrap(),
+            PathBuf::from("out/app-v2")
+        );
+        // A `~` elsewhere and a lone `$` are left alone
+        assert_eq!(expand("~x/a~b$").unwrap(), PathBuf::from("~x/a~b$"));
+
+        let err = expand("~/projects/$MISSING").unwrap_err();
+        assert!(err.to_string().contains("MISSING"));
+        assert!(expand("out/${}").is_err());
+        assert!(expand("out/${NAME").is_ok_and(|path| path == Path::new("out/${NAME")));
+    }
+
+    #[test]
+    fn test_expand_env_defaults() {
+        let env = |name: &str| match name {
+            "OS_ROOT" => Some("/opt".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        let expand = |text: &str| expand_env_with(text, false, env);
+
+        assert_eq!(
+            expand("${OS_ROOT:-/usr/local}/templates").unwrap(),
+            "/opt/templates"
+        );
+        assert_eq!(
+            expand("${MISSING:-/usr/local}/templates").unwrap(),
+            "/usr/local/templates"
+        );
+        assert_eq!(expand("${EMPTY:-fallback}").unwrap(), "fallback");
+        assert_eq!(expand("${MISSING:-}x").unwrap(), "x");
+        assert_eq!(
+            expand("https://${HOST:-example.com}/{name}.zip").unwrap(),
+            "https://example.com/{name}.zip"
+        );
+
+        // Without a default an unset variable is an error
+        assert!(expand("${MISSING}/templates").is_err());
+        assert!(expand("${:-x}").is_err());
+
+        // Only braced references are expanded
+        assert_eq!(
+            expand("https://x/$OS_ROOT/$ref").unwrap(),
+            "https://x/$OS_ROOT/$ref"
+        );
+    }
+
+    #[test]
+    fn test_extract_zip_reports_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("template.zip");
+
+        let mut writer = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        let options = zip::write::FileOptions::default();
+        writer.add_directory("src/", options).unwrap();
+        writer.start_file("src/lib.rs", options).unwrap();
+        writer.write_all(b"pub struct ScfName;").unwrap();
+        writer.finish().unwrap();
+
+        let dest = dir.path().join("out");
+        let mut reported = Vec::new();
+        extract_zip_with_progress(&zip_path, &dest, &mut |progress| {
+            reported.push((
+                progress.index,
+                progress.total,
+                progress.name,
+                progress.bytes,
+            ));
+        })
+        .unwrap();
+
+        assert_eq!(
+            reported,
+            vec![
+                (0, 2, "src/".to_string(), 0),
+                (1, 2, "src/lib.rs".to_string(), 19)
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dest.join("src/lib.rs")).unwrap(),
+            "pub struct ScfName;"
+        );
+    }
 
     #[test]
     fn test_contains_template_variables() {
-        assert!(contains_template_variables("ScfMyProject"));
-        assert!(contains_template_variables("scf-my-project"));
-        assert!(contains_template_variables("SCF_MY_PROJECT"));
-        assert!(contains_template_variables("scf.my.project"));
-        assert!(!contains_template_variables("regular text"));
-        assert!(!contains_template_variables("scaffold"));
+        let processor = TemplateProcessor::new();
+        assert!(contains_template_variables("ScfMyProject", &processor));
+        assert!(contains_template_variables("scf-my-project", &processor));
+        assert!(contains_template_variables("SCF_MY_PROJECT", &processor));
+        assert!(contains_template_variables("scf.my.project", &processor));
+        assert!(!contains_template_variables("regular text", &processor));
+        assert!(!contains_template_variables("scaffold", &processor));
+    }
+
+    #[test]
+    fn test_template_directory_uses_the_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = dir.path().join("content");
+        fs::create_dir(&content).unwrap();
+        fs::write(content.join("main.rs"), "struct TplName;").unwrap();
+        let names = dir.path().join("names");
+        fs::create_dir(&names).unwrap();
+        fs::write(names.join("tpl-name.rs"), "").unwrap();
+        let plain = dir.path().join("plain");
+        fs::create_dir(&plain).unwrap();
+        fs::write(plain.join("guide.md"), "scaffold ScfName").unwrap();
+
+        let default_processor = TemplateProcessor::new();
+        let mut processor = TemplateProcessor::new();
+        processor.set_syntax("tpl", &crate::template::CaseStyle::ALL);
+        for dir in [&content, &names] {
+            assert!(!is_template_directory(dir, &default_processor).unwrap());
+            assert!(is_template_directory(dir, &processor).unwrap());
+        }
+        assert!(is_template_directory(&plain, &default_processor).unwrap());
+        assert!(!is_template_directory(&plain, &processor).unwrap());
+    }
+
+    #[test]
+    fn test_stable_key() {
+        // What `printf 'ab\0c\0' | sha256sum` prints
+        let key = stable_key([b"ab".as_slice(), b"c"]);
+        assert_eq!(
+            key,
+            "629c2f14f654f026086f2aefe1855533d0897f49df86a19073be17b237361b09"
+        );
+        assert_eq!(stable_key([b"ab".as_slice(), b"c"]), key);
+        assert_ne!(stable_key([b"a".as_slice(), b"bc"]), key);
+        assert_ne!(stable_key([b"abc".as_slice()]), key);
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_long_path_gets_extended_prefix() {
+        let short = Path::new(r"C:\out\file.txt");
+        assert_eq!(long_path(short), short);
+
+        let long = PathBuf::from(format!(r"C:\out\{}\file.txt", "a".repeat(300)));
+        let extended = long_path(&long);
+        assert!(extended.to_string_lossy().starts_with(r"\\?\C:\"));
+        assert!(extended.to_string_lossy().ends_with(r"\file.txt"));
+    }
+
+    #[test]
+    fn test_url_placeholders() {
+        let url = "https://templates.example.com/{version}/{name}-{version}.zip";
+        assert_eq!(url_placeholders(url), vec!["version", "name"]);
+
+        let mut vars = HashMap::new();
+        vars.insert("version".to_string(), "1.2".to_string());
+        assert!(expand_url_placeholders(url, &vars).is_err());
+
+        vars.insert("name".to_string(), "react".to_string());
+        assert_eq!(
+            expand_url_placeholders(url, &vars).unwrap(),
+            "https://templates.example.com/1.2/react-1.2.zip"
+        );
     }
 }
@@ -0,0 +1,127 @@
+use anyhow::{Context, Result, bail};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+/// Validator commands used when the configuration does not override them
+const DEFAULT_VALIDATORS: &[(&str, &str)] = &[
+    ("js", "node --check {file}"),
+    ("mjs", "node --check {file}"),
+    ("cjs", "node --check {file}"),
+    ("py", "python3 -m py_compile {file}"),
+];
+
+/// Result of validating one processed file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationOutcome {
+    Valid,
+    Invalid(String),
+}
+
+/// Runs per-extension syntax checks against processed file content
+#[derive(Debug, Clone)]
+pub struct Validator {
+    commands: HashMap<String, String>,
+}
+
+impl Validator {
+    /// Create a validator from configured `extension -> command` entries layered over the defaults
+    ///
+    /// A command may contain `{file}`, which is replaced by the path of a temporary
+    /// file holding the processed content; otherwise the path is appended.
+    pub fn new(configured: HashMap<String, String>) -> Self {
+        let mut commands: HashMap<String, String> = DEFAULT_VALIDATORS
+            .iter()
+            .map(|(ext, cmd)| (ext.to_string(), cmd.to_string()))
+            .collect();
+        commands.extend(configured);
+
+        Self { commands }
+    }
+
+    /// Validate processed content for the given output path
+    ///
+    /// Returns `None` when no check is configured for the file's extension.
+    pub fn validate(&self, rel_path: &str, content: &[u8]) -> Result<Option<ValidationOutcome>> {
+        let Some(ext) = Path::new(rel_path).extension().and_then(|e| e.to_str()) else {
+            return Ok(None);
+        };
+
+        if let Some(command) = self.commands.get(ext) {
+            return self.run_command(command, ext, content).map(Some);
+        }
+
+        // JSON can be checked without an external tool
+        if ext == "json" {
+            return Ok(Some(
+                match serde_json::from_slice::<serde_json::Value>(content) {
+                    Ok(_) => ValidationOutcome::Valid,
+                    Err(err) => ValidationOutcome::Invalid(err.to_string()),
+                },
+            ));
+        }
+
+        Ok(None)
+    }
+
+    fn run_command(&self, command: &str, ext: &str, content: &[u8]) -> Result<ValidationOutcome> {
+        let mut file = tempfile::Builder::new()
+            .prefix("scaffer-validate-")
+            .suffix(&format!(".{ext}"))
+            .tempfile()
+            .context("Failed to create temporary file for validation")?;
+        file.write_all(content)
+            .context("Failed to write temporary file for validation")?;
+
+        if command.trim().is_empty() {
+            bail!("Empty validator command for .{ext} files");
+        }
+
+        let file_path = file.path().to_string_lossy().to_string();
+        let mut args: Vec<String> = command
+            .split_whitespace()
+            .map(|arg| arg.replace("{file}", &file_path))
+            .collect();
+        if !command.contains("{file}") {
+            args.push(file_path);
+        }
+
+        let output = Command::new(&args[0])
+            .args(&args[1..])
+            .output()
+            .with_context(|| format!("Failed to run validator '{command}'"))?;
+
+        if output.status.success() {
+            Ok(ValidationOutcome::Valid)
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            Ok(ValidationOutcome::Invalid(if stderr.is_empty() {
+                stdout
+            } else {
+                stderr
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_json_validation() {
+        let validator = Validator::new(HashMap::new());
+
+        assert_eq!(
+            validator.validate("config.json", br#"{"a": 1}"#).unwrap(),
+            Some(ValidationOutcome::Valid)
+        );
+        assert!(matches!(
+            validator.validate("config.json", b"{\"a\": }").unwrap(),
+            Some(ValidationOutcome::Invalid(_))
+        ));
+        assert_eq!(validator.validate("README.md", b"# hi").unwrap(), None);
+    }
+}